@@ -1,3 +1,4 @@
+-- IF NOT EXISTS lets databases created from the old schema.sql adopt this history
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 
 CREATE TABLE IF NOT EXISTS films (
@@ -8,4 +9,4 @@ CREATE TABLE IF NOT EXISTS films (
     poster text NOT NULL,
     created_at timestamp with time zone default CURRENT_TIMESTAMP,
     updated_at timestamp with time zone
-);
\ No newline at end of file
+);
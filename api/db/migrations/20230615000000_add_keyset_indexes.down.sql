@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS films_year_id_idx;
+DROP INDEX IF EXISTS films_director_id_idx;
+DROP INDEX IF EXISTS films_title_id_idx;
+DROP INDEX IF EXISTS films_created_at_id_idx;
@@ -0,0 +1,5 @@
+-- (sort column, id) indexes backing keyset pagination
+CREATE INDEX IF NOT EXISTS films_created_at_id_idx ON films (created_at NULLS FIRST, id);
+CREATE INDEX IF NOT EXISTS films_title_id_idx ON films (title COLLATE "C", id);
+CREATE INDEX IF NOT EXISTS films_director_id_idx ON films (director COLLATE "C", id);
+CREATE INDEX IF NOT EXISTS films_year_id_idx ON films (year, id);
@@ -0,0 +1,2 @@
+-- optimistic concurrency control, bumped on every write
+ALTER TABLE films ADD COLUMN IF NOT EXISTS version integer NOT NULL DEFAULT 1;
@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS films_maintain_timestamps ON films;
+DROP FUNCTION IF EXISTS films_maintain_timestamps();
+
+ALTER TABLE films
+    ALTER COLUMN created_at DROP NOT NULL,
+    ALTER COLUMN created_at SET DEFAULT CURRENT_TIMESTAMP;
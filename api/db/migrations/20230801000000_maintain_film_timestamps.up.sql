@@ -0,0 +1,18 @@
+-- created_at is set once on insert and never changes
+UPDATE films SET created_at = coalesce(updated_at, now()) WHERE created_at IS NULL;
+ALTER TABLE films
+    ALTER COLUMN created_at SET DEFAULT now(),
+    ALTER COLUMN created_at SET NOT NULL;
+
+-- every update stamps updated_at, whichever statement or client issues it
+CREATE OR REPLACE FUNCTION films_maintain_timestamps() RETURNS trigger AS $$
+BEGIN
+    NEW.created_at := OLD.created_at;
+    NEW.updated_at := now();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER films_maintain_timestamps
+    BEFORE UPDATE ON films
+    FOR EACH ROW EXECUTE FUNCTION films_maintain_timestamps();
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS films_notify_change ON films;
+DROP FUNCTION IF EXISTS films_notify_change();
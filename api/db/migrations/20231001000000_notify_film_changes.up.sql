@@ -0,0 +1,25 @@
+-- announce every row change on the film_changes channel, so other API
+-- instances can drop what they cached; the payload is small on purpose,
+-- NOTIFY payloads are capped at 8000 bytes
+CREATE OR REPLACE FUNCTION films_notify_change() RETURNS trigger AS $$
+DECLARE
+    film films%ROWTYPE;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        film := OLD;
+    ELSE
+        film := NEW;
+    END IF;
+
+    PERFORM pg_notify('film_changes', json_build_object(
+        'kind', CASE TG_OP WHEN 'INSERT' THEN 'created' WHEN 'UPDATE' THEN 'updated' ELSE 'deleted' END,
+        'id', film.id,
+        'version', film.version
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER films_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON films
+    FOR EACH ROW EXECUTE FUNCTION films_notify_change();
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS films_search_idx;
+ALTER TABLE films DROP COLUMN IF EXISTS search;
+DROP FUNCTION IF EXISTS films_unaccent(text);
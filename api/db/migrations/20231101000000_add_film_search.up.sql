@@ -0,0 +1,17 @@
+-- full-text search over titles and directors, ignoring case and accents;
+-- unaccent() is only STABLE because its dictionary could change, so a
+-- generated column needs this IMMUTABLE wrapper pinned to the default one
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+CREATE OR REPLACE FUNCTION films_unaccent(text) RETURNS text AS $$
+    SELECT public.unaccent('public.unaccent'::regdictionary, $1)
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+-- title words rank as A and director words as B, which ts_rank() weighs
+-- 1.0 and 0.4 by default
+ALTER TABLE films ADD COLUMN search tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', films_unaccent(title)), 'A') ||
+    setweight(to_tsvector('simple', films_unaccent(director)), 'B')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS films_search_idx ON films USING gin (search);
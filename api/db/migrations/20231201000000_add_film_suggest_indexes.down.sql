@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS films_director_trgm_idx;
+DROP INDEX IF EXISTS films_title_trgm_idx;
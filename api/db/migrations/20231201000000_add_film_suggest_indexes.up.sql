@@ -0,0 +1,8 @@
+-- trigram indexes over the folded titles and directors, so suggesting the
+-- values a prefix completes with LIKE 'prefix%' doesn't scan every film
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS films_title_trgm_idx
+    ON films USING gin (lower(films_unaccent(title)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS films_director_trgm_idx
+    ON films USING gin (lower(films_unaccent(director)) gin_trgm_ops);
@@ -0,0 +1,13 @@
+-- keys are only stored as the hex SHA-256 of the whole key, which is random
+-- enough that a plain hash can't be brute forced; the unique index finds a
+-- presented key by its hash
+CREATE TABLE IF NOT EXISTS api_keys (
+    id uuid NOT NULL CONSTRAINT api_keys_pkey PRIMARY KEY,
+    name text NOT NULL,
+    prefix text NOT NULL,
+    key_hash text NOT NULL CONSTRAINT api_keys_key_hash_key UNIQUE,
+    scope text NOT NULL CONSTRAINT api_keys_scope_check CHECK (scope IN ('read', 'write')),
+    created_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at timestamp with time zone,
+    revoked_at timestamp with time zone
+);
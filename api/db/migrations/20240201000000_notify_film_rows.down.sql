@@ -0,0 +1,20 @@
+CREATE OR REPLACE FUNCTION films_notify_change() RETURNS trigger AS $$
+DECLARE
+    film films%ROWTYPE;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        film := OLD;
+    ELSE
+        film := NEW;
+    END IF;
+
+    PERFORM pg_notify('film_changes', json_build_object(
+        'kind', CASE TG_OP WHEN 'INSERT' THEN 'created' WHEN 'UPDATE' THEN 'updated' ELSE 'deleted' END,
+        'id', film.id,
+        'version', film.version
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP FUNCTION IF EXISTS films_notify_row(films);
@@ -0,0 +1,51 @@
+-- carry the written film, and the film an update replaced, in film_changes
+-- notifications, so other instances can stream changes they didn't make.
+-- NOTIFY payloads are capped at 8000 bytes: a payload that would exceed it
+-- drops `previous`, then `film`, which listeners answer with a resync
+
+-- the film as the API serializes it, without the search column
+CREATE FUNCTION films_notify_row(film films) RETURNS jsonb AS $$
+    SELECT jsonb_build_object(
+        'id', film.id,
+        'title', film.title,
+        'director', film.director,
+        'year', film.year,
+        'poster', film.poster,
+        'created_at', film.created_at,
+        'updated_at', film.updated_at,
+        'version', film.version
+    );
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION films_notify_change() RETURNS trigger AS $$
+DECLARE
+    film films%ROWTYPE;
+    change jsonb;
+    payload text;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        film := OLD;
+    ELSE
+        film := NEW;
+    END IF;
+
+    change := jsonb_build_object(
+        'kind', CASE TG_OP WHEN 'INSERT' THEN 'created' WHEN 'UPDATE' THEN 'updated' ELSE 'deleted' END,
+        'id', film.id,
+        'version', film.version
+    );
+    payload := (change || jsonb_build_object(
+        'film', films_notify_row(film),
+        'previous', CASE TG_OP WHEN 'UPDATE' THEN films_notify_row(OLD) END
+    ))::text;
+    IF octet_length(payload) >= 8000 THEN
+        payload := (change || jsonb_build_object('film', films_notify_row(film)))::text;
+    END IF;
+    IF octet_length(payload) >= 8000 THEN
+        payload := change::text;
+    END IF;
+
+    PERFORM pg_notify('film_changes', payload);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
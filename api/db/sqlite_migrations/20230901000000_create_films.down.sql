@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS films_keep_created_at;
+DROP INDEX IF EXISTS films_year_id_idx;
+DROP INDEX IF EXISTS films_director_id_idx;
+DROP INDEX IF EXISTS films_title_id_idx;
+DROP INDEX IF EXISTS films_created_at_id_idx;
+DROP TABLE IF EXISTS films;
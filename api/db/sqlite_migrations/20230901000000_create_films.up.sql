@@ -0,0 +1,26 @@
+-- ids are 16 byte UUID blobs and timestamps fixed-width RFC 3339 text
+-- (microseconds, UTC), so both sort the same way as in Postgres
+CREATE TABLE IF NOT EXISTS films (
+    id blob NOT NULL CONSTRAINT films_pkey PRIMARY KEY,
+    title text NOT NULL,
+    director text NOT NULL,
+    year integer NOT NULL,
+    poster text NOT NULL,
+    created_at text NOT NULL,
+    updated_at text,
+    version integer NOT NULL DEFAULT 1
+);
+
+-- (sort column, id) indexes backing keyset pagination
+CREATE INDEX IF NOT EXISTS films_created_at_id_idx ON films (created_at, id);
+CREATE INDEX IF NOT EXISTS films_title_id_idx ON films (title, id);
+CREATE INDEX IF NOT EXISTS films_director_id_idx ON films (director, id);
+CREATE INDEX IF NOT EXISTS films_year_id_idx ON films (year, id);
+
+-- created_at is set once on insert and never changes
+CREATE TRIGGER IF NOT EXISTS films_keep_created_at
+    AFTER UPDATE OF created_at ON films
+    FOR EACH ROW WHEN NEW.created_at IS NOT OLD.created_at
+BEGIN
+    UPDATE films SET created_at = OLD.created_at WHERE id = NEW.id;
+END;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS films_search_delete;
+DROP TRIGGER IF EXISTS films_search_update;
+DROP TRIGGER IF EXISTS films_search_insert;
+DROP TABLE IF EXISTS films_search;
@@ -0,0 +1,29 @@
+-- full-text search over titles and directors, ignoring case and accents;
+-- films has no INTEGER PRIMARY KEY, so its rowids may change on VACUUM and
+-- the index keeps its own copy of each row, keyed by film id
+CREATE VIRTUAL TABLE IF NOT EXISTS films_search USING fts5(
+    id UNINDEXED,
+    title,
+    director,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO films_search (id, title, director) SELECT id, title, director FROM films;
+
+CREATE TRIGGER IF NOT EXISTS films_search_insert
+    AFTER INSERT ON films
+BEGIN
+    INSERT INTO films_search (id, title, director) VALUES (NEW.id, NEW.title, NEW.director);
+END;
+
+CREATE TRIGGER IF NOT EXISTS films_search_update
+    AFTER UPDATE OF title, director ON films
+BEGIN
+    UPDATE films_search SET title = NEW.title, director = NEW.director WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS films_search_delete
+    AFTER DELETE ON films
+BEGIN
+    DELETE FROM films_search WHERE id = OLD.id;
+END;
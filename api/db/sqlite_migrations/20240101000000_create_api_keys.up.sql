@@ -0,0 +1,13 @@
+-- laid out like the Postgres table, with the films table's uuid and
+-- timestamp encodings; keys are only stored as the hex SHA-256 of the whole
+-- key, found by the unique index when presented
+CREATE TABLE IF NOT EXISTS api_keys (
+    id blob NOT NULL CONSTRAINT api_keys_pkey PRIMARY KEY,
+    name text NOT NULL,
+    prefix text NOT NULL,
+    key_hash text NOT NULL CONSTRAINT api_keys_key_hash_key UNIQUE,
+    scope text NOT NULL CONSTRAINT api_keys_scope_check CHECK (scope IN ('read', 'write')),
+    created_at text NOT NULL,
+    last_used_at text,
+    revoked_at text
+);
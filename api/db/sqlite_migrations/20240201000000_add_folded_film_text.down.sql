@@ -0,0 +1,2 @@
+ALTER TABLE films DROP COLUMN director_folded;
+ALTER TABLE films DROP COLUMN title_folded;
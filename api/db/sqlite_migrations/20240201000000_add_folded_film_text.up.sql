@@ -0,0 +1,6 @@
+-- title and director lowercased the way Rust's str::to_lowercase does, for
+-- the case-insensitive filters; SQLite's lower() only folds ASCII, so the
+-- repository writes these, and rows written before them are folded once
+-- the migrations have run
+ALTER TABLE films ADD COLUMN title_folded text;
+ALTER TABLE films ADD COLUMN director_folded text;
@@ -0,0 +1,163 @@
+//! Throughput of `MemoryFilmRepository` listings, alone and under
+//! concurrent readers and writers.
+//!
+//! ```text
+//! cargo bench -p api-lib --bench memory_film_repository
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use api_lib::film_repository::{FilmCursor, FilmRepository, ListFilms, MemoryFilmRepository};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shared::models::{CreateFilm, FilmPatch, FilmSort, SortOrder};
+use tokio::runtime::Runtime;
+
+const FILMS: usize = 10_000;
+const WORKERS: usize = 4;
+/// Operations per task in the concurrent benchmark; one in ten is a write.
+const OPS_PER_TASK: usize = 200;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(WORKERS)
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn seeded(rt: &Runtime) -> Arc<MemoryFilmRepository> {
+    let repo = Arc::new(MemoryFilmRepository::new());
+    rt.block_on(async {
+        for i in 0..FILMS {
+            let film = CreateFilm {
+                title: format!("Film {:05}", (i * 7919) % FILMS),
+                director: format!("Director {}", i % 100),
+                year: 1950 + (i % 70) as u16,
+                poster: "https://example.com/poster.png".to_string(),
+            };
+            repo.create_film(&film).await.unwrap();
+        }
+    });
+    repo
+}
+
+fn listing(c: &mut Criterion) {
+    let rt = runtime();
+    let repo = seeded(&rt);
+    let middle = rt.block_on(async {
+        let query = ListFilms {
+            sort: FilmSort::Title,
+            offset: (FILMS / 2) as u32,
+            limit: 1,
+            ..Default::default()
+        };
+        repo.get_films(&query).await.unwrap().remove(0)
+    });
+
+    let queries = [
+        ("first_page_by_created_at", ListFilms::default()),
+        (
+            "cursor_page_by_title",
+            ListFilms {
+                sort: FilmSort::Title,
+                after: Some(FilmCursor::after(&middle, FilmSort::Title, SortOrder::Asc)),
+                ..Default::default()
+            },
+        ),
+        (
+            "year_range_by_year_desc",
+            ListFilms {
+                sort: FilmSort::Year,
+                order: SortOrder::Desc,
+                year_from: Some(1980),
+                year_to: Some(1989),
+                ..Default::default()
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("memory_listing");
+    for (name, query) in queries {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &query, |b, query| {
+            b.to_async(&rt)
+                .iter(|| async { repo.get_films(query).await.unwrap() });
+        });
+    }
+    group.finish();
+}
+
+fn concurrent_reads_and_writes(c: &mut Criterion) {
+    let rt = runtime();
+    let repo = seeded(&rt);
+    let ids = rt.block_on(async {
+        let query = ListFilms {
+            limit: 100,
+            ..Default::default()
+        };
+        repo.get_films(&query)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|film| film.id)
+            .collect::<Vec<_>>()
+    });
+
+    let mut group = c.benchmark_group("memory_concurrent");
+    for tasks in [1, 4, 16] {
+        group.throughput(Throughput::Elements((tasks * OPS_PER_TASK) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(tasks), &tasks, |b, &tasks| {
+            b.to_async(&rt).iter_custom(|iters| {
+                let repo = repo.clone();
+                let ids = ids.clone();
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let handles = (0..tasks)
+                            .map(|task| tokio::spawn(mixed_load(repo.clone(), ids.clone(), task)))
+                            .collect::<Vec<_>>();
+                        for handle in handles {
+                            handle.await.unwrap();
+                        }
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+async fn mixed_load(repo: Arc<MemoryFilmRepository>, ids: Vec<uuid::Uuid>, task: usize) {
+    let year = 1950 + (task % 70) as u16;
+    let by_year = ListFilms {
+        sort: FilmSort::Year,
+        year_from: Some(year),
+        year_to: Some(year),
+        ..Default::default()
+    };
+
+    for op in 0..OPS_PER_TASK {
+        let id = ids[(task * OPS_PER_TASK + op) % ids.len()];
+        match op % 10 {
+            0 => {
+                let patch = FilmPatch {
+                    year: Some(Some(1950 + (op % 70) as u16)),
+                    ..Default::default()
+                };
+                repo.patch_film(&id, &patch, None).await.unwrap();
+            }
+            1..=4 => {
+                repo.get_films(&by_year).await.unwrap();
+            }
+            _ => {
+                repo.get_film(&id).await.unwrap();
+            }
+        }
+    }
+}
+
+criterion_group!(benches, listing, concurrent_reads_and_writes);
+criterion_main!(benches);
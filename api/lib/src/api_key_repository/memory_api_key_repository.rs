@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use chrono::{SubsecRound, Utc};
+use shared::models::{ApiKey, CreateApiKey, CreatedApiKey};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{hash_key, records_use, ApiKeyError, ApiKeyRepository, ApiKeyResult, NewApiKey};
+
+/// API keys kept in memory, by hash; they are lost on exit.
+#[derive(Debug, Default)]
+pub struct MemoryApiKeyRepository {
+    keys: RwLock<HashMap<String, ApiKey>>,
+}
+
+impl MemoryApiKeyRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyRepository for MemoryApiKeyRepository {
+    async fn create_api_key(&self, create: &CreateApiKey) -> ApiKeyResult<CreatedApiKey> {
+        let new = NewApiKey::generate();
+        let api_key = ApiKey {
+            id: new.id,
+            name: create.name.clone(),
+            prefix: new.prefix,
+            scope: create.scope,
+            created_at: Utc::now().trunc_subsecs(6),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        self.keys.write().await.insert(new.hash, api_key.clone());
+
+        Ok(CreatedApiKey {
+            api_key,
+            key: new.key,
+        })
+    }
+
+    async fn list_api_keys(&self) -> ApiKeyResult<Vec<ApiKey>> {
+        let mut keys = self.keys.read().await.values().cloned().collect::<Vec<_>>();
+        keys.sort_by_key(|key| (key.created_at, key.id));
+        Ok(keys)
+    }
+
+    async fn revoke_api_key(&self, id: &Uuid) -> ApiKeyResult<ApiKey> {
+        let mut keys = self.keys.write().await;
+        let api_key = keys
+            .values_mut()
+            .find(|key| key.id == *id)
+            .ok_or(ApiKeyError::NotFound(*id))?;
+        api_key
+            .revoked_at
+            .get_or_insert_with(|| Utc::now().trunc_subsecs(6));
+        Ok(api_key.clone())
+    }
+
+    async fn use_api_key(&self, key: &str) -> ApiKeyResult<Option<ApiKey>> {
+        let mut keys = self.keys.write().await;
+        let Some(api_key) = keys
+            .get_mut(&hash_key(key))
+            .filter(|api_key| api_key.revoked_at.is_none())
+        else {
+            return Ok(None);
+        };
+        let now = Utc::now().trunc_subsecs(6);
+        if records_use(api_key, now) {
+            api_key.last_used_at = Some(now);
+        }
+        Ok(Some(api_key.clone()))
+    }
+}
@@ -0,0 +1,201 @@
+//! Storage for the API keys batch jobs and scripts authenticate with, see
+//! [`Authentication`](crate::auth::Authentication).
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use shared::models::{ApiKey, CreateApiKey, CreatedApiKey};
+use shared::validation::ValidationErrors;
+use uuid::Uuid;
+
+use crate::film_repository::FilmError;
+use crate::problem::Problem;
+
+pub use memory_api_key_repository::MemoryApiKeyRepository;
+pub use postgres_api_key_repository::PostgresApiKeyRepository;
+pub use sqlite_api_key_repository::SqliteApiKeyRepository;
+
+mod memory_api_key_repository;
+mod postgres_api_key_repository;
+mod sqlite_api_key_repository;
+
+/// Start of every API key, telling keys apart from JWTs in an
+/// `Authorization: Bearer` header.
+pub const API_KEY_PREFIX: &str = "fk_";
+/// Characters of a key kept in [`ApiKey::prefix`], [`API_KEY_PREFIX`]
+/// included.
+const DISPLAYED_CHARS: usize = 11;
+/// Seconds [`ApiKey::last_used_at`] may lag behind a key's latest use, so
+/// a busy key isn't written on every request.
+pub const LAST_USED_PRECISION_SECS: i64 = 60;
+
+/// Errors produced by every [`ApiKeyRepository`] implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyError {
+    #[error("API key with id {0} does not exist")]
+    NotFound(Uuid),
+    #[error("Validation failed: {0}")]
+    Validation(ValidationErrors),
+    #[error("API key store unavailable: {0}")]
+    Unavailable(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+pub type ApiKeyResult<T> = Result<T, ApiKeyError>;
+
+impl From<ValidationErrors> for ApiKeyError {
+    fn from(errors: ValidationErrors) -> Self {
+        ApiKeyError::Validation(errors)
+    }
+}
+
+impl From<sqlx::Error> for ApiKeyError {
+    /// Classified like film queries; the key queries only bind values they
+    /// generate, so anything but an outage is a bug.
+    fn from(e: sqlx::Error) -> Self {
+        match FilmError::from(e) {
+            FilmError::Unavailable(message) => ApiKeyError::Unavailable(message),
+            e => ApiKeyError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl ResponseError for ApiKeyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiKeyError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiKeyError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiKeyError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiKeyError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            tracing::error!("{}", self);
+        }
+
+        Problem::from(self).error_response()
+    }
+}
+
+impl From<&ApiKeyError> for Problem {
+    fn from(e: &ApiKeyError) -> Self {
+        let status = e.status_code();
+        match e {
+            ApiKeyError::NotFound(_) => {
+                Problem::new(status, "/problems/api-key-not-found", "API key not found")
+                    .with_detail(e.to_string())
+            }
+            ApiKeyError::Validation(errors) => {
+                Problem::new(status, "/problems/validation-failed", "Validation failed")
+                    .with_detail(format!(
+                        "{} field(s) failed validation",
+                        errors.errors().len()
+                    ))
+                    .with_errors(errors.errors())
+            }
+            // don't leak storage internals to the client
+            ApiKeyError::Unavailable(_) => Problem::new(
+                status,
+                "/problems/service-unavailable",
+                "Service unavailable",
+            ),
+            ApiKeyError::Internal(_) => {
+                Problem::new(status, "/problems/internal-error", "Internal server error")
+            }
+        }
+    }
+}
+
+/// Storage for API keys.
+///
+/// Keys are stored as their [`hash_key`] only, so a presented key is found
+/// by hashing it.
+#[async_trait::async_trait]
+pub trait ApiKeyRepository: Send + Sync + 'static {
+    /// Stores a new key, returned with the key itself.
+    async fn create_api_key(&self, create: &CreateApiKey) -> ApiKeyResult<CreatedApiKey>;
+    /// Every key, revoked ones included, oldest first.
+    async fn list_api_keys(&self) -> ApiKeyResult<Vec<ApiKey>>;
+    /// Revokes a key; revoking it again keeps the first revocation time.
+    async fn revoke_api_key(&self, id: &Uuid) -> ApiKeyResult<ApiKey>;
+    /// The unrevoked key `key` is, after recording it as used now unless
+    /// a use within [`LAST_USED_PRECISION_SECS`] already is.
+    async fn use_api_key(&self, key: &str) -> ApiKeyResult<Option<ApiKey>>;
+}
+
+/// A freshly generated key, with what gets stored of it.
+pub(crate) struct NewApiKey {
+    pub id: Uuid,
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+impl NewApiKey {
+    /// A key holding 244 random bits, after [`API_KEY_PREFIX`].
+    pub fn generate() -> Self {
+        let random = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+        let key = format!("{}{}", API_KEY_PREFIX, URL_SAFE_NO_PAD.encode(random));
+        Self {
+            id: Uuid::new_v4(),
+            prefix: key[..DISPLAYED_CHARS].to_string(),
+            hash: hash_key(&key),
+            key,
+        }
+    }
+}
+
+/// Whether using `api_key` at `now` should be recorded, its last recorded
+/// use being older than [`LAST_USED_PRECISION_SECS`].
+pub(crate) fn records_use(api_key: &ApiKey, now: DateTime<Utc>) -> bool {
+    !matches!(
+        api_key.last_used_at,
+        Some(last_used) if now - last_used < Duration::seconds(LAST_USED_PRECISION_SECS)
+    )
+}
+
+/// The hex SHA-256 of `key`, as stored.
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_distinct_and_only_hashes_are_kept() {
+        let first = NewApiKey::generate();
+        let second = NewApiKey::generate();
+
+        assert_ne!(first.key, second.key);
+        assert!(first.key.starts_with(API_KEY_PREFIX));
+        assert!(first.key.starts_with(&first.prefix));
+        assert_eq!(first.hash, hash_key(&first.key));
+        assert_eq!(first.hash.len(), 64);
+        assert!(!first.hash.contains(&first.key[API_KEY_PREFIX.len()..]));
+    }
+
+    #[test]
+    fn uses_are_recorded_once_the_last_one_is_stale() {
+        let now = Utc::now();
+        let used = |secs_ago: Option<i64>| ApiKey {
+            id: Uuid::new_v4(),
+            name: "nightly".to_string(),
+            prefix: API_KEY_PREFIX.to_string(),
+            scope: Default::default(),
+            created_at: now,
+            last_used_at: secs_ago.map(|secs| now - Duration::seconds(secs)),
+            revoked_at: None,
+        };
+
+        assert!(records_use(&used(None), now));
+        assert!(!records_use(&used(Some(1)), now));
+        assert!(records_use(&used(Some(LAST_USED_PRECISION_SECS)), now));
+    }
+}
@@ -0,0 +1,85 @@
+use shared::models::{ApiKey, CreateApiKey, CreatedApiKey};
+use uuid::Uuid;
+
+use crate::film_repository::{sqlite_now, sqlite_timestamp};
+
+use super::{hash_key, records_use, ApiKeyError, ApiKeyRepository, ApiKeyResult, NewApiKey};
+
+/// API keys in the `api_keys` table of a SQLite database migrated with
+/// [`SQLITE_MIGRATOR`](crate::migrations::SQLITE_MIGRATOR); timestamps are
+/// stored like [`SqliteFilmRepository`](crate::film_repository::SqliteFilmRepository)'s.
+pub struct SqliteApiKeyRepository {
+    pool: sqlx::SqlitePool,
+}
+
+impl SqliteApiKeyRepository {
+    pub fn new(pool: sqlx::SqlitePool) -> SqliteApiKeyRepository {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyRepository for SqliteApiKeyRepository {
+    async fn create_api_key(&self, create: &CreateApiKey) -> ApiKeyResult<CreatedApiKey> {
+        let new = NewApiKey::generate();
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"INSERT INTO api_keys (id, name, prefix, key_hash, scope, created_at) VALUES (?, ?, ?, ?, ?, ?) RETURNING id, name, prefix, scope, created_at, last_used_at, revoked_at"#,
+        )
+        .bind(new.id)
+        .bind(&create.name)
+        .bind(&new.prefix)
+        .bind(&new.hash)
+        .bind(create.scope.as_str())
+        .bind(sqlite_timestamp(&sqlite_now()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(CreatedApiKey {
+            api_key,
+            key: new.key,
+        })
+    }
+
+    async fn list_api_keys(&self) -> ApiKeyResult<Vec<ApiKey>> {
+        sqlx::query_as::<_, ApiKey>(
+            r#"SELECT id, name, prefix, scope, created_at, last_used_at, revoked_at FROM api_keys ORDER BY created_at, id"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(ApiKeyError::from)
+    }
+
+    async fn revoke_api_key(&self, id: &Uuid) -> ApiKeyResult<ApiKey> {
+        sqlx::query_as::<_, ApiKey>(
+            r#"UPDATE api_keys SET revoked_at = coalesce(revoked_at, ?) WHERE id = ? RETURNING id, name, prefix, scope, created_at, last_used_at, revoked_at"#,
+        )
+        .bind(sqlite_timestamp(&sqlite_now()))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(ApiKeyError::NotFound(*id))
+    }
+
+    async fn use_api_key(&self, key: &str) -> ApiKeyResult<Option<ApiKey>> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"SELECT id, name, prefix, scope, created_at, last_used_at, revoked_at FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL"#,
+        )
+        .bind(hash_key(key))
+        .fetch_optional(&self.pool)
+        .await?;
+        let now = sqlite_now();
+        let api_key = match api_key {
+            Some(api_key) if records_use(&api_key, now) => api_key,
+            api_key => return Ok(api_key),
+        };
+
+        sqlx::query_as::<_, ApiKey>(
+            r#"UPDATE api_keys SET last_used_at = ? WHERE id = ? AND revoked_at IS NULL RETURNING id, name, prefix, scope, created_at, last_used_at, revoked_at"#,
+        )
+        .bind(sqlite_timestamp(&now))
+        .bind(api_key.id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(ApiKeyError::from)
+    }
+}
@@ -0,0 +1,61 @@
+use actix_web::web::{self, ServiceConfig};
+use actix_web::HttpResponse;
+use shared::models::CreateApiKey;
+use shared::validation::Validate;
+use uuid::Uuid;
+
+use crate::api_key_repository::{ApiKeyRepository, ApiKeyResult};
+use crate::auth::Admin;
+use crate::problem;
+
+/// Mount point of the API key routes.
+pub const API_KEYS_PATH: &str = "/v1/api-keys";
+
+/// API key management at [`API_KEYS_PATH`], for [`Admin`]s only.
+///
+/// Needs a `web::Data<dyn ApiKeyRepository>` registered as app data.
+pub fn service(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope(API_KEYS_PATH)
+            .app_data(problem::json_config())
+            .app_data(problem::path_config())
+            .route("", web::get().to(list_api_keys))
+            .route("", web::post().to(create_api_key))
+            .route("/{api_key_id}", web::delete().to(revoke_api_key)),
+    );
+}
+
+pub async fn list_api_keys(
+    _: Admin,
+    api_keys: web::Data<dyn ApiKeyRepository>,
+) -> ApiKeyResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(api_keys.list_api_keys().await?))
+}
+
+/// Creates a key, answering with the key itself; it can't be shown again.
+pub async fn create_api_key(
+    Admin(admin): Admin,
+    api_keys: web::Data<dyn ApiKeyRepository>,
+    create: web::Json<CreateApiKey>,
+) -> ApiKeyResult<HttpResponse> {
+    create.validate()?;
+    let created = api_keys.create_api_key(&create).await?;
+    tracing::info!(
+        "{} created API key {} ({})",
+        admin.subject,
+        created.api_key.id,
+        created.api_key.name
+    );
+    Ok(HttpResponse::Ok().json(created))
+}
+
+/// Revokes a key, which is kept and listed with its revocation time.
+pub async fn revoke_api_key(
+    Admin(admin): Admin,
+    api_keys: web::Data<dyn ApiKeyRepository>,
+    api_key_id: web::Path<Uuid>,
+) -> ApiKeyResult<HttpResponse> {
+    let api_key = api_keys.revoke_api_key(&api_key_id).await?;
+    tracing::info!("{} revoked API key {}", admin.subject, api_key.id);
+    Ok(HttpResponse::Ok().json(api_key))
+}
@@ -0,0 +1,107 @@
+use actix_cors::Cors;
+use actix_web::http::header;
+use actix_web::middleware::Condition;
+use actix_web::web::{self, ServiceConfig};
+use std::sync::Arc;
+
+use crate::api_key_repository::ApiKeyRepository;
+use crate::auth::Authentication;
+use crate::film_repository::{CursorCodec, FilmRepository};
+use crate::problem::RequestId;
+use crate::routes::{hello_world, ping};
+use crate::settings::{CorsSettings, Settings};
+use crate::ws::Presence;
+use crate::{api_keys, films, health, ws};
+
+/// Every route of the app as `settings` configure them, for any film and
+/// API key repository; shared by the Shuttle and standalone entry points.
+///
+/// Routes needing more than the repository, like `/version`, must be
+/// registered before this, since the static files are served from `/`.
+pub fn configure<R: FilmRepository, K: ApiKeyRepository>(
+    settings: web::Data<Settings>,
+    repo: web::Data<R>,
+    api_keys: web::Data<K>,
+) -> impl Fn(&mut ServiceConfig) + Send + Clone + 'static {
+    let cursor_codec = settings
+        .api
+        .cursor_secret
+        .as_ref()
+        .map(|secret| web::Data::new(CursorCodec::new(secret.expose())));
+    let events = repo.events().map(web::Data::from);
+    let presence = web::Data::new(Presence::new());
+    let api_keys: Arc<dyn ApiKeyRepository> = api_keys.into_inner();
+    let authentication = Authentication::new(&settings.auth).with_api_keys(api_keys.clone());
+    let api_keys = web::Data::from(api_keys);
+
+    move |cfg: &mut ServiceConfig| {
+        let cors_enabled = !settings.cors.allowed_origins.is_empty();
+        let cursor_codec = cursor_codec.clone();
+        let events = events.clone();
+        cfg.service(
+            web::scope("/api")
+                .wrap(authentication.clone())
+                .wrap(Condition::new(cors_enabled, cors(&settings.cors)))
+                .wrap(RequestId)
+                .app_data(settings.clone())
+                .app_data(repo.clone())
+                .app_data(presence.clone())
+                .app_data(api_keys.clone())
+                .configure(move |cfg| {
+                    if let Some(codec) = cursor_codec {
+                        cfg.app_data(codec);
+                    }
+                    if let Some(events) = events {
+                        cfg.app_data(events);
+                    }
+                })
+                .configure(api_keys::service)
+                .configure(health::service)
+                .configure(ws::service::<R>)
+                .service(films::scope::<R>(
+                    &settings.api.films_path,
+                    &settings.features,
+                )),
+        )
+        .service(hello_world)
+        .service(ping);
+
+        if settings.features.static_files {
+            cfg.service(
+                actix_files::Files::new("/", &settings.server.static_dir)
+                    .show_files_listing()
+                    .index_file("index.html"),
+            );
+        }
+    }
+}
+
+/// CORS policy allowing the configured origins to use the whole film API,
+/// including its conditional request and authorization headers.
+pub fn cors(settings: &CorsSettings) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(["GET", "POST", "PUT", "PATCH", "DELETE"])
+        .allowed_headers([
+            header::AUTHORIZATION,
+            header::HeaderName::from_static(crate::auth::API_KEY_HEADER),
+            header::CONTENT_TYPE,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            crate::events::LAST_EVENT_ID,
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LAST_MODIFIED,
+            header::HeaderName::from_static(crate::problem::REQUEST_ID_HEADER),
+        ])
+        .max_age(settings.max_age_secs);
+
+    for origin in &settings.allowed_origins {
+        cors = match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        };
+    }
+    cors
+}
@@ -0,0 +1,682 @@
+//! Bearer token and API key authentication.
+//!
+//! [`Authentication`] checks the signed JWT in a request's
+//! `Authorization: Bearer` header, or the API key it presents, and hands
+//! handlers the [`Principal`] it names. Tokens are HS256 with the
+//! configured secret, or RS256 with a key from the configured JSON Web Key
+//! Set file; keys are looked up in an [`ApiKeyRepository`], but for the
+//! configured admin key. See [`AuthSettings`](crate::settings::AuthSettings)
+//! for which requests need credentials.
+
+use std::fs;
+use std::future::{ready, Future, Ready};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use shared::models::{ApiKey, ApiKeyScope};
+
+use crate::api_key_repository::{hash_key, ApiKeyError, ApiKeyRepository, API_KEY_PREFIX};
+use crate::problem::Problem;
+use crate::settings::{AuthSettings, Settings};
+
+/// Header API keys may be sent in, instead of `Authorization`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Token scope allowing to manage API keys.
+pub const ADMIN_SCOPE: &str = "admin";
+/// Token scope allowing to create, change and delete films.
+pub const WRITE_SCOPE: &str = "films:write";
+/// [`Principal::subject`] of requests made with
+/// [`AuthSettings::admin_key`].
+pub const ADMIN_KEY_SUBJECT: &str = "admin-key";
+
+/// Who made a request, as established by [`Authentication`].
+///
+/// Handlers take it as an extractor, which answers 401 when the request
+/// carried no valid credentials, or as `Option<Principal>` when they serve
+/// anonymous requests too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// The token's `sub` claim, `api-key:<id>` for API keys, or
+    /// [`ADMIN_KEY_SUBJECT`].
+    pub subject: String,
+    /// The token's space separated `scope` claim; for API keys,
+    /// [`WRITE_SCOPE`] when they may write.
+    pub scopes: Vec<String>,
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+impl From<ApiKey> for Principal {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            subject: format!("api-key:{}", api_key.id),
+            scopes: match api_key.scope {
+                ApiKeyScope::Read => Vec::new(),
+                ApiKeyScope::Write => vec![WRITE_SCOPE.to_string()],
+            },
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = Problem;
+    type Future = Ready<Result<Self, Problem>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let principal = req.extensions().get::<Principal>().cloned();
+        ready(principal.ok_or_else(|| unauthorized("This request needs a bearer token or API key")))
+    }
+}
+
+/// A [`Principal`] holding the [`ADMIN_SCOPE`]; extracting it answers 401
+/// without credentials and 403 without the scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Admin(pub Principal);
+
+impl FromRequest for Admin {
+    type Error = Problem;
+    type Future = Ready<Result<Self, Problem>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let admin = Principal::from_request(req, payload)
+            .into_inner()
+            .and_then(|principal| match principal.has_scope(ADMIN_SCOPE) {
+                true => Ok(Admin(principal)),
+                false => Err(forbidden(format!(
+                    "This request needs the {} scope",
+                    ADMIN_SCOPE
+                ))),
+            });
+        ready(admin)
+    }
+}
+
+/// Whoever may write films: a [`Principal`] holding [`WRITE_SCOPE`], or
+/// anyone while [`AuthSettings::anonymous_writes`] opens writes to requests
+/// without credentials. Extracting it answers 401 without credentials and
+/// 403 without the scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilmWriter(pub Option<Principal>);
+
+impl FromRequest for FilmWriter {
+    type Error = Problem;
+    type Future = Ready<Result<Self, Problem>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let anonymous_writes = req
+            .app_data::<web::Data<Settings>>()
+            .is_some_and(|settings| settings.auth.anonymous_writes);
+        let writer = match req.extensions().get::<Principal>() {
+            Some(principal) if principal.has_scope(WRITE_SCOPE) => {
+                Ok(FilmWriter(Some(principal.clone())))
+            }
+            Some(_) => Err(forbidden(format!(
+                "This request needs the {} scope",
+                WRITE_SCOPE
+            ))),
+            None if anonymous_writes => Ok(FilmWriter(None)),
+            None => Err(unauthorized(format!(
+                "This request needs a bearer token or API key with the {} scope",
+                WRITE_SCOPE
+            ))),
+        };
+        ready(writer)
+    }
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    scope: String,
+}
+
+/// Why a token was refused.
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("the token has expired")]
+    Expired,
+    #[error("no configured key matches the token's algorithm and key id")]
+    UnknownKey,
+    #[error("the token is invalid: {0}")]
+    Invalid(jsonwebtoken::errors::Error),
+}
+
+/// Why the configured keys couldn't be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum KeysError {
+    #[error("can't read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{} is not a JSON Web Key Set: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("key {kid:?} of {} is invalid: {source}", path.display())]
+    Key {
+        path: PathBuf,
+        kid: Option<String>,
+        source: jsonwebtoken::errors::Error,
+    },
+    #[error("{} holds no RS256 keys", path.display())]
+    NoKeys { path: PathBuf },
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Checks token signatures and claims against the configured keys.
+pub struct TokenVerifier {
+    keys: Vec<VerifyingKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway_secs: u64,
+}
+
+impl TokenVerifier {
+    /// Loads the HS256 secret and JWKS file `settings` name.
+    pub fn from_settings(settings: &AuthSettings) -> Result<Self, KeysError> {
+        let mut keys = vec![];
+        if let Some(secret) = &settings.hs256_secret {
+            keys.push(VerifyingKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.expose().as_bytes()),
+            });
+        }
+        if let Some(path) = &settings.jwks_file {
+            keys.extend(read_jwks(path)?);
+        }
+
+        Ok(Self {
+            keys,
+            issuer: settings.issuer.clone(),
+            audience: settings.audience.clone(),
+            leeway_secs: settings.leeway_secs,
+        })
+    }
+
+    /// The principal `token` names, once its signature, expiry, issuer and
+    /// audience check out. Tokens carrying a `kid` are only checked against
+    /// the key with that id.
+    pub fn verify(&self, token: &str) -> Result<Principal, TokenError> {
+        let header = jsonwebtoken::decode_header(token).map_err(TokenError::Invalid)?;
+        let candidates = self.keys.iter().filter(|key| {
+            key.algorithm == header.alg
+                && (header.kid.is_none() || key.kid.is_none() || key.kid == header.kid)
+        });
+
+        let mut last_error = TokenError::UnknownKey;
+        for key in candidates {
+            match jsonwebtoken::decode::<Claims>(token, &key.key, &self.validation(key.algorithm)) {
+                Ok(data) => {
+                    return Ok(Principal {
+                        subject: data.claims.sub,
+                        scopes: data
+                            .claims
+                            .scope
+                            .split_whitespace()
+                            .map(str::to_string)
+                            .collect(),
+                    })
+                }
+                Err(e) if *e.kind() == ErrorKind::ExpiredSignature => {
+                    return Err(TokenError::Expired)
+                }
+                Err(e) => last_error = TokenError::Invalid(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = self.leeway_secs;
+        let mut required = vec!["exp", "sub"];
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+        validation
+    }
+}
+
+/// The RS256 keys of the JSON Web Key Set at `path`; keys for other
+/// algorithms are skipped.
+fn read_jwks(path: &Path) -> Result<Vec<VerifyingKey>, KeysError> {
+    let json = fs::read_to_string(path).map_err(|source| KeysError::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+    let set: JwkSet = serde_json::from_str(&json).map_err(|source| KeysError::Parse {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    let mut keys = vec![];
+    for jwk in &set.keys {
+        let rs256 = matches!(jwk.common.algorithm, None | Some(Algorithm::RS256));
+        if !rs256 || !matches!(jwk.algorithm, AlgorithmParameters::RSA(_)) {
+            continue;
+        }
+        let kid = jwk.common.key_id.clone();
+        let key = DecodingKey::from_jwk(jwk).map_err(|source| KeysError::Key {
+            path: path.to_owned(),
+            kid: kid.clone(),
+            source,
+        })?;
+        keys.push(VerifyingKey {
+            kid,
+            algorithm: Algorithm::RS256,
+            key,
+        });
+    }
+
+    if keys.is_empty() {
+        return Err(KeysError::NoKeys {
+            path: path.to_owned(),
+        });
+    }
+    Ok(keys)
+}
+
+/// Checks that `path` holds usable keys, for settings validation.
+pub fn check_jwks(path: &Path) -> Result<(), KeysError> {
+    read_jwks(path).map(drop)
+}
+
+/// Middleware authenticating requests with a bearer token or API key, as
+/// [`AuthSettings`] configure it.
+///
+/// A valid credential's [`Principal`] is stored in the request extensions.
+/// Requests with invalid credentials, and requests needing some without
+/// them, are answered with a 401 problem; read-only API keys get a 403 on
+/// writes. API keys are checked even while tokens are disabled, and film
+/// writes need a [`FilmWriter`] either way.
+#[derive(Clone)]
+pub struct Authentication {
+    verifier: Option<Arc<TokenVerifier>>,
+    api_keys: Option<Arc<dyn ApiKeyRepository>>,
+    /// [`hash_key`] of [`AuthSettings::admin_key`].
+    admin_key: Option<Arc<str>>,
+    public_reads: bool,
+}
+
+impl Authentication {
+    /// Authentication as `settings` configure it; while disabled only API
+    /// keys are checked, and only [`FilmWriter`]s need credentials.
+    ///
+    /// Keys that fail to load are logged and leave no key to check tokens
+    /// against, so a broken key file refuses writes rather than opening
+    /// them up.
+    pub fn new(settings: &AuthSettings) -> Self {
+        let verifier = settings.enabled.then(|| {
+            TokenVerifier::from_settings(settings).unwrap_or_else(|e| {
+                tracing::error!("Refusing every token: {}", e);
+                TokenVerifier {
+                    keys: vec![],
+                    issuer: None,
+                    audience: None,
+                    leeway_secs: 0,
+                }
+            })
+        });
+
+        Self {
+            verifier: verifier.map(Arc::new),
+            api_keys: None,
+            admin_key: settings
+                .admin_key
+                .as_ref()
+                .map(|key| hash_key(key.expose()).into()),
+            public_reads: settings.public_reads,
+        }
+    }
+
+    /// Also accepts the keys stored in `api_keys`.
+    pub fn with_api_keys(mut self, api_keys: Arc<dyn ApiKeyRepository>) -> Self {
+        self.api_keys = Some(api_keys);
+        self
+    }
+
+    /// The principal `req` authenticates as, if any, or why it's refused.
+    async fn authenticate(&self, req: &ServiceRequest) -> Result<Option<Principal>, Refusal> {
+        let read = is_read(req.method());
+
+        match (credentials(req.headers()), &self.verifier) {
+            (Some(Ok(Credentials::ApiKey(key))), _)
+                if self.admin_key.as_deref() == Some(&hash_key(key)) =>
+            {
+                Ok(Some(Principal {
+                    subject: ADMIN_KEY_SUBJECT.to_string(),
+                    scopes: vec![ADMIN_SCOPE.to_string()],
+                }))
+            }
+            (Some(Ok(Credentials::ApiKey(key))), _) => {
+                let api_key = match &self.api_keys {
+                    Some(api_keys) => api_keys.use_api_key(key).await.map_err(Refusal::Store)?,
+                    None => None,
+                };
+                let Some(api_key) = api_key else {
+                    return Err(Refusal::InvalidToken(
+                        "the API key is unknown or revoked".to_string(),
+                    ));
+                };
+                if api_key.scope == ApiKeyScope::Read && !read {
+                    return Err(Refusal::ReadOnly);
+                }
+                Ok(Some(Principal::from(api_key)))
+            }
+            (_, None) => Ok(None),
+            (Some(Ok(Credentials::Token(token))), Some(verifier)) => verifier
+                .verify(token)
+                .map(Some)
+                .map_err(|e| Refusal::InvalidToken(e.to_string())),
+            (Some(Err(())), Some(_)) => Err(Refusal::InvalidRequest),
+            (None, Some(_)) if !(self.public_reads && read) => Err(Refusal::Missing),
+            (None, Some(_)) => Ok(None),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+            authentication: self.clone(),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+    authentication: Authentication,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let authentication = self.authentication.clone();
+
+        // refusals are rendered in here, inside the request id scope
+        Box::pin(async move {
+            match authentication.authenticate(&req).await {
+                Ok(principal) => {
+                    if let Some(principal) = principal {
+                        req.extensions_mut().insert(principal);
+                    }
+                    Ok(service.call(req).await?.map_into_left_body())
+                }
+                Err(refusal) => Ok(req
+                    .into_response(refusal.error_response())
+                    .map_into_right_body()),
+            }
+        })
+    }
+}
+
+/// Credentials presented by a request.
+#[derive(Debug, PartialEq, Eq)]
+enum Credentials<'a> {
+    Token(&'a str),
+    ApiKey(&'a str),
+}
+
+/// Why a request was refused; the 401s as RFC 6750 distinguishes them.
+enum Refusal {
+    Missing,
+    InvalidRequest,
+    InvalidToken(String),
+    ReadOnly,
+    Store(ApiKeyError),
+}
+
+impl Refusal {
+    fn error_response(&self) -> HttpResponse {
+        let (problem, challenge) = match self {
+            Refusal::Missing => (
+                unauthorized("This request needs a bearer token or API key"),
+                "Bearer",
+            ),
+            Refusal::InvalidRequest => (
+                unauthorized("The Authorization header must hold a bearer token or API key"),
+                r#"Bearer error="invalid_request""#,
+            ),
+            Refusal::InvalidToken(reason) => (
+                unauthorized(reason.clone()),
+                r#"Bearer error="invalid_token""#,
+            ),
+            Refusal::ReadOnly => (
+                forbidden("This API key is read-only"),
+                r#"Bearer error="insufficient_scope""#,
+            ),
+            Refusal::Store(e) => return e.error_response(),
+        };
+
+        let mut res = problem.error_response();
+        res.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_static(challenge),
+        );
+        res
+    }
+}
+
+fn unauthorized(detail: impl Into<String>) -> Problem {
+    Problem::new(
+        StatusCode::UNAUTHORIZED,
+        "/problems/unauthorized",
+        "Unauthorized",
+    )
+    .with_detail(detail)
+}
+
+fn forbidden(detail: impl Into<String>) -> Problem {
+    Problem::new(StatusCode::FORBIDDEN, "/problems/forbidden", "Forbidden").with_detail(detail)
+}
+
+fn is_read(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// The credentials of a request: the key of an [`API_KEY_HEADER`], else an
+/// `Authorization` header's bearer token, or API key when given as
+/// `ApiKey <key>` or as a bearer token starting with [`API_KEY_PREFIX`].
+/// `Err` for any other scheme.
+fn credentials(headers: &HeaderMap) -> Option<Result<Credentials<'_>, ()>> {
+    if let Some(value) = headers.get(API_KEY_HEADER) {
+        let key = value
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|key| !key.is_empty());
+        return Some(key.map(Credentials::ApiKey).ok_or(()));
+    }
+
+    let value = headers.get(header::AUTHORIZATION)?;
+    let credentials = value
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once(' '))
+        .map(|(scheme, value)| (scheme, value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .and_then(
+            |(scheme, value)| match scheme.to_ascii_lowercase().as_str() {
+                "apikey" => Some(Credentials::ApiKey(value)),
+                "bearer" if value.starts_with(API_KEY_PREFIX) => Some(Credentials::ApiKey(value)),
+                "bearer" => Some(Credentials::Token(value)),
+                _ => None,
+            },
+        );
+    Some(credentials.ok_or(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Secret;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    fn settings() -> AuthSettings {
+        AuthSettings {
+            enabled: true,
+            hs256_secret: Some(Secret::new("secret")),
+            issuer: Some("films".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn token(claims: serde_json::Value, secret: &str) -> String {
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn in_an_hour() -> u64 {
+        jsonwebtoken::get_current_timestamp() + 3600
+    }
+
+    #[test]
+    fn valid_tokens_name_their_principal() {
+        let verifier = TokenVerifier::from_settings(&settings()).unwrap();
+        let claims = serde_json::json!({
+            "sub": "ann", "iss": "films", "exp": in_an_hour(), "scope": "films:write  admin",
+        });
+
+        let principal = verifier.verify(&token(claims, "secret")).unwrap();
+        assert_eq!(principal.subject, "ann");
+        assert_eq!(principal.scopes, ["films:write", "admin"]);
+    }
+
+    #[test]
+    fn forged_expired_and_foreign_tokens_are_refused() {
+        let verifier = TokenVerifier::from_settings(&settings()).unwrap();
+        let claims =
+            |iss: &str, exp: u64| serde_json::json!({"sub": "ann", "iss": iss, "exp": exp});
+
+        let forged = token(claims("films", in_an_hour()), "guess");
+        assert!(matches!(
+            verifier.verify(&forged),
+            Err(TokenError::Invalid(_))
+        ));
+        let expired = token(claims("films", 1_000_000), "secret");
+        assert!(matches!(
+            verifier.verify(&expired),
+            Err(TokenError::Expired)
+        ));
+        let foreign = token(claims("elsewhere", in_an_hour()), "secret");
+        assert!(matches!(
+            verifier.verify(&foreign),
+            Err(TokenError::Invalid(_))
+        ));
+        let no_issuer = token(
+            serde_json::json!({"sub": "ann", "exp": in_an_hour()}),
+            "secret",
+        );
+        assert!(matches!(
+            verifier.verify(&no_issuer),
+            Err(TokenError::Invalid(_))
+        ));
+        assert!(verifier.verify("not.a.token").is_err());
+    }
+
+    #[test]
+    fn missing_key_files_are_reported() {
+        let settings = AuthSettings {
+            jwks_file: Some(PathBuf::from("/nonexistent/jwks.json")),
+            ..settings()
+        };
+        assert!(matches!(
+            TokenVerifier::from_settings(&settings),
+            Err(KeysError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn credentials_come_from_either_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(credentials(&headers), None);
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("bearer abc"),
+        );
+        assert_eq!(credentials(&headers), Some(Ok(Credentials::Token("abc"))));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer fk_abc"),
+        );
+        assert_eq!(
+            credentials(&headers),
+            Some(Ok(Credentials::ApiKey("fk_abc")))
+        );
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("ApiKey fk_abc"),
+        );
+        assert_eq!(
+            credentials(&headers),
+            Some(Ok(Credentials::ApiKey("fk_abc")))
+        );
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(credentials(&headers), Some(Err(())));
+
+        headers.insert(
+            header::HeaderName::from_static(API_KEY_HEADER),
+            HeaderValue::from_static("fk_def"),
+        );
+        assert_eq!(
+            credentials(&headers),
+            Some(Ok(Credentials::ApiKey("fk_def")))
+        );
+    }
+}
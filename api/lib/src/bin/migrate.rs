@@ -0,0 +1,80 @@
+//! Applies, reverts and lists the embedded database migrations.
+//!
+//! ```text
+//! DATABASE_URL=postgres://... cargo run -p api-lib --bin migrate -- [up | down [VERSION] | status]
+//! ```
+//!
+//! A `sqlite:` URL applies the SQLite migrations instead.
+
+use api_lib::migrations::{self, MigrationError, Migrations};
+use sqlx::migrate::Migrate;
+use sqlx::Pool;
+
+const USAGE: &str = "usage: migrate [up | down [VERSION] | status]";
+
+#[actix_web::main]
+async fn main() {
+    if let Err(message) = run(std::env::args().skip(1).collect()).await {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Vec<String>) -> Result<(), String> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let url = std::env::var("DATABASE_URL").map_err(|_| "DATABASE_URL is not set".to_string())?;
+    if url.starts_with("sqlite:") {
+        let pool = sqlx::SqlitePool::connect(&url).await.map_err(unreachable)?;
+        command(&pool, &args).await
+    } else {
+        let pool = sqlx::PgPool::connect(&url).await.map_err(unreachable)?;
+        command(&pool, &args).await
+    }
+}
+
+async fn command<DB>(pool: &Pool<DB>, args: &[&str]) -> Result<(), String>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    match args {
+        [] | ["up"] => {
+            migrations::run(pool).await.map_err(describe)?;
+            println!(
+                "database is at migration {}",
+                migrations::latest_version::<DB>()
+            );
+        }
+        ["down"] => migrations::undo(pool, None).await.map_err(describe)?,
+        ["down", version] => {
+            let version = version
+                .parse()
+                .map_err(|_| format!("invalid version {}\n{}", version, USAGE))?;
+            migrations::undo(pool, Some(version))
+                .await
+                .map_err(describe)?;
+        }
+        ["status"] => {
+            for status in migrations::status(pool).await.map_err(describe)? {
+                let state = match (status.applied, status.modified) {
+                    (true, true) => "modified",
+                    (true, false) => "applied",
+                    (false, _) => "pending",
+                };
+                let description = status.description.as_deref().unwrap_or("(unknown)");
+                println!("{:>14}  {:<8}  {}", status.version, state, description);
+            }
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+
+    Ok(())
+}
+
+fn unreachable(e: sqlx::Error) -> String {
+    format!("can't connect to the database: {}", e)
+}
+
+fn describe(e: MigrationError) -> String {
+    format!("migration failed: {}", e)
+}
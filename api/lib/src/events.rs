@@ -0,0 +1,123 @@
+//! `GET /v1/films/events`: film changes as server-sent events.
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use actix_web::http::header::{CacheControl, CacheDirective, HeaderName};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::stream;
+use shared::models::{EventId, FilmChangeKind, FilmEvent};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::film_repository::{FilmEvents, LiveFilmEvent};
+use crate::problem::Problem;
+
+/// Header `EventSource` resumes a stream with.
+pub const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+/// A comment is sent on a stream idle this long, so proxies keep it open.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Milliseconds `EventSource` waits before reconnecting a dropped stream.
+const RETRY_MS: u64 = 3000;
+
+/// Streams film changes as `created`, `updated` and `deleted` events, each
+/// carrying a [`FilmEvent`] as its data and its id as the event id.
+///
+/// `Last-Event-ID` resumes after that event from the replay buffer. When the
+/// events since are no longer buffered, the client fell too far behind, or
+/// the server missed changes made elsewhere, a `resync` event asks it to
+/// reload the films it shows; the stream then carries on. Answers 404
+/// unless the repository publishes its writes.
+pub async fn film_events(
+    req: HttpRequest,
+    events: Option<web::Data<FilmEvents>>,
+) -> Result<HttpResponse, Problem> {
+    let Some(events) = events else {
+        return Err(not_published());
+    };
+
+    // an id that can't be ours is treated like one from another epoch
+    let (last_event_id, unknown) = match req.headers().get(LAST_EVENT_ID) {
+        None => (None, false),
+        Some(value) => match value.to_str().ok().and_then(|id| id.trim().parse().ok()) {
+            Some(id) => (Some(id), false),
+            None => (None, true),
+        },
+    };
+    let subscription = events.subscribe(last_event_id);
+
+    let mut pending = VecDeque::new();
+    pending.push_back(Bytes::from(format!("retry: {}\n\n", RETRY_MS)));
+    if subscription.missed || unknown {
+        // resuming after the resync starts right before what follows it
+        let resume = subscription
+            .replay
+            .first()
+            .map_or(subscription.last_id, |event| EventId {
+                seq: event.id.seq - 1,
+                ..event.id
+            });
+        pending.push_back(resync(Some(resume)));
+    }
+    pending.extend(subscription.replay.iter().map(frame));
+
+    let body = stream::unfold(
+        (pending, subscription.receiver),
+        |(mut pending, mut receiver)| async move {
+            let chunk = match pending.pop_front() {
+                Some(chunk) => chunk,
+                None => match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+                    Ok(Ok(LiveFilmEvent::Event(event))) => frame(&event),
+                    Ok(Ok(LiveFilmEvent::Resync(id))) => resync(Some(id)),
+                    Ok(Err(RecvError::Lagged(_))) => resync(None),
+                    Ok(Err(RecvError::Closed)) => return None,
+                    Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+                },
+            };
+            Some((Ok::<_, Infallible>(chunk), (pending, receiver)))
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // keeps nginx from buffering the stream
+        .insert_header(("x-accel-buffering", "no"))
+        .streaming(body))
+}
+
+/// Answer of the event routes when the repository doesn't publish its writes.
+pub(crate) fn not_published() -> Problem {
+    Problem::new(
+        StatusCode::NOT_FOUND,
+        "/problems/not-found",
+        "Resource not found",
+    )
+    .with_detail("film events are not published")
+}
+
+fn frame(event: &FilmEvent) -> Bytes {
+    let name = match event.kind {
+        FilmChangeKind::Created => "created",
+        FilmChangeKind::Updated => "updated",
+        FilmChangeKind::Deleted => "deleted",
+    };
+    // serde_json never writes newlines, so the data fits on one line
+    let data = serde_json::to_string(event).expect("film events serialize");
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id, name, data
+    ))
+}
+
+/// Without an id the client keeps resuming after the last event it got.
+fn resync(id: Option<EventId>) -> Bytes {
+    match id {
+        Some(id) => Bytes::from(format!("id: {}\nevent: resync\ndata: {{}}\n\n", id)),
+        None => Bytes::from_static(b"event: resync\ndata: {}\n\n"),
+    }
+}
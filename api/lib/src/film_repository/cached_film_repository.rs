@@ -0,0 +1,449 @@
+use super::{
+    CollectionStamp, FilmNotification, FilmRepository, FilmResult, FilmUpdate, ListFilms,
+    SearchFilms, SuggestFilms,
+};
+use lru::LruCache;
+use shared::models::{CreateFilm, Film, FilmPatch, FilmSearchHit, FilmSuggestion};
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+/// Sizes and lifetime of the [`CachedFilmRepository`] caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Films kept by id; `0` disables the film cache.
+    pub films: usize,
+    /// Listing pages kept by query, plus the collection stamp; `0`
+    /// disables both.
+    pub lists: usize,
+    /// How long an entry is served before the wrapped repository is asked
+    /// again, bounding staleness from writes that bypass this wrapper.
+    pub ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            films: 1000,
+            lists: 100,
+            ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Hit and miss counts since the cache was created.
+///
+/// List counts include collection stamp lookups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub film_hits: u64,
+    pub film_misses: u64,
+    pub list_hits: u64,
+    pub list_misses: u64,
+}
+
+/// Any [`FilmRepository`] with bounded LRU caches of single films and
+/// listing pages in front of it.
+///
+/// Writes made through the wrapper invalidate what they affect before
+/// returning, so its own callers never read their writes stale. Writes made
+/// elsewhere, e.g. by another instance, show up once entries expire, or
+/// as soon as a change feed passed to [`CachedFilmRepository::follow`]
+/// reports them.
+pub struct CachedFilmRepository<R> {
+    inner: R,
+    ttl: Duration,
+    films: Option<Mutex<LruCache<Uuid, Entry<Film>>>>,
+    lists: Option<Mutex<LruCache<ListFilms, Entry<Vec<Film>>>>>,
+    stamp: Mutex<Option<Entry<CollectionStamp>>>,
+    /// Bumped by every invalidation; a read that started before one must
+    /// not cache what it fetched.
+    generation: AtomicU64,
+    film_hits: AtomicU64,
+    film_misses: AtomicU64,
+    list_hits: AtomicU64,
+    list_misses: AtomicU64,
+}
+
+struct Entry<T> {
+    value: T,
+    expires: Instant,
+}
+
+impl<R: FilmRepository> CachedFilmRepository<R> {
+    pub fn new(inner: R, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            ttl: config.ttl,
+            films: lru(config.films),
+            lists: lru(config.lists),
+            stamp: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            film_hits: AtomicU64::new(0),
+            film_misses: AtomicU64::new(0),
+            list_hits: AtomicU64::new(0),
+            list_misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            film_hits: self.film_hits.load(Ordering::Relaxed),
+            film_misses: self.film_misses.load(Ordering::Relaxed),
+            list_hits: self.list_hits.load(Ordering::Relaxed),
+            list_misses: self.list_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drops the cached copy of film `id` and every cached listing, which
+    /// may include it.
+    pub fn invalidate(&self, id: &Uuid) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        if let Some(films) = &self.films {
+            lock(films).pop(id);
+        }
+        self.invalidate_lists();
+    }
+
+    /// Drops every cached entry.
+    pub fn invalidate_all(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        if let Some(films) = &self.films {
+            lock(films).clear();
+        }
+        self.invalidate_lists();
+    }
+
+    /// Invalidates what `notifications` report changed until the feed
+    /// closes; everything when notifications were missed.
+    pub async fn follow(&self, mut notifications: broadcast::Receiver<FilmNotification>) {
+        loop {
+            match notifications.recv().await {
+                Ok(FilmNotification::Changed(change)) => self.invalidate(&change.id),
+                Ok(FilmNotification::Resync) | Err(RecvError::Lagged(_)) => self.invalidate_all(),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    fn invalidate_lists(&self) {
+        if let Some(lists) = &self.lists {
+            lock(lists).clear();
+        }
+        *lock(&self.stamp) = None;
+    }
+
+    fn entry<T>(&self, value: T) -> Entry<T> {
+        Entry {
+            value,
+            expires: Instant::now() + self.ttl,
+        }
+    }
+
+    /// Caches `value` unless an invalidation happened since `generation`.
+    fn put<K: Hash + Eq, T>(
+        &self,
+        cache: &Mutex<LruCache<K, Entry<T>>>,
+        generation: u64,
+        key: K,
+        value: T,
+    ) {
+        let mut cache = lock(cache);
+        if self.generation.load(Ordering::Acquire) == generation {
+            cache.put(key, self.entry(value));
+        }
+    }
+}
+
+fn lru<K: Hash + Eq, V>(capacity: usize) -> Option<Mutex<LruCache<K, V>>> {
+    NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity)))
+}
+
+/// A poisoned cache is still a valid cache: entries are only ever replaced
+/// whole.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn cached<K: Hash + Eq, T: Clone>(cache: &Mutex<LruCache<K, Entry<T>>>, key: &K) -> Option<T> {
+    let mut cache = lock(cache);
+    match cache.get(key) {
+        Some(entry) if entry.expires > Instant::now() => Some(entry.value.clone()),
+        Some(_) => {
+            cache.pop(key);
+            None
+        }
+        None => None,
+    }
+}
+
+fn count(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+#[async_trait::async_trait]
+impl<R: FilmRepository> FilmRepository for CachedFilmRepository<R> {
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        let Some(lists) = &self.lists else {
+            return self.inner.get_films(query).await;
+        };
+        if let Some(films) = cached(lists, query) {
+            count(&self.list_hits);
+            return Ok(films);
+        }
+
+        count(&self.list_misses);
+        let generation = self.generation.load(Ordering::Acquire);
+        let films = self.inner.get_films(query).await?;
+        self.put(lists, generation, query.clone(), films.clone());
+        Ok(films)
+    }
+
+    async fn get_film(&self, id: &Uuid) -> FilmResult<Film> {
+        let Some(films) = &self.films else {
+            return self.inner.get_film(id).await;
+        };
+        if let Some(film) = cached(films, id) {
+            count(&self.film_hits);
+            return Ok(film);
+        }
+
+        count(&self.film_misses);
+        let generation = self.generation.load(Ordering::Acquire);
+        let film = self.inner.get_film(id).await?;
+        self.put(films, generation, *id, film.clone());
+        Ok(film)
+    }
+
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        if self.lists.is_none() {
+            return self.inner.collection_stamp().await;
+        }
+        if let Some(entry) = &*lock(&self.stamp) {
+            if entry.expires > Instant::now() {
+                count(&self.list_hits);
+                return Ok(entry.value);
+            }
+        }
+
+        count(&self.list_misses);
+        let generation = self.generation.load(Ordering::Acquire);
+        let stamp = self.inner.collection_stamp().await?;
+        let mut cached = lock(&self.stamp);
+        if self.generation.load(Ordering::Acquire) == generation {
+            *cached = Some(self.entry(stamp));
+        }
+        Ok(stamp)
+    }
+
+    async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
+        let film = self.inner.create_film(create_film).await?;
+        self.invalidate(&film.id);
+        Ok(film)
+    }
+
+    // a failed write may mean the cached copy is stale, so invalidate either way
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        let result = self.inner.update_film(film, if_version).await;
+        self.invalidate(&film.id);
+        result
+    }
+
+    async fn patch_film(
+        &self,
+        id: &Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        let result = self.inner.patch_film(id, patch, if_version).await;
+        self.invalidate(id);
+        result
+    }
+
+    async fn delete_film(&self, id: &Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        let result = self.inner.delete_film(id, if_version).await;
+        self.invalidate(id);
+        result
+    }
+
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        self.inner.search_films(query).await
+    }
+
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        self.inner.suggest_films(query).await
+    }
+
+    fn events(&self) -> Option<std::sync::Arc<super::FilmEvents>> {
+        self.inner.events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film_repository::{FilmError, MemoryFilmRepository};
+
+    fn cached_memory(config: CacheConfig) -> CachedFilmRepository<MemoryFilmRepository> {
+        CachedFilmRepository::new(MemoryFilmRepository::new(), config)
+    }
+
+    fn create_film(title: &str) -> CreateFilm {
+        CreateFilm {
+            title: title.to_string(),
+            director: "Director".to_string(),
+            year: 2001,
+            poster: String::new(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn repeated_reads_are_served_from_the_cache() {
+        let repo = cached_memory(CacheConfig::default());
+        let film = repo.create_film(&create_film("Heat")).await.unwrap();
+
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), film);
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), film);
+        repo.get_films(&ListFilms::default()).await.unwrap();
+        repo.get_films(&ListFilms::default()).await.unwrap();
+
+        assert_eq!(
+            repo.stats(),
+            CacheStats {
+                film_hits: 1,
+                film_misses: 1,
+                list_hits: 1,
+                list_misses: 1,
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn writes_through_the_wrapper_invalidate() {
+        let repo = cached_memory(CacheConfig::default());
+        let film = repo.create_film(&create_film("Heat")).await.unwrap();
+        repo.get_film(&film.id).await.unwrap();
+        repo.get_films(&ListFilms::default()).await.unwrap();
+        let stamp = repo.collection_stamp().await.unwrap();
+
+        let mut renamed = film.clone();
+        renamed.title = "Thief".to_string();
+        let renamed = repo.update_film(&renamed, None).await.unwrap().film;
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), renamed);
+        assert_eq!(
+            repo.get_films(&ListFilms::default()).await.unwrap(),
+            vec![renamed]
+        );
+        assert_ne!(repo.collection_stamp().await.unwrap(), stamp);
+
+        repo.delete_film(&film.id, None).await.unwrap();
+        assert!(matches!(
+            repo.get_film(&film.id).await,
+            Err(FilmError::NotFound(_))
+        ));
+        assert!(repo
+            .get_films(&ListFilms::default())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn writes_elsewhere_show_up_after_the_ttl_or_an_invalidation() {
+        let repo = cached_memory(CacheConfig {
+            ttl: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let film = repo.create_film(&create_film("Heat")).await.unwrap();
+        repo.get_film(&film.id).await.unwrap();
+
+        let mut renamed = film.clone();
+        renamed.title = "Thief".to_string();
+        let renamed = repo.inner().update_film(&renamed, None).await.unwrap().film;
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), film);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), renamed);
+
+        let mut restored = renamed.clone();
+        restored.title = "Heat".to_string();
+        let restored = repo
+            .inner()
+            .update_film(&restored, None)
+            .await
+            .unwrap()
+            .film;
+        repo.invalidate(&film.id);
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), restored);
+    }
+
+    #[actix_rt::test]
+    async fn least_recently_used_films_are_evicted() {
+        let repo = cached_memory(CacheConfig {
+            films: 2,
+            ..Default::default()
+        });
+        let mut ids = Vec::new();
+        for title in ["A", "B", "C"] {
+            let film = repo.create_film(&create_film(title)).await.unwrap();
+            repo.get_film(&film.id).await.unwrap();
+            ids.push(film.id);
+        }
+
+        repo.get_film(&ids[2]).await.unwrap();
+        repo.get_film(&ids[0]).await.unwrap();
+        assert_eq!(repo.stats().film_hits, 1);
+        assert_eq!(repo.stats().film_misses, 4);
+    }
+
+    #[actix_rt::test]
+    async fn zero_capacity_disables_caching() {
+        let repo = cached_memory(CacheConfig {
+            films: 0,
+            lists: 0,
+            ..Default::default()
+        });
+        let film = repo.create_film(&create_film("Heat")).await.unwrap();
+        repo.get_film(&film.id).await.unwrap();
+        repo.get_films(&ListFilms::default()).await.unwrap();
+        repo.collection_stamp().await.unwrap();
+
+        assert_eq!(repo.stats(), CacheStats::default());
+    }
+
+    #[actix_rt::test]
+    async fn followed_changes_invalidate() {
+        let repo = cached_memory(CacheConfig::default());
+        let film = repo.create_film(&create_film("Heat")).await.unwrap();
+        repo.get_film(&film.id).await.unwrap();
+
+        let mut renamed = film.clone();
+        renamed.title = "Thief".to_string();
+        let renamed = repo.inner().update_film(&renamed, None).await.unwrap().film;
+
+        let (sender, receiver) = broadcast::channel(4);
+        sender
+            .send(FilmNotification::Changed(Box::new(
+                crate::film_repository::FilmChange {
+                    kind: crate::film_repository::FilmChangeKind::Updated,
+                    id: film.id,
+                    version: renamed.version,
+                    film: None,
+                    previous: None,
+                },
+            )))
+            .unwrap();
+        drop(sender);
+        repo.follow(receiver).await;
+
+        assert_eq!(repo.get_film(&film.id).await.unwrap(), renamed);
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use shared::models::Film;
+pub use shared::models::FilmChangeKind;
+use uuid::Uuid;
+
+/// A film written by any API instance or client of the store.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilmChange {
+    pub kind: FilmChangeKind,
+    pub id: Uuid,
+    /// Version the film has after the change, or had before its deletion.
+    pub version: i32,
+    /// The film after the change, or as it was when deleted; left out when
+    /// it doesn't fit the feed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub film: Option<Film>,
+    /// The film an update replaced, when it fits the feed too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Film>,
+}
+
+/// What a change feed such as [`super::PostgresFilmListener`] reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilmNotification {
+    Changed(Box<FilmChange>),
+    /// Changes may have been missed, e.g. while the feed reconnected;
+    /// consumers should assume any film changed.
+    Resync,
+}
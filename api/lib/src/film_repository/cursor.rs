@@ -0,0 +1,203 @@
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use shared::models::{Film, FilmSort, SortOrder};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Value of the sort column a page ended on.
+///
+/// Keys of the same variant order like [`super::ListFilms::compare`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(tag = "sort", content = "key", rename_all = "snake_case")]
+pub enum SortKey {
+    CreatedAt(Option<DateTime<Utc>>),
+    Title(String),
+    Year(u16),
+    Director(String),
+}
+
+impl SortKey {
+    pub fn of(sort: FilmSort, film: &Film) -> Self {
+        match sort {
+            FilmSort::CreatedAt => SortKey::CreatedAt(film.created_at),
+            FilmSort::Title => SortKey::Title(film.title.clone()),
+            FilmSort::Year => SortKey::Year(film.year),
+            FilmSort::Director => SortKey::Director(film.director.clone()),
+        }
+    }
+
+    pub fn sort(&self) -> FilmSort {
+        match self {
+            SortKey::CreatedAt(_) => FilmSort::CreatedAt,
+            SortKey::Title(_) => FilmSort::Title,
+            SortKey::Year(_) => FilmSort::Year,
+            SortKey::Director(_) => FilmSort::Director,
+        }
+    }
+
+    /// Compares the sort column of `film` against this key, ascending.
+    pub fn cmp_film(&self, film: &Film) -> Ordering {
+        match self {
+            SortKey::CreatedAt(key) => film.created_at.cmp(key),
+            SortKey::Title(key) => film.title.as_str().cmp(key),
+            SortKey::Year(key) => film.year.cmp(key),
+            SortKey::Director(key) => film.director.as_str().cmp(key),
+        }
+    }
+}
+
+/// Position of the last film of a page; the next page starts right after it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FilmCursor {
+    #[serde(flatten)]
+    pub key: SortKey,
+    pub order: SortOrder,
+    pub id: Uuid,
+}
+
+impl FilmCursor {
+    pub fn after(film: &Film, sort: FilmSort, order: SortOrder) -> Self {
+        Self {
+            key: SortKey::of(sort, film),
+            order,
+            id: film.id,
+        }
+    }
+
+    /// Whether `film` comes strictly after this cursor in the cursor's order.
+    pub fn precedes(&self, film: &Film) -> bool {
+        let ordering = self.key.cmp_film(film).then_with(|| film.id.cmp(&self.id));
+        match self.order {
+            SortOrder::Asc => ordering == Ordering::Greater,
+            SortOrder::Desc => ordering == Ordering::Less,
+        }
+    }
+}
+
+/// Encodes cursors as opaque `payload.signature` tokens.
+///
+/// The payload is URL-safe base64 JSON and the signature an HMAC-SHA256
+/// over it, so clients can't forge positions or change the sort of a walk.
+pub struct CursorCodec {
+    key: Vec<u8>,
+}
+
+impl CursorCodec {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// Process wide codec used when none is registered as app data, as
+    /// when `api.cursor_secret` is unset. Its key is random, so its tokens
+    /// don't survive restarts or cross instances.
+    pub fn global() -> &'static CursorCodec {
+        static CODEC: OnceLock<CursorCodec> = OnceLock::new();
+        CODEC.get_or_init(|| {
+            tracing::warn!("api.cursor_secret is not set, using a random key to sign cursors");
+            let key = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+            CursorCodec::new(key)
+        })
+    }
+
+    pub fn encode(&self, cursor: &FilmCursor) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default());
+        let signature =
+            URL_SAFE_NO_PAD.encode(self.mac(payload.as_bytes()).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Decodes `token`, returning `None` when it is malformed or its
+    /// signature doesn't match.
+    pub fn decode(&self, token: &str) -> Option<FilmCursor> {
+        let (payload, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(payload.as_bytes()).verify_slice(&signature).ok()?;
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        serde_json::from_slice(&payload).ok()
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn film(title: &str) -> Film {
+        Film {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            created_at: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips_through_token() {
+        let codec = CursorCodec::new("secret");
+        for sort in [
+            FilmSort::CreatedAt,
+            FilmSort::Title,
+            FilmSort::Year,
+            FilmSort::Director,
+        ] {
+            let cursor = FilmCursor::after(&film("Heat"), sort, SortOrder::Desc);
+            let token = codec.encode(&cursor);
+            assert_eq!(codec.decode(&token).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let codec = CursorCodec::new("secret");
+        let token = codec.encode(&FilmCursor::after(
+            &film("Heat"),
+            FilmSort::Title,
+            SortOrder::Asc,
+        ));
+
+        let forged = codec.encode(&FilmCursor::after(
+            &film("Alien"),
+            FilmSort::Title,
+            SortOrder::Asc,
+        ));
+        let (forged_payload, _) = forged.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        let tampered = format!("{}.{}", forged_payload, signature);
+
+        assert!(codec.decode(&tampered).is_none());
+        assert!(CursorCodec::new("other").decode(&token).is_none());
+        assert!(codec.decode("garbage").is_none());
+    }
+
+    #[test]
+    fn precedes_follows_sort_direction_and_breaks_ties_by_id() {
+        let a = film("A");
+        let b = film("B");
+        let cursor = FilmCursor::after(&a, FilmSort::Title, SortOrder::Asc);
+        assert!(cursor.precedes(&b));
+        assert!(!cursor.precedes(&a));
+
+        let cursor = FilmCursor::after(&b, FilmSort::Title, SortOrder::Desc);
+        assert!(cursor.precedes(&a));
+        assert!(!cursor.precedes(&b));
+
+        let mut twin = a.clone();
+        twin.id = Uuid::new_v4();
+        let cursor = FilmCursor::after(&a, FilmSort::Title, SortOrder::Asc);
+        assert_eq!(cursor.precedes(&twin), twin.id > a.id);
+    }
+}
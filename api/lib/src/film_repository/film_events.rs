@@ -0,0 +1,391 @@
+use super::{FilmChange, FilmNotification};
+use shared::models::{EventId, Film, FilmChangeKind, FilmEvent};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+/// Events buffered per live subscriber before it lags.
+const CAPACITY: usize = 1024;
+
+/// Changes remembered so that each is published once, however it arrives.
+const SEEN: usize = 1024;
+
+/// Numbers the film changes published to it and keeps the latest ones, so
+/// clients of `GET /v1/films/events` can resume where they left off.
+///
+/// Ids carry an epoch picked at random when the events are created, and
+/// count from 1 within it; a client presenting an id of another epoch, such
+/// as one handed out by another instance or before a restart, or one never
+/// handed out, is told to resync.
+///
+/// A change is identified by its film, version and kind, and only published
+/// the first time: a write made through this instance is published by it,
+/// and comes back from the store's change feed, in either order.
+pub struct FilmEvents {
+    epoch: u64,
+    log: Mutex<Log>,
+    sender: broadcast::Sender<LiveFilmEvent>,
+    replay: usize,
+}
+
+type ChangeKey = (Uuid, i32, FilmChangeKind);
+
+struct Log {
+    last_id: u64,
+    /// The last `replay` events, oldest first, with consecutive ids.
+    recent: VecDeque<FilmEvent>,
+    /// The last `SEEN` changes published, oldest first, and the same as a set.
+    seen: VecDeque<ChangeKey>,
+    seen_keys: HashSet<ChangeKey>,
+}
+
+/// What live subscribers are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveFilmEvent {
+    Event(Box<FilmEvent>),
+    /// Changes up to this id were missed, e.g. while the store's change feed
+    /// reconnected; the subscriber should reload whatever it shows.
+    Resync(EventId),
+}
+
+/// Where a new subscriber starts.
+pub struct FilmEventSubscription {
+    /// Buffered events after the id the subscriber asked for, oldest first.
+    pub replay: Vec<FilmEvent>,
+    /// Some events after that id are no longer buffered, or the id was never
+    /// handed out; the subscriber should reload whatever it shows.
+    pub missed: bool,
+    /// Id of the latest event when subscribing.
+    pub last_id: EventId,
+    /// Everything published after `replay`, without gaps or repeats.
+    pub receiver: broadcast::Receiver<LiveFilmEvent>,
+}
+
+impl FilmEvents {
+    /// Keeps the last `replay` events for resuming subscribers, none at `0`.
+    pub fn new(replay: usize) -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self {
+            epoch: Uuid::new_v4().as_u64_pair().0,
+            log: Mutex::new(Log {
+                last_id: 0,
+                recent: VecDeque::with_capacity(replay),
+                seen: VecDeque::with_capacity(SEEN),
+                seen_keys: HashSet::with_capacity(SEEN),
+            }),
+            sender,
+            replay,
+        }
+    }
+
+    /// Assigns the next id to a change and sends it to every subscriber;
+    /// `None` when the change was published already.
+    pub fn publish(
+        &self,
+        kind: FilmChangeKind,
+        film: Film,
+        previous: Option<Film>,
+    ) -> Option<FilmEvent> {
+        let mut log = lock(&self.log);
+        let key = (film.id, film.version, kind);
+        if !log.seen_keys.insert(key) {
+            return None;
+        }
+        if log.seen.len() == SEEN {
+            let oldest = log.seen.pop_front().expect("SEEN is not zero");
+            log.seen_keys.remove(&oldest);
+        }
+        log.seen.push_back(key);
+
+        log.last_id += 1;
+        let event = FilmEvent {
+            id: self.id(log.last_id),
+            kind,
+            film,
+            previous,
+        };
+
+        if self.replay > 0 {
+            if log.recent.len() == self.replay {
+                log.recent.pop_front();
+            }
+            log.recent.push_back(event.clone());
+        }
+        // no subscribers is fine, resuming ones are served from `recent`
+        let _ = self
+            .sender
+            .send(LiveFilmEvent::Event(Box::new(event.clone())));
+        Some(event)
+    }
+
+    /// Tells every subscriber that changes were missed, under an id of its
+    /// own; resuming from before it resyncs too.
+    pub fn resync(&self) -> EventId {
+        let mut log = lock(&self.log);
+        log.last_id += 1;
+        log.recent.clear();
+        let id = self.id(log.last_id);
+        let _ = self.sender.send(LiveFilmEvent::Resync(id));
+        id
+    }
+
+    /// Publishes the changes `notifications` report until the feed closes,
+    /// such as those other instances made to a shared Postgres database.
+    /// Missed notifications, and changes reported without their film, are
+    /// passed on as a [`resync`](Self::resync).
+    pub async fn follow(&self, mut notifications: broadcast::Receiver<FilmNotification>) {
+        loop {
+            match notifications.recv().await {
+                Ok(FilmNotification::Changed(change)) => match *change {
+                    FilmChange {
+                        kind,
+                        film: Some(film),
+                        previous,
+                        ..
+                    } => {
+                        self.publish(kind, film, previous);
+                    }
+                    FilmChange { film: None, .. } => {
+                        self.resync();
+                    }
+                },
+                Ok(FilmNotification::Resync) | Err(RecvError::Lagged(_)) => {
+                    self.resync();
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Id of the latest event, numbered `0` before the first one.
+    pub fn last_id(&self) -> EventId {
+        self.id(lock(&self.log).last_id)
+    }
+
+    fn id(&self, seq: u64) -> EventId {
+        EventId {
+            epoch: self.epoch,
+            seq,
+        }
+    }
+
+    /// Subscribes to events after `last_event_id`, or from now on without one.
+    pub fn subscribe(&self, last_event_id: Option<EventId>) -> FilmEventSubscription {
+        // subscribing under the lock keeps publishers from slipping an event
+        // in between the replay and the receiver
+        let log = lock(&self.log);
+        let receiver = self.sender.subscribe();
+
+        let (replay, missed) = match last_event_id {
+            None => (Vec::new(), false),
+            Some(last) if last.epoch != self.epoch || last.seq > log.last_id => (Vec::new(), true),
+            Some(EventId { seq: last, .. }) => {
+                let oldest = log
+                    .recent
+                    .front()
+                    .map_or(log.last_id + 1, |event| event.id.seq);
+                let skip = (last + 1).saturating_sub(oldest) as usize;
+                let replay = log.recent.iter().skip(skip).cloned().collect();
+                (replay, last + 1 < oldest)
+            }
+        };
+
+        FilmEventSubscription {
+            replay,
+            missed,
+            last_id: self.id(log.last_id),
+            receiver,
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(events: &FilmEvents, title: &str) -> FilmEvent {
+        let film = Film {
+            id: uuid::Uuid::new_v4(),
+            title: title.to_string(),
+            ..Default::default()
+        };
+        events
+            .publish(FilmChangeKind::Created, film, None)
+            .expect("a new film")
+    }
+
+    fn ids(events: &[FilmEvent]) -> Vec<u64> {
+        events.iter().map(|event| event.id.seq).collect()
+    }
+
+    #[test]
+    fn ids_increase_with_every_event() {
+        let events = FilmEvents::new(10);
+        assert_eq!(events.last_id().seq, 0);
+
+        assert_eq!(publish(&events, "Heat").id, events.id(1));
+        assert_eq!(publish(&events, "Thief").id, events.id(2));
+        assert_eq!(events.last_id(), events.id(2));
+
+        // another instance, or this one after a restart
+        assert_ne!(FilmEvents::new(10).last_id().epoch, events.epoch);
+    }
+
+    #[test]
+    fn resuming_replays_what_came_after_the_last_event() {
+        let events = FilmEvents::new(10);
+        for title in ["Heat", "Thief", "Collateral"] {
+            publish(&events, title);
+        }
+
+        let subscription = events.subscribe(Some(events.id(1)));
+        assert_eq!(ids(&subscription.replay), [2, 3]);
+        assert!(!subscription.missed);
+
+        let subscription = events.subscribe(Some(events.id(3)));
+        assert!(subscription.replay.is_empty());
+        assert!(!subscription.missed);
+
+        let subscription = events.subscribe(None);
+        assert!(subscription.replay.is_empty());
+        assert!(!subscription.missed);
+    }
+
+    #[test]
+    fn evicted_and_unknown_ids_are_reported_missed() {
+        let events = FilmEvents::new(2);
+        for title in ["Heat", "Thief", "Collateral"] {
+            publish(&events, title);
+        }
+
+        // event 2 is still buffered, only event 1 was evicted
+        let subscription = events.subscribe(Some(events.id(1)));
+        assert_eq!(ids(&subscription.replay), [2, 3]);
+        assert!(!subscription.missed);
+
+        let subscription = events.subscribe(Some(events.id(0)));
+        assert_eq!(ids(&subscription.replay), [2, 3]);
+        assert!(subscription.missed);
+
+        // never handed out
+        let subscription = events.subscribe(Some(events.id(7)));
+        assert!(subscription.replay.is_empty());
+        assert!(subscription.missed);
+    }
+
+    #[test]
+    fn ids_of_another_epoch_are_missed() {
+        let events = FilmEvents::new(10);
+        let restarted = FilmEvents::new(10);
+        for title in ["Heat", "Thief", "Collateral"] {
+            publish(&events, title);
+            publish(&restarted, title);
+        }
+
+        // the same sequence number of another epoch is no place to resume
+        let subscription = restarted.subscribe(Some(events.id(1)));
+        assert!(subscription.replay.is_empty());
+        assert!(subscription.missed);
+        assert_eq!(subscription.last_id, restarted.id(3));
+    }
+
+    #[test]
+    fn live_events_follow_the_replay() {
+        let events = FilmEvents::new(0);
+        publish(&events, "Heat");
+
+        let mut subscription = events.subscribe(Some(events.id(0)));
+        assert!(subscription.replay.is_empty());
+        assert!(subscription.missed);
+
+        let thief = publish(&events, "Thief");
+        assert_eq!(
+            subscription.receiver.try_recv().unwrap(),
+            LiveFilmEvent::Event(Box::new(thief))
+        );
+    }
+
+    #[test]
+    fn changes_are_published_once() {
+        let events = FilmEvents::new(10);
+        let heat = publish(&events, "Heat");
+
+        // the store's notification of a write made here
+        let again = events.publish(FilmChangeKind::Created, heat.film.clone(), None);
+        assert_eq!(again, None);
+        assert_eq!(events.last_id(), heat.id);
+
+        let renamed = Film {
+            title: "Thief".to_string(),
+            version: heat.film.version + 1,
+            ..heat.film.clone()
+        };
+        let updated = events.publish(FilmChangeKind::Updated, renamed.clone(), Some(heat.film));
+        assert_eq!(updated.unwrap().id, events.id(2));
+        let deleted = events.publish(FilmChangeKind::Deleted, renamed, None);
+        assert_eq!(deleted.unwrap().id, events.id(3));
+    }
+
+    #[test]
+    fn resyncs_reach_live_and_resuming_subscribers() {
+        let events = FilmEvents::new(10);
+        publish(&events, "Heat");
+        let mut live = events.subscribe(None).receiver;
+
+        let resync = events.resync();
+        assert_eq!(resync, events.id(2));
+        assert_eq!(live.try_recv().unwrap(), LiveFilmEvent::Resync(resync));
+        publish(&events, "Thief");
+
+        let subscription = events.subscribe(Some(events.id(1)));
+        assert!(subscription.missed);
+        assert_eq!(ids(&subscription.replay), [3]);
+
+        let subscription = events.subscribe(Some(resync));
+        assert!(!subscription.missed);
+        assert_eq!(ids(&subscription.replay), [3]);
+    }
+
+    #[actix_rt::test]
+    async fn followed_changes_are_published() {
+        let events = FilmEvents::new(10);
+        let mut live = events.subscribe(None).receiver;
+        let film = Film {
+            id: uuid::Uuid::new_v4(),
+            title: "Heat".to_string(),
+            version: 1,
+            ..Default::default()
+        };
+        let change = |film: Option<Film>| {
+            FilmNotification::Changed(Box::new(FilmChange {
+                kind: FilmChangeKind::Created,
+                id: film.as_ref().map_or(uuid::Uuid::new_v4(), |film| film.id),
+                version: 1,
+                film,
+                previous: None,
+            }))
+        };
+
+        let (sender, receiver) = broadcast::channel(4);
+        sender.send(change(Some(film.clone()))).unwrap();
+        sender.send(change(Some(film.clone()))).unwrap();
+        sender.send(change(None)).unwrap();
+        drop(sender);
+        events.follow(receiver).await;
+
+        match live.try_recv().unwrap() {
+            LiveFilmEvent::Event(event) => assert_eq!(event.film, film),
+            resync => panic!("expected the film, got {:?}", resync),
+        }
+        assert_eq!(
+            live.try_recv().unwrap(),
+            LiveFilmEvent::Resync(events.id(2))
+        );
+        assert!(live.try_recv().is_err());
+    }
+}
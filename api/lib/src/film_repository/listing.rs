@@ -0,0 +1,307 @@
+use std::cmp::Ordering;
+
+use shared::filter::Filter;
+use shared::models::{film_matches, Film, FilmQuery, FilmSort, SortOrder};
+
+use shared::validation::ValidationErrors;
+
+use super::{CursorCodec, FilmCursor, FilmError};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Validated listing parameters handed to [`super::FilmRepository::get_films`].
+///
+/// [`ListFilms::matches`] and [`ListFilms::compare`] are the reference
+/// semantics every backend must reproduce: text is compared byte-wise,
+/// a missing `created_at` sorts before any timestamp and ties are broken
+/// by `id` in the same direction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ListFilms {
+    pub director: Option<String>,
+    pub title: Option<String>,
+    pub year_from: Option<u16>,
+    pub year_to: Option<u16>,
+    /// Parsed `filter` parameter; [`Filter::matches`] is its reference.
+    pub filter: Option<Filter>,
+    pub sort: FilmSort,
+    pub order: SortOrder,
+    pub limit: u32,
+    pub offset: u32,
+    /// Keyset position; only films strictly after it are listed.
+    pub after: Option<FilmCursor>,
+}
+
+impl Default for ListFilms {
+    fn default() -> Self {
+        Self {
+            director: None,
+            title: None,
+            year_from: None,
+            year_to: None,
+            filter: None,
+            sort: FilmSort::default(),
+            order: SortOrder::default(),
+            limit: DEFAULT_PAGE_SIZE,
+            offset: 0,
+            after: None,
+        }
+    }
+}
+
+impl ListFilms {
+    /// Validates `query`, decoding its `cursor` with `codec`.
+    ///
+    /// A cursor carries the sort it was issued for; `sort` and `order` may be
+    /// omitted when continuing a walk but must not contradict it.
+    pub fn from_query(query: &FilmQuery, codec: &CursorCodec) -> Result<Self, FilmError> {
+        let mut errors = ValidationErrors::new();
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            errors.add(
+                "limit",
+                "range",
+                format!("must be between 1 and {}", MAX_PAGE_SIZE),
+            );
+        }
+
+        if let (Some(from), Some(to)) = (query.year_from, query.year_to) {
+            if from > to {
+                errors.add("year_from", "range", "must not be greater than year_to");
+            }
+        }
+
+        let filter = query
+            .filter
+            .as_deref()
+            .filter(|filter| !filter.trim().is_empty())
+            .and_then(|filter| match Filter::parse(filter) {
+                Ok(filter) => Some(filter),
+                Err(error) => {
+                    errors.add("filter", "syntax", error.to_string());
+                    None
+                }
+            });
+
+        let after = query.cursor.as_deref().and_then(|token| {
+            let cursor = codec.decode(token);
+            if cursor.is_none() {
+                errors.add("cursor", "invalid", "is invalid or has been tampered with");
+            }
+            cursor
+        });
+
+        let (sort, order) = match &after {
+            Some(cursor) => {
+                if query.offset.is_some() {
+                    errors.add("offset", "conflict", "can't be combined with cursor");
+                }
+                let sort = query.sort.unwrap_or(cursor.key.sort());
+                let order = query.order.unwrap_or(cursor.order);
+                if sort != cursor.key.sort() || order != cursor.order {
+                    errors.add("cursor", "conflict", "was issued for a different sort");
+                }
+                (sort, order)
+            }
+            None => (
+                query.sort.unwrap_or_default(),
+                query.order.unwrap_or_default(),
+            ),
+        };
+
+        if !errors.is_empty() {
+            return Err(FilmError::Validation(errors));
+        }
+
+        Ok(Self {
+            director: query.director.clone().filter(|d| !d.is_empty()),
+            title: query.title.clone().filter(|t| !t.is_empty()),
+            year_from: query.year_from,
+            year_to: query.year_to,
+            filter,
+            sort,
+            order,
+            limit,
+            offset: query.offset.unwrap_or(0),
+            after,
+        })
+    }
+
+    /// Whether `film` passes the filters and lies after the cursor, if any.
+    pub fn matches(&self, film: &Film) -> bool {
+        if matches!(&self.after, Some(after) if !after.precedes(film)) {
+            return false;
+        }
+        if !film_matches(
+            film,
+            self.director.as_deref(),
+            self.title.as_deref(),
+            self.year_from,
+            self.year_to,
+        ) {
+            return false;
+        }
+
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(film))
+    }
+
+    pub fn compare(&self, a: &Film, b: &Film) -> Ordering {
+        let ordering = match self.sort {
+            FilmSort::CreatedAt => a.created_at.cmp(&b.created_at),
+            FilmSort::Title => a.title.cmp(&b.title),
+            FilmSort::Year => a.year.cmp(&b.year),
+            FilmSort::Director => a.director.cmp(&b.director),
+        }
+        .then_with(|| a.id.cmp(&b.id));
+
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn film(title: &str, director: &str, year: u16) -> Film {
+        Film {
+            id: uuid::Uuid::new_v4(),
+            title: title.to_string(),
+            director: director.to_string(),
+            year,
+            ..Default::default()
+        }
+    }
+
+    fn parse(query: &FilmQuery) -> Result<ListFilms, FilmError> {
+        ListFilms::from_query(query, &CursorCodec::new("secret"))
+    }
+
+    #[test]
+    fn empty_query_uses_defaults() {
+        let list = parse(&FilmQuery::default()).unwrap();
+        assert_eq!(list, ListFilms::default());
+    }
+
+    #[test]
+    fn out_of_range_limit_is_rejected() {
+        for limit in [0, MAX_PAGE_SIZE + 1] {
+            let query = FilmQuery {
+                limit: Some(limit),
+                ..Default::default()
+            };
+            assert!(matches!(parse(&query), Err(FilmError::Validation(_))));
+        }
+    }
+
+    #[test]
+    fn inverted_year_range_is_rejected() {
+        let query = FilmQuery {
+            year_from: Some(2010),
+            year_to: Some(2000),
+            ..Default::default()
+        };
+        assert!(matches!(parse(&query), Err(FilmError::Validation(_))));
+    }
+
+    #[test]
+    fn cursor_fixes_the_sort_and_excludes_offset() {
+        let codec = CursorCodec::new("secret");
+        let cursor = FilmCursor::after(
+            &film("Heat", "Michael Mann", 1995),
+            FilmSort::Year,
+            SortOrder::Desc,
+        );
+        let token = codec.encode(&cursor);
+
+        let query = FilmQuery {
+            cursor: Some(token.clone()),
+            ..Default::default()
+        };
+        let list = ListFilms::from_query(&query, &codec).unwrap();
+        assert_eq!(list.sort, FilmSort::Year);
+        assert_eq!(list.order, SortOrder::Desc);
+        assert_eq!(list.after, Some(cursor));
+
+        let query = FilmQuery {
+            cursor: Some(token.clone()),
+            sort: Some(FilmSort::Title),
+            ..Default::default()
+        };
+        assert!(ListFilms::from_query(&query, &codec).is_err());
+
+        let query = FilmQuery {
+            cursor: Some(token),
+            offset: Some(10),
+            ..Default::default()
+        };
+        assert!(ListFilms::from_query(&query, &codec).is_err());
+    }
+
+    #[test]
+    fn filters_are_case_insensitive_and_inclusive() {
+        let list = ListFilms {
+            director: Some("christopher nolan".to_string()),
+            title: Some("DARK".to_string()),
+            year_from: Some(2008),
+            year_to: Some(2012),
+            ..Default::default()
+        };
+
+        assert!(list.matches(&film("The Dark Knight", "Christopher Nolan", 2008)));
+        assert!(list.matches(&film("The Dark Knight Rises", "Christopher Nolan", 2012)));
+        assert!(!list.matches(&film("Batman Begins", "Christopher Nolan", 2005)));
+        assert!(!list.matches(&film("Dark City", "Alex Proyas", 2008)));
+    }
+
+    #[test]
+    fn filter_syntax_errors_are_reported_with_their_position() {
+        let query = FilmQuery {
+            filter: Some("year:>=2000 rating:5".to_string()),
+            ..Default::default()
+        };
+        let Err(FilmError::Validation(errors)) = parse(&query) else {
+            panic!("expected a validation error");
+        };
+        let error = &errors.errors()[0];
+        assert_eq!(
+            (error.field.as_str(), error.code.as_str()),
+            ("filter", "syntax")
+        );
+        assert!(
+            error.message.ends_with("at position 12"),
+            "{}",
+            error.message
+        );
+
+        let query = FilmQuery {
+            filter: Some("year:>=2000".to_string()),
+            ..Default::default()
+        };
+        let list = parse(&query).unwrap();
+        assert!(list.matches(&film("Heat", "Michael Mann", 2000)));
+        assert!(!list.matches(&film("Heat", "Michael Mann", 1995)));
+    }
+
+    #[test]
+    fn descending_order_reverses_ties_too() {
+        let a = film("Heat", "Michael Mann", 1995);
+        let b = film("Heat", "Michael Mann", 1995);
+        let asc = ListFilms {
+            sort: FilmSort::Title,
+            ..Default::default()
+        };
+        let desc = ListFilms {
+            order: SortOrder::Desc,
+            ..asc.clone()
+        };
+
+        assert_eq!(asc.compare(&a, &b), a.id.cmp(&b.id));
+        assert_eq!(desc.compare(&a, &b), b.id.cmp(&a.id));
+    }
+}
@@ -1,15 +1,134 @@
-use super::{FilmRepository, FilmResult};
-use shared::models::{CreateFilm, Film};
-use std::{collections::HashMap, sync::RwLock};
-
+use super::memory_store::FilmStore;
+use super::{
+    check_version, CollectionStamp, FilmError, FilmRepository, FilmResult, FilmUpdate, ListFilms,
+    SearchFilms, SuggestFilms,
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use shared::models::{CreateFilm, Film, FilmPatch, FilmSearchHit, FilmSuggestion};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+/// Films kept in process memory, optionally persisted to a JSON snapshot
+/// file with [`MemoryFilmRepository::open`].
+///
+/// The store sits behind an async lock, so a writer makes other requests
+/// wait without blocking their executor threads, and listings walk ordered
+/// indexes instead of sorting every film.
 pub struct MemoryFilmRepository {
-    store: RwLock<HashMap<uuid::Uuid, Film>>,
+    store: RwLock<FilmStore>,
+    snapshot: Option<Snapshot>,
+}
+
+/// When a persistent [`MemoryFilmRepository`] writes its snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotPolicy {
+    /// After every successful write.
+    OnChange,
+    /// At most once per interval, if anything changed, and when the
+    /// repository is dropped.
+    Every(Duration),
+}
+
+struct Snapshot {
+    path: PathBuf,
+    policy: SnapshotPolicy,
+    dirty: AtomicBool,
+    /// Serialises saves so an older state can't replace a newer one.
+    saving: Mutex<()>,
 }
 
 impl MemoryFilmRepository {
     pub fn new() -> MemoryFilmRepository {
         Self {
-            store: RwLock::new(HashMap::new()),
+            store: RwLock::new(FilmStore::default()),
+            snapshot: None,
+        }
+    }
+
+    /// Loads the films in the snapshot at `path`, if it exists, and keeps it
+    /// up to date according to `policy`.
+    ///
+    /// [`SnapshotPolicy::Every`] saves from a task spawned on the current
+    /// Tokio runtime, which stops once the repository is dropped.
+    pub fn open(path: impl Into<PathBuf>, policy: SnapshotPolicy) -> io::Result<Arc<Self>> {
+        let path = path.into();
+        let films = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<Film>>(&bytes).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not a film snapshot: {}", path.display(), e),
+                )
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        tracing::info!("Loaded {} film(s) from {}", films.len(), path.display());
+
+        let mut store = FilmStore::default();
+        films.into_iter().for_each(|film| store.insert(film));
+        let repo = Arc::new(Self {
+            store: RwLock::new(store),
+            snapshot: Some(Snapshot {
+                path,
+                policy,
+                dirty: AtomicBool::new(false),
+                saving: Mutex::new(()),
+            }),
+        });
+        if let SnapshotPolicy::Every(every) = policy {
+            tokio::spawn(save_periodically(Arc::downgrade(&repo), every));
+        }
+
+        Ok(repo)
+    }
+
+    /// Writes the snapshot now if anything changed since the last one.
+    /// Does nothing for a repository without a snapshot file.
+    pub async fn save_snapshot(&self) -> io::Result<()> {
+        let Some(snapshot) = &self.snapshot else {
+            return Ok(());
+        };
+        let _saving = snapshot.saving.lock().await;
+        if !snapshot.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let json = films_json(&*self.store.read().await);
+        let path = snapshot.path.clone();
+        let result = match json {
+            Ok(json) => tokio::task::spawn_blocking(move || write_atomically(&path, &json))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e))),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            // try again on the next save
+            snapshot.dirty.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    /// Records a successful write, saving right away under
+    /// [`SnapshotPolicy::OnChange`].
+    async fn changed(&self) {
+        let Some(snapshot) = &self.snapshot else {
+            return;
+        };
+        snapshot.dirty.store(true, Ordering::Release);
+        if snapshot.policy == SnapshotPolicy::OnChange {
+            self.log_save().await;
+        }
+    }
+
+    async fn log_save(&self) {
+        if let Err(e) = self.save_snapshot().await {
+            // the write itself succeeded, only its durability is delayed
+            tracing::error!("Couldn't save the films snapshot: {}", e);
         }
     }
 }
@@ -20,102 +139,190 @@ impl Default for MemoryFilmRepository {
     }
 }
 
-#[async_trait::async_trait]
-impl FilmRepository for MemoryFilmRepository {
-    async fn get_films(&self) -> FilmResult<Vec<Film>> {
-        let result = self
-            .store
-            .read()
-            .map(|films| films.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>())
-            .map_err(|e| format!("An error occured while trying to read films store: {}", e));
+impl Drop for MemoryFilmRepository {
+    fn drop(&mut self) {
+        let Some(snapshot) = &self.snapshot else {
+            return;
+        };
+        if !snapshot.dirty.load(Ordering::Acquire) {
+            return;
+        }
+        // no lock needed with exclusive access, and no runtime either
+        let result = films_json(self.store.get_mut())
+            .and_then(|json| write_atomically(&snapshot.path, &json));
+        if let Err(e) = result {
+            tracing::error!("Couldn't save the films snapshot: {}", e);
+        }
+    }
+}
 
-        if result.is_err() {
-            tracing::error!("Couldn't retrive a films");
+async fn save_periodically(repo: Weak<MemoryFilmRepository>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match repo.upgrade() {
+            Some(repo) => repo.log_save().await,
+            None => return,
         }
+    }
+}
 
-        result
+/// Every film, oldest first, so snapshots diff well.
+fn films_json(store: &FilmStore) -> io::Result<Vec<u8>> {
+    let mut films = store.values().collect::<Vec<_>>();
+    films.sort_by_key(|film| (film.created_at, film.id));
+    serde_json::to_vec_pretty(&films).map_err(io::Error::other)
+}
+
+/// Replaces `path` with `contents` through a temporary file in the same
+/// directory, so readers and crashes only ever see a whole snapshot.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+/// Current time at the microsecond precision Postgres stores, so timestamps
+/// compare and paginate the same way in both repositories.
+fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+#[async_trait::async_trait]
+impl FilmRepository for MemoryFilmRepository {
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        Ok(self.store.read().await.list(query))
     }
 
     async fn get_film(&self, film_id: &uuid::Uuid) -> FilmResult<Film> {
-        let result = self
-            .store
+        self.store
             .read()
-            .map_err(|e| format!("An error occured while trying to read films store: {}", e))
-            .and_then(|films| {
-                films
-                    .get(film_id)
-                    .cloned()
-                    .ok_or_else(|| format!("Couldn't find film: {}", film_id))
-            });
-
-        if result.is_err() {
-            tracing::error!("Couldn't retrive a film with id {}", film_id);
-        }
+            .await
+            .get(film_id)
+            .cloned()
+            .ok_or(FilmError::NotFound(*film_id))
+    }
 
-        result
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        let films = self.store.read().await;
+
+        Ok(CollectionStamp {
+            count: films.len() as i64,
+            version_sum: films.values().map(|film| film.version as i64).sum(),
+            last_modified: films
+                .values()
+                .filter_map(|film| film.updated_at.max(film.created_at))
+                .max(),
+        })
     }
 
     async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
-        match self.store.write() {
-            Ok(mut films) => {
-                let id = uuid::Uuid::new_v4();
-                let utc_now = chrono::Utc::now();
-                let new_film = Film {
-                    id,
-                    title: create_film.title.clone(),
-                    director: create_film.director.clone(),
-                    year: create_film.year,
-                    poster: create_film.poster.clone(),
-                    created_at: Some(utc_now),
-                    updated_at: None,
-                };
-                films.insert(id, new_film.clone());
-                tracing::trace!("Film with id {} successfully created", id);
-                Ok(new_film)
-            }
-            Err(e) => {
-                let err = format!("An error occured while trying to create film: {}", e);
-                tracing::error!(err);
-                Err(err)
-            }
-        }
+        let new_film = Film {
+            id: uuid::Uuid::new_v4(),
+            title: create_film.title.clone(),
+            director: create_film.director.clone(),
+            year: create_film.year,
+            poster: create_film.poster.clone(),
+            created_at: Some(now()),
+            updated_at: None,
+            version: 1,
+        };
+        self.store.write().await.insert(new_film.clone());
+        tracing::trace!("Film with id {} successfully created", new_film.id);
+
+        self.changed().await;
+        Ok(new_film)
     }
 
-    async fn update_film(&self, film: &Film) -> FilmResult<Film> {
-        match self.store.write() {
-            Ok(mut films) => {
-                let utc_now = chrono::Utc::now();
-                if let Some(the_film) = films.get_mut(&film.id) {
-                    the_film.title = film.title.clone();
-                    the_film.director = film.director.clone();
-                    the_film.year = film.year;
-                    the_film.poster = film.poster.clone();
-                    the_film.updated_at = Some(utc_now);
-                    Ok(the_film.clone())
-                } else {
-                    Err(format!("Film with id {} does not exist", film.id))
-                }
-            }
-            Err(e) => {
-                let err = format!("An error occured while trying to update film: {}", e);
-                tracing::error!(err);
-                Err(err)
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        let update = {
+            let mut films = self.store.write().await;
+            let previous = films
+                .get(&film.id)
+                .cloned()
+                .ok_or(FilmError::NotFound(film.id))?;
+            check_version(&previous, if_version)?;
+            let mut the_film = previous.clone();
+            the_film.title = film.title.clone();
+            the_film.director = film.director.clone();
+            the_film.year = film.year;
+            the_film.poster = film.poster.clone();
+            the_film.updated_at = Some(now());
+            the_film.version += 1;
+            films.insert(the_film.clone());
+            FilmUpdate {
+                film: the_film,
+                previous,
             }
-        }
+        };
+
+        self.changed().await;
+        Ok(update)
     }
 
-    async fn delete_film(&self, film_id: &uuid::Uuid) -> FilmResult<uuid::Uuid> {
-        match self.store.write() {
-            Ok(mut films) => {
-                films.remove(&film_id);
-                Ok(film_id.to_owned())
+    async fn patch_film(
+        &self,
+        film_id: &uuid::Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        let update = {
+            let mut films = self.store.write().await;
+            let previous = films
+                .get(film_id)
+                .cloned()
+                .ok_or(FilmError::NotFound(*film_id))?;
+            check_version(&previous, if_version)?;
+            let mut the_film = previous.clone();
+            if patch.is_empty() {
+                return Ok(FilmUpdate {
+                    film: the_film,
+                    previous,
+                });
             }
-            Err(e) => {
-                let err = format!("An error occured while trying to delete film: {}", e);
-                tracing::error!(err);
-                Err(err)
+            patch.apply_to(&mut the_film);
+            the_film.updated_at = Some(now());
+            the_film.version += 1;
+            films.insert(the_film.clone());
+            FilmUpdate {
+                film: the_film,
+                previous,
             }
-        }
+        };
+
+        self.changed().await;
+        Ok(update)
+    }
+
+    async fn delete_film(&self, film_id: &uuid::Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        let deleted = {
+            let mut films = self.store.write().await;
+            let the_film = films
+                .get(film_id)
+                .cloned()
+                .ok_or(FilmError::NotFound(*film_id))?;
+            check_version(&the_film, if_version)?;
+            films.remove(film_id);
+            the_film
+        };
+
+        self.changed().await;
+        Ok(deleted)
+    }
+
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        Ok(self.store.read().await.search(query))
+    }
+
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        Ok(self.store.read().await.suggest(query))
     }
 }
 
@@ -127,6 +334,15 @@ mod tests {
         MemoryFilmRepository::new()
     }
 
+    fn repo_with(film: &Film) -> MemoryFilmRepository {
+        let mut store = FilmStore::default();
+        store.insert(film.clone());
+        MemoryFilmRepository {
+            store: RwLock::new(store),
+            snapshot: None,
+        }
+    }
+
     fn generate_test_film(id: &'static str) -> Film {
         Film {
             id: uuid::Uuid::new_v4(),
@@ -136,6 +352,7 @@ mod tests {
             year: 2001,
             created_at: Some(chrono::Utc::now()),
             updated_at: None,
+            version: 1,
         }
     }
 
@@ -152,7 +369,7 @@ mod tests {
     async fn empty_store_will_return_empty_film_list() {
         let mem_film_repo = create_empty_store();
 
-        let films = mem_film_repo.get_films().await;
+        let films = mem_film_repo.get_films(&ListFilms::default()).await;
         let expected = vec![];
 
         assert!(films.is_ok());
@@ -176,7 +393,7 @@ mod tests {
         let film = result.unwrap();
         let expected = vec![film];
 
-        let films = mem_film_repo.get_films().await;
+        let films = mem_film_repo.get_films(&ListFilms::default()).await;
         assert!(films.is_ok());
         assert_eq!(films.unwrap(), expected);
     }
@@ -198,11 +415,11 @@ mod tests {
         let film_id = result.unwrap().id;
         let expected = film_id.to_string();
 
-        let deleted_film_uuid = mem_film_repo.delete_film(&film_id).await;
+        let deleted_film_uuid = mem_film_repo.delete_film(&film_id, None).await;
         assert!(deleted_film_uuid.is_ok());
-        assert_eq!(deleted_film_uuid.unwrap().to_string(), expected);
+        assert_eq!(deleted_film_uuid.unwrap().id.to_string(), expected);
 
-        let films = mem_film_repo.get_films().await;
+        let films = mem_film_repo.get_films(&ListFilms::default()).await;
         assert!(films.is_ok());
         assert_eq!(films.unwrap().len(), 0);
     }
@@ -210,7 +427,7 @@ mod tests {
     #[actix_rt::test]
     async fn repo_must_be_empty_on_new() {
         let repo = MemoryFilmRepository::new();
-        let result = repo.get_films().await;
+        let result = repo.get_films(&ListFilms::default()).await;
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -220,7 +437,7 @@ mod tests {
     #[actix_rt::test]
     async fn repo_must_be_empty_on_default() {
         let repo = MemoryFilmRepository::default();
-        let result = repo.get_films().await;
+        let result = repo.get_films(&ListFilms::default()).await;
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -229,11 +446,8 @@ mod tests {
 
     #[actix_rt::test]
     async fn get_film_works() {
-        let store = RwLock::new(HashMap::new());
         let film = generate_test_film("1");
-        store.write().unwrap().insert(film.id, film.clone());
-
-        let repo = MemoryFilmRepository { store };
+        let repo = repo_with(&film);
         let result = repo.get_film(&film.id).await;
 
         assert!(result.is_ok());
@@ -245,19 +459,27 @@ mod tests {
         let film_update = generate_test_film("2");
 
         let repo = MemoryFilmRepository::default();
-        let result = repo.update_film(&film_update).await;
+        let result = repo.update_film(&film_update, None).await;
 
         assert!(result.is_err());
         let err = result.unwrap_err();
-        assert!(err.contains("does not exist"));
+        assert!(matches!(err, FilmError::NotFound(id) if id == film_update.id));
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[actix_rt::test]
+    async fn get_film_returns_not_found_for_unknown_id() {
+        let repo = MemoryFilmRepository::default();
+        let id = uuid::Uuid::new_v4();
+        let result = repo.get_film(&id).await;
+
+        assert!(matches!(result, Err(FilmError::NotFound(not_found)) if not_found == id));
     }
 
     #[actix_rt::test]
     async fn create_film_works() {
-        let store = RwLock::new(HashMap::new());
         let create_film = generate_test_create_film("1");
-
-        let repo = MemoryFilmRepository { store };
+        let repo = MemoryFilmRepository::default();
         let result = repo.create_film(&create_film).await;
 
         assert!(result.is_ok());
@@ -271,19 +493,18 @@ mod tests {
 
     #[actix_rt::test]
     async fn update_film_works() {
-        let store = RwLock::new(HashMap::new());
         let film = generate_test_film("1");
-        store.write().unwrap().insert(film.id, film.clone());
+        let repo = repo_with(&film);
 
         let mut film_update = film.clone();
         film_update.title = "new-title".to_string();
         film_update.year = 2002;
-
-        let repo = MemoryFilmRepository { store };
-        let result = repo.update_film(&film_update).await;
+        let result = repo.update_film(&film_update, None).await;
 
         assert!(result.is_ok());
-        let updated_file = result.unwrap();
+        let update = result.unwrap();
+        assert_eq!(update.previous, film);
+        let updated_file = update.film;
         assert_eq!(updated_file.id, film.id);
         assert_ne!(updated_file.title, film.title);
         assert_eq!(updated_file.title, film_update.title);
@@ -298,40 +519,234 @@ mod tests {
 
     #[actix_rt::test]
     async fn update_film_fails_if_file_is_not_present() {
-        let store = RwLock::new(HashMap::new());
         let film = generate_test_film("1");
-        store.write().unwrap().insert(film.id, film.clone());
+        let repo = repo_with(&film);
 
         let film_update = generate_test_film("2");
-
-        let repo = MemoryFilmRepository { store };
-        let result = repo.update_film(&film_update).await;
+        let result = repo.update_film(&film_update, None).await;
 
         assert!(result.is_err());
         let err = result.unwrap_err();
-        assert!(err.contains("does not exist"));
+        assert!(matches!(err, FilmError::NotFound(id) if id == film_update.id));
+        assert!(err.to_string().contains("does not exist"));
     }
 
     #[actix_rt::test]
     async fn delete_film_works() {
-        let store = RwLock::new(HashMap::new());
         let film = generate_test_film("1");
-        store.write().unwrap().insert(film.id, film.clone());
-
-        let repo = MemoryFilmRepository { store };
-        let result = repo.delete_film(&film.id).await;
+        let repo = repo_with(&film);
+        let result = repo.delete_film(&film.id, None).await;
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), film.id);
+        assert_eq!(result.unwrap(), film);
     }
 
     #[actix_rt::test]
-    async fn delete_film_does_not_fail_if_film_is_not_present() {
+    async fn delete_film_fails_if_film_is_not_present() {
         let repo = MemoryFilmRepository::default();
         let id = uuid::Uuid::new_v4();
-        let result = repo.delete_film(&id).await;
+        let result = repo.delete_film(&id, None).await;
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), id);
+        assert!(matches!(result, Err(FilmError::NotFound(missing)) if missing == id));
+    }
+
+    #[actix_rt::test]
+    async fn get_films_filters_sorts_and_pages() {
+        let repo = MemoryFilmRepository::default();
+        for (title, year) in [("C", 2003), ("A", 2001), ("B", 2002), ("D", 1999)] {
+            let film = CreateFilm {
+                title: title.to_string(),
+                director: "Director".to_string(),
+                year,
+                poster: String::new(),
+            };
+            repo.create_film(&film).await.unwrap();
+        }
+
+        let query = ListFilms {
+            year_from: Some(2000),
+            sort: shared::models::FilmSort::Title,
+            order: shared::models::SortOrder::Desc,
+            limit: 2,
+            offset: 1,
+            ..Default::default()
+        };
+        let films = repo.get_films(&query).await.unwrap();
+
+        let titles = films.iter().map(|f| f.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["B", "A"]);
+    }
+
+    #[actix_rt::test]
+    async fn patch_film_only_changes_supplied_fields() {
+        let film = generate_test_film("1");
+        let repo = repo_with(&film);
+
+        let patch = FilmPatch {
+            title: Some(Some("new-title".to_string())),
+            ..Default::default()
+        };
+        let patched = repo.patch_film(&film.id, &patch, None).await.unwrap().film;
+
+        assert_eq!(patched.title, "new-title");
+        assert_eq!(patched.director, film.director);
+        assert_eq!(patched.year, film.year);
+        assert_eq!(patched.poster, film.poster);
+        assert_eq!(patched.created_at, film.created_at);
+        assert!(patched.updated_at.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn patch_film_fails_if_film_is_not_present() {
+        let repo = MemoryFilmRepository::default();
+        let id = uuid::Uuid::new_v4();
+        let result = repo.patch_film(&id, &FilmPatch::default(), None).await;
+
+        assert!(matches!(result, Err(FilmError::NotFound(not_found)) if not_found == id));
+    }
+
+    #[actix_rt::test]
+    async fn writes_bump_the_version() {
+        let repo = MemoryFilmRepository::default();
+        let film = repo
+            .create_film(&generate_test_create_film("1"))
+            .await
+            .unwrap();
+        assert_eq!(film.version, 1);
+
+        let updated = repo.update_film(&film, Some(1)).await.unwrap().film;
+        assert_eq!(updated.version, 2);
+
+        let patch = FilmPatch {
+            year: Some(Some(2002)),
+            ..Default::default()
+        };
+        let patched = repo.patch_film(&film.id, &patch, Some(2)).await.unwrap();
+        assert_eq!(patched.previous, updated);
+        assert_eq!(patched.film.version, 3);
+
+        let unchanged = repo
+            .patch_film(&film.id, &FilmPatch::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(unchanged.film, patched.film);
+        assert_eq!(unchanged.previous, patched.film);
+    }
+
+    #[actix_rt::test]
+    async fn stale_version_is_rejected_without_writing() {
+        let repo = MemoryFilmRepository::default();
+        let film = repo
+            .create_film(&generate_test_create_film("1"))
+            .await
+            .unwrap();
+        repo.update_film(&film, None).await.unwrap();
+
+        let mut stale = film.clone();
+        stale.title = "stale-title".to_string();
+        let result = repo.update_film(&stale, Some(film.version)).await;
+        assert!(matches!(result, Err(FilmError::VersionMismatch(id)) if id == film.id));
+
+        let result = repo.delete_film(&film.id, Some(film.version)).await;
+        assert!(matches!(result, Err(FilmError::VersionMismatch(_))));
+
+        let stored = repo.get_film(&film.id).await.unwrap();
+        assert_eq!(stored.title, film.title);
+        assert_eq!(stored.version, 2);
+    }
+
+    #[actix_rt::test]
+    async fn collection_stamp_moves_with_every_write() {
+        let repo = MemoryFilmRepository::default();
+        assert_eq!(
+            repo.collection_stamp().await.unwrap(),
+            CollectionStamp::default()
+        );
+
+        let film = repo
+            .create_film(&generate_test_create_film("1"))
+            .await
+            .unwrap();
+        let created = repo.collection_stamp().await.unwrap();
+        assert_eq!(created.count, 1);
+        assert_eq!(created.last_modified, film.created_at);
+
+        repo.update_film(&film, None).await.unwrap();
+        let updated = repo.collection_stamp().await.unwrap();
+        assert_eq!(updated.version_sum, 2);
+        assert_ne!(updated, created);
+
+        repo.delete_film(&film.id, None).await.unwrap();
+        assert_eq!(repo.collection_stamp().await.unwrap().count, 0);
+    }
+
+    fn snapshot_path() -> PathBuf {
+        std::env::temp_dir().join(format!("films-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[actix_rt::test]
+    async fn snapshot_survives_a_restart() {
+        let path = snapshot_path();
+
+        let repo = MemoryFilmRepository::open(&path, SnapshotPolicy::OnChange).unwrap();
+        let kept = repo
+            .create_film(&generate_test_create_film("1"))
+            .await
+            .unwrap();
+        let deleted = repo
+            .create_film(&generate_test_create_film("2"))
+            .await
+            .unwrap();
+        repo.delete_film(&deleted.id, None).await.unwrap();
+        let kept = repo.update_film(&kept, None).await.unwrap().film;
+
+        // saved on every change, without waiting for the drop
+        let reopened = MemoryFilmRepository::open(&path, SnapshotPolicy::OnChange).unwrap();
+        let films = reopened.get_films(&ListFilms::default()).await.unwrap();
+        assert_eq!(films, vec![kept]);
+
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        assert!(!PathBuf::from(tmp).exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn periodic_snapshots_save_changes_and_flush_on_drop() {
+        let path = snapshot_path();
+        let policy = SnapshotPolicy::Every(Duration::from_millis(10));
+
+        let repo = MemoryFilmRepository::open(&path, policy).unwrap();
+        assert!(!path.exists(), "nothing to save yet");
+        let first = repo
+            .create_film(&generate_test_create_film("1"))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let saved = MemoryFilmRepository::open(&path, policy).unwrap();
+        assert_eq!(saved.get_film(&first.id).await.unwrap(), first);
+        drop(saved);
+
+        let second = repo
+            .create_film(&generate_test_create_film("2"))
+            .await
+            .unwrap();
+        drop(repo);
+        let saved = MemoryFilmRepository::open(&path, policy).unwrap();
+        assert_eq!(saved.get_film(&second.id).await.unwrap(), second);
+
+        drop(saved);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn corrupt_snapshot_is_refused() {
+        let path = snapshot_path();
+        fs::write(&path, "not json").unwrap();
+
+        let result = MemoryFilmRepository::open(&path, SnapshotPolicy::OnChange);
+        assert!(matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidData));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not json");
+        fs::remove_file(path).unwrap();
     }
 }
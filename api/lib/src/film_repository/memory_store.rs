@@ -0,0 +1,281 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+
+use shared::models::{Film, FilmSearchHit, FilmSort, FilmSuggestion, SortOrder};
+use uuid::Uuid;
+
+use super::search::SearchIndex;
+use super::suggest::SuggestIndex;
+use super::{ListFilms, SearchFilms, SortKey, SuggestFilms};
+
+type Entry = (SortKey, Uuid);
+type Index = BTreeSet<Entry>;
+
+/// Films by id plus one ordered index per sort column, kept in step on
+/// every write.
+///
+/// A listing walks the index of its sort from the cursor (or, sorting by
+/// year, from the requested year range) and stops once the page is full,
+/// so it costs the rows skipped and filtered out rather than a sort of the
+/// whole collection. Searches look their words up in an inverted index, and
+/// suggestions their prefix up in sorted per-value film counts.
+#[derive(Debug, Default)]
+pub(super) struct FilmStore {
+    films: HashMap<Uuid, Film>,
+    created_at: Index,
+    title: Index,
+    year: Index,
+    director: Index,
+    search: SearchIndex,
+    suggest: SuggestIndex,
+}
+
+const SORTS: [FilmSort; 4] = [
+    FilmSort::CreatedAt,
+    FilmSort::Title,
+    FilmSort::Year,
+    FilmSort::Director,
+];
+
+impl FilmStore {
+    pub fn get(&self, id: &Uuid) -> Option<&Film> {
+        self.films.get(id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Film> {
+        self.films.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.films.len()
+    }
+
+    /// Adds `film`, replacing and unindexing any film with the same id.
+    pub fn insert(&mut self, film: Film) {
+        self.remove(&film.id);
+        for sort in SORTS {
+            self.index_mut(sort)
+                .insert((SortKey::of(sort, &film), film.id));
+        }
+        self.search.insert(&film);
+        self.suggest.insert(&film);
+        self.films.insert(film.id, film);
+    }
+
+    pub fn remove(&mut self, id: &Uuid) -> Option<Film> {
+        let film = self.films.remove(id)?;
+        for sort in SORTS {
+            self.index_mut(sort)
+                .remove(&(SortKey::of(sort, &film), film.id));
+        }
+        self.search.remove(&film);
+        self.suggest.remove(&film);
+        Some(film)
+    }
+
+    /// The page of films `query` selects, in its order.
+    pub fn list(&self, query: &ListFilms) -> Vec<Film> {
+        let (lower, upper) = bounds(query);
+        if is_empty(&lower, &upper) {
+            return Vec::new();
+        }
+
+        let range = self.index(query.sort).range((lower, upper));
+        let ids: Box<dyn Iterator<Item = &Entry>> = match query.order {
+            SortOrder::Asc => Box::new(range),
+            SortOrder::Desc => Box::new(range.rev()),
+        };
+
+        ids.filter_map(|(_, id)| self.films.get(id))
+            .filter(|film| query.matches(film))
+            .skip(query.offset as usize)
+            .take(query.limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// The page of films matching `query`, best first.
+    pub fn search(&self, query: &SearchFilms) -> Vec<FilmSearchHit> {
+        let mut hits = self
+            .search
+            .search(&query.terms)
+            .into_iter()
+            .filter_map(|(id, score)| Some((self.films.get(&id)?, score)))
+            .collect::<Vec<_>>();
+        hits.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .total_cmp(a_score)
+                .then_with(|| a.title.cmp(&b.title))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        hits.into_iter()
+            .skip(query.offset as usize)
+            .take(query.limit as usize)
+            .map(|(film, score)| FilmSearchHit {
+                film: film.clone(),
+                score,
+            })
+            .collect()
+    }
+
+    pub fn suggest(&self, query: &SuggestFilms) -> Vec<FilmSuggestion> {
+        self.suggest.suggest(query)
+    }
+
+    fn index(&self, sort: FilmSort) -> &Index {
+        match sort {
+            FilmSort::CreatedAt => &self.created_at,
+            FilmSort::Title => &self.title,
+            FilmSort::Year => &self.year,
+            FilmSort::Director => &self.director,
+        }
+    }
+
+    fn index_mut(&mut self, sort: FilmSort) -> &mut Index {
+        match sort {
+            FilmSort::CreatedAt => &mut self.created_at,
+            FilmSort::Title => &mut self.title,
+            FilmSort::Year => &mut self.year,
+            FilmSort::Director => &mut self.director,
+        }
+    }
+}
+
+/// Part of the sort index `query` can possibly select, ascending.
+fn bounds(query: &ListFilms) -> (Bound<Entry>, Bound<Entry>) {
+    let (mut lower, mut upper) = (Bound::Unbounded, Bound::Unbounded);
+
+    if query.sort == FilmSort::Year {
+        if let Some(from) = query.year_from {
+            lower = Bound::Included((SortKey::Year(from), Uuid::nil()));
+        }
+        if let Some(to) = query.year_to {
+            upper = Bound::Included((SortKey::Year(to), Uuid::from_u128(u128::MAX)));
+        }
+    }
+
+    if let Some(after) = &query.after {
+        let after = (after.key.clone(), after.id);
+        match query.order {
+            SortOrder::Asc if !starts_after(&lower, &after) => lower = Bound::Excluded(after),
+            SortOrder::Desc if !ends_before(&upper, &after) => upper = Bound::Excluded(after),
+            _ => {}
+        }
+    }
+
+    (lower, upper)
+}
+
+/// Whether no key fits between the bounds, which `BTreeSet::range` panics on.
+fn is_empty(lower: &Bound<Entry>, upper: &Bound<Entry>) -> bool {
+    match (lower, upper) {
+        (Bound::Included(lower), Bound::Included(upper)) => lower > upper,
+        (
+            Bound::Included(lower) | Bound::Excluded(lower),
+            Bound::Included(upper) | Bound::Excluded(upper),
+        ) => lower >= upper,
+        _ => false,
+    }
+}
+
+fn starts_after(lower: &Bound<Entry>, key: &Entry) -> bool {
+    matches!(lower, Bound::Included(bound) | Bound::Excluded(bound) if bound > key)
+}
+
+fn ends_before(upper: &Bound<Entry>, key: &Entry) -> bool {
+    matches!(upper, Bound::Included(bound) | Bound::Excluded(bound) if bound < key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film_repository::FilmCursor;
+
+    fn film(title: &str, year: u16) -> Film {
+        Film {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            director: "Director".to_string(),
+            year,
+            created_at: Some(chrono::Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    /// The page `query` selects by filtering and sorting every film.
+    fn reference(store: &FilmStore, query: &ListFilms) -> Vec<Film> {
+        let mut films = store
+            .values()
+            .filter(|film| query.matches(film))
+            .cloned()
+            .collect::<Vec<_>>();
+        films.sort_by(|a, b| query.compare(a, b));
+        films
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(query.limit as usize)
+            .collect()
+    }
+
+    #[test]
+    fn listing_matches_a_full_sort() {
+        let mut store = FilmStore::default();
+        for (i, title) in ["Heat", "Alien", "Heat", "Ran", "Brazil", "Alien"]
+            .into_iter()
+            .enumerate()
+        {
+            store.insert(film(title, 1990 + (i as u16 % 3)));
+        }
+        let pivot = store.values().next().unwrap().clone();
+
+        for sort in SORTS {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                for (year_from, year_to) in [
+                    (None, None),
+                    (Some(1991), None),
+                    (None, Some(1991)),
+                    (Some(1992), Some(1990)),
+                ] {
+                    let query = ListFilms {
+                        sort,
+                        order,
+                        year_from,
+                        year_to,
+                        limit: 3,
+                        offset: 1,
+                        ..Default::default()
+                    };
+                    assert_eq!(store.list(&query), reference(&store, &query));
+
+                    let query = ListFilms {
+                        offset: 0,
+                        after: Some(FilmCursor::after(&pivot, sort, order)),
+                        ..query
+                    };
+                    assert_eq!(store.list(&query), reference(&store, &query));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn replacing_and_removing_keep_the_indexes_in_step() {
+        let mut store = FilmStore::default();
+        let mut heat = film("Heat", 1995);
+        store.insert(heat.clone());
+        heat.title = "Thief".to_string();
+        store.insert(heat.clone());
+
+        let by_title = ListFilms {
+            sort: FilmSort::Title,
+            ..Default::default()
+        };
+        assert_eq!(store.list(&by_title), vec![heat.clone()]);
+        assert_eq!(store.title.len(), 1);
+
+        assert_eq!(store.remove(&heat.id), Some(heat));
+        assert!(store.list(&by_title).is_empty());
+        assert!(SORTS.iter().all(|sort| store.index(*sort).is_empty()));
+    }
+}
@@ -1,19 +1,436 @@
-use shared::models::{CreateFilm, Film};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use shared::models::{CreateFilm, Film, FilmPatch, FilmSearchHit, FilmSuggestion};
+use shared::validation::ValidationErrors;
+
+use crate::problem::Problem;
+use std::sync::Arc;
 use uuid::Uuid;
 
+pub use cached_film_repository::{CacheConfig, CacheStats, CachedFilmRepository};
+pub use changes::{FilmChange, FilmChangeKind, FilmNotification};
+pub use cursor::{CursorCodec, FilmCursor, SortKey};
+pub use film_events::{FilmEventSubscription, FilmEvents, LiveFilmEvent};
+pub use listing::{ListFilms, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+pub use memory_film_repository::{MemoryFilmRepository, SnapshotPolicy};
+pub use postgres_film_listener::{PostgresFilmListener, FILM_CHANGES_CHANNEL};
 pub use postgres_film_repository::PostgresFilmRepository;
+pub use publishing_film_repository::PublishingFilmRepository;
+pub use search::{SearchFilms, MAX_SEARCH_CHARS, MAX_SEARCH_TERMS};
+pub use sqlite_film_repository::SqliteFilmRepository;
+pub(crate) use sqlite_film_repository::{
+    fold_films as sqlite_fold_films, now as sqlite_now, timestamp as sqlite_timestamp,
+};
+pub use suggest::{SuggestFilms, DEFAULT_SUGGESTIONS, MAX_PREFIX_CHARS, MAX_SUGGESTIONS};
 
+mod cached_film_repository;
+mod changes;
+mod cursor;
+mod film_events;
+mod listing;
 mod memory_film_repository;
+mod memory_store;
+mod postgres_film_listener;
 mod postgres_film_repository;
+mod publishing_film_repository;
+mod search;
+mod sql;
+mod sqlite_film_repository;
+mod suggest;
+
+/// Errors produced by every [`FilmRepository`] implementation.
+///
+/// Each variant maps to exactly one HTTP status so handlers never have to
+/// guess how a repository failure should be reported to the client.
+#[derive(Debug, thiserror::Error)]
+pub enum FilmError {
+    #[error("Film with id {0} does not exist")]
+    NotFound(Uuid),
+    #[error("Film with id {0} has been modified since it was read")]
+    VersionMismatch(Uuid),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Validation failed: {0}")]
+    Validation(ValidationErrors),
+    #[error("Film store unavailable: {0}")]
+    Unavailable(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
 
-pub type FilmError = String;
 pub type FilmResult<T> = Result<T, FilmError>;
 
+/// Summary of the whole collection, used as the film list's cache validator.
+///
+/// Creating or deleting a film changes `count`, updating one bumps
+/// `version_sum` and `last_modified` moves with every create or update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CollectionStamp {
+    pub count: i64,
+    pub version_sum: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// A film as an update left it, with the film it replaced.
+///
+/// Both come from the write itself, so `previous` is exactly the state the
+/// update applied to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilmUpdate {
+    pub film: Film,
+    pub previous: Film,
+}
+
+impl From<ValidationErrors> for FilmError {
+    fn from(errors: ValidationErrors) -> Self {
+        FilmError::Validation(errors)
+    }
+}
+
+impl From<sqlx::Error> for FilmError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(ref db)
+                if db.try_downcast_ref::<sqlx::sqlite::SqliteError>().is_some() =>
+            {
+                from_sqlite(db.as_ref())
+            }
+            sqlx::Error::Database(ref db) => match db.code().as_deref() {
+                // unique_violation, foreign_key_violation
+                Some("23505") | Some("23503") => FilmError::Conflict(db.message().to_string()),
+                // integrity constraints and data exceptions (e.g. out of range values)
+                Some(code) if code.starts_with("23") || code.starts_with("22") => {
+                    let name = db.constraint().or_else(|| {
+                        db.try_downcast_ref::<sqlx::postgres::PgDatabaseError>()
+                            .and_then(|pg| pg.column())
+                    });
+                    constraint_violation(name, db.message())
+                }
+                // connection exceptions, insufficient resources, operator intervention
+                Some(code)
+                    if code.starts_with("08")
+                        || code.starts_with("53")
+                        || code.starts_with("57") =>
+                {
+                    FilmError::Unavailable(db.message().to_string())
+                }
+                _ => FilmError::Internal(e.to_string()),
+            },
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_) => FilmError::Unavailable(e.to_string()),
+            _ => FilmError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Maps SQLite's extended result codes the same way as the SQLSTATEs above.
+fn from_sqlite(db: &dyn sqlx::error::DatabaseError) -> FilmError {
+    let code = db
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .unwrap_or_default();
+    match code {
+        // SQLITE_CONSTRAINT_UNIQUE, _PRIMARYKEY, _FOREIGNKEY
+        2067 | 1555 | 787 => FilmError::Conflict(db.message().to_string()),
+        // any other SQLITE_CONSTRAINT, SQLITE_TOOBIG, SQLITE_MISMATCH
+        // the message ends with the constraint or column, e.g.
+        // "NOT NULL constraint failed: films.title"
+        code if code & 0xff == 19 => {
+            constraint_violation(db.message().rsplit(": ").next(), db.message())
+        }
+        code if code == 18 || code == 20 => constraint_violation(None, db.message()),
+        // SQLITE_BUSY, SQLITE_LOCKED, SQLITE_FULL, SQLITE_CANTOPEN
+        code if matches!(code & 0xff, 5 | 6 | 13 | 14) => {
+            FilmError::Unavailable(db.message().to_string())
+        }
+        _ => FilmError::Internal(db.to_string()),
+    }
+}
+
+/// Fields of a film that database constraints may guard.
+const FILM_FIELDS: [&str; 4] = ["title", "director", "year", "poster"];
+
+/// A value the database refused. Its message names constraints and values,
+/// so it is only logged; the client sees the film field `name` guards, as
+/// a constraint (`films_year_check`) or column (`films.year`), or `film`.
+fn constraint_violation(name: Option<&str>, message: &str) -> FilmError {
+    tracing::warn!("The database refused a film: {}", message);
+    let name = name.unwrap_or_default();
+    let name = name.rsplit('.').next().unwrap_or(name);
+    let name = name.strip_prefix("films_").unwrap_or(name);
+    let field = FILM_FIELDS
+        .into_iter()
+        .find(|field| name.split('_').next() == Some(*field))
+        .unwrap_or("film");
+    FilmError::Validation(ValidationErrors::single(
+        field,
+        "constraint",
+        "The value is not accepted by the film store",
+    ))
+}
+
+/// Fails unless `film` has the version a conditional write expects.
+fn check_version(film: &Film, if_version: Option<i32>) -> FilmResult<()> {
+    match if_version {
+        Some(version) if version != film.version => Err(FilmError::VersionMismatch(film.id)),
+        _ => Ok(()),
+    }
+}
+
+/// Converts a year for the `smallint` column, refusing values that would wrap.
+fn year_to_smallint(year: u16) -> FilmResult<i16> {
+    i16::try_from(year).map_err(|_| {
+        FilmError::Validation(ValidationErrors::single(
+            "year",
+            "range",
+            format!("{} does not fit the year column", year),
+        ))
+    })
+}
+
+impl ResponseError for FilmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            FilmError::NotFound(_) => StatusCode::NOT_FOUND,
+            FilmError::VersionMismatch(_) => StatusCode::PRECONDITION_FAILED,
+            FilmError::Conflict(_) => StatusCode::CONFLICT,
+            FilmError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            FilmError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            FilmError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            tracing::error!("{}", self);
+        } else if let FilmError::Conflict(_) = self {
+            tracing::warn!("{}", self);
+        }
+
+        Problem::from(self).error_response()
+    }
+}
+
+impl From<&FilmError> for Problem {
+    fn from(e: &FilmError) -> Self {
+        let status = e.status_code();
+        match e {
+            FilmError::NotFound(_) => {
+                Problem::new(status, "/problems/film-not-found", "Film not found")
+                    .with_detail(e.to_string())
+            }
+            FilmError::VersionMismatch(_) => Problem::new(
+                status,
+                "/problems/precondition-failed",
+                "Precondition failed",
+            )
+            .with_detail(e.to_string()),
+            // the database's message names constraints and values, it is
+            // only logged, see `error_response`
+            FilmError::Conflict(_) => Problem::new(status, "/problems/conflict", "Conflict")
+                .with_detail("The film conflicts with the current state of the collection"),
+            FilmError::Validation(errors) => {
+                Problem::new(status, "/problems/validation-failed", "Validation failed")
+                    .with_detail(format!(
+                        "{} field(s) failed validation",
+                        errors.errors().len()
+                    ))
+                    .with_errors(errors.errors())
+            }
+            // don't leak storage internals to the client
+            FilmError::Unavailable(_) => Problem::new(
+                status,
+                "/problems/service-unavailable",
+                "Service unavailable",
+            ),
+            FilmError::Internal(_) => {
+                Problem::new(status, "/problems/internal-error", "Internal server error")
+            }
+        }
+    }
+}
+
+/// Storage for films.
+///
+/// Writes taking an `if_version` only apply while the stored film still has
+/// that version, failing with [`FilmError::VersionMismatch`] otherwise; the
+/// check and the write happen atomically. Every successful write bumps the
+/// film's version.
 #[async_trait::async_trait]
 pub trait FilmRepository: Send + Sync + 'static {
-    async fn get_films(&self) -> FilmResult<Vec<Film>>;
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>>;
     async fn get_film(&self, id: &Uuid) -> FilmResult<Film>;
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp>;
     async fn create_film(&self, id: &CreateFilm) -> FilmResult<Film>;
-    async fn update_film(&self, id: &Film) -> FilmResult<Film>;
-    async fn delete_film(&self, id: &Uuid) -> FilmResult<Uuid>;
+    async fn update_film(&self, id: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate>;
+    /// Changes only the fields present in `patch`; an empty patch leaves the
+    /// film, and its version, as they are.
+    async fn patch_film(
+        &self,
+        id: &Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate>;
+    /// Removes the film, returning it as it was.
+    async fn delete_film(&self, id: &Uuid, if_version: Option<i32>) -> FilmResult<Film>;
+    /// Films matching every search term, best first; see [`SearchFilms`].
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>>;
+    /// Values of a field completing a prefix, most used first; see
+    /// [`SuggestFilms`].
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>>;
+
+    /// Where this repository publishes its writes, if it does; see
+    /// [`PublishingFilmRepository`].
+    fn events(&self) -> Option<Arc<FilmEvents>> {
+        None
+    }
+}
+
+/// Lets a repository shared with background tasks, such as a snapshotting
+/// [`MemoryFilmRepository`], be wrapped like any other.
+#[async_trait::async_trait]
+impl<R: FilmRepository> FilmRepository for Arc<R> {
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        (**self).get_films(query).await
+    }
+
+    async fn get_film(&self, id: &Uuid) -> FilmResult<Film> {
+        (**self).get_film(id).await
+    }
+
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        (**self).collection_stamp().await
+    }
+
+    async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
+        (**self).create_film(create_film).await
+    }
+
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        (**self).update_film(film, if_version).await
+    }
+
+    async fn patch_film(
+        &self,
+        id: &Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        (**self).patch_film(id, patch, if_version).await
+    }
+
+    async fn delete_film(&self, id: &Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        (**self).delete_film(id, if_version).await
+    }
+
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        (**self).search_films(query).await
+    }
+
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        (**self).suggest_films(query).await
+    }
+
+    fn events(&self) -> Option<Arc<FilmEvents>> {
+        (**self).events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_maps_to_its_own_status() {
+        let cases = [
+            (FilmError::NotFound(Uuid::new_v4()), StatusCode::NOT_FOUND),
+            (
+                FilmError::VersionMismatch(Uuid::new_v4()),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (FilmError::Conflict("dup".into()), StatusCode::CONFLICT),
+            (
+                FilmError::Validation(ValidationErrors::single("year", "range", "bad")),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                FilmError::Unavailable("down".into()),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                FilmError::Internal("boom".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ];
+
+        for (err, status) in cases {
+            assert_eq!(err.status_code(), status);
+            assert_eq!(err.error_response().status(), status);
+        }
+    }
+
+    #[test]
+    fn server_errors_do_not_leak_details() {
+        let err = FilmError::Internal("relation \"films\" does not exist".into());
+        let details = Problem::from(&err).details();
+
+        assert_eq!(details.status, 500);
+        assert_eq!(details.detail, None);
+    }
+
+    #[actix_rt::test]
+    async fn constraint_errors_name_fields_not_constraints() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query(
+            "CREATE TABLE films (title text NOT NULL, \
+             year integer CONSTRAINT films_year_check CHECK (year > 0))",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let cases = [
+            (
+                "INSERT INTO films (title, year) VALUES ('Heat', -1)",
+                "year",
+            ),
+            (
+                "INSERT INTO films (title, year) VALUES (NULL, 1995)",
+                "title",
+            ),
+        ];
+        for (insert, field) in cases {
+            let err = FilmError::from(sqlx::query(insert).execute(&pool).await.unwrap_err());
+            let FilmError::Validation(errors) = err else {
+                panic!("{} wasn't refused as invalid", insert);
+            };
+            let error = &errors.errors()[0];
+            assert_eq!(
+                (error.field.as_str(), error.code.as_str()),
+                (field, "constraint")
+            );
+            assert!(!error.message.contains("films"), "{}", error.message);
+        }
+    }
+
+    #[test]
+    fn unknown_constraints_are_reported_on_the_film() {
+        let FilmError::Validation(errors) = constraint_violation(Some("films_pkey"), "secret")
+        else {
+            panic!("not a validation error");
+        };
+        assert_eq!(errors.errors()[0].field, "film");
+        assert!(!errors.errors()[0].message.contains("secret"));
+    }
+
+    #[test]
+    fn pool_errors_are_reported_as_unavailable() {
+        let err = FilmError::from(sqlx::Error::PoolTimedOut);
+        assert!(matches!(err, FilmError::Unavailable(_)));
+
+        let err = FilmError::from(sqlx::Error::PoolClosed);
+        assert!(matches!(err, FilmError::Unavailable(_)));
+    }
 }
@@ -0,0 +1,142 @@
+use super::{FilmChange, FilmNotification};
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Channel the `films_notify_change` trigger announces every film write on.
+pub const FILM_CHANGES_CHANNEL: &str = "film_changes";
+
+/// Notifications buffered per subscriber before it lags and is sent a
+/// [`FilmNotification::Resync`] instead.
+const CAPACITY: usize = 1024;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Follows the `film_changes` channel on one dedicated connection and fans
+/// the changes out to every subscriber, whichever instance or client wrote
+/// them.
+///
+/// Listening stops when the listener is dropped or the pool is closed.
+pub struct PostgresFilmListener {
+    sender: broadcast::Sender<FilmNotification>,
+    task: JoinHandle<()>,
+}
+
+impl PostgresFilmListener {
+    /// Starts listening; changes committed from now on are delivered.
+    pub async fn start(pool: &sqlx::PgPool) -> Result<Self, sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(FILM_CHANGES_CHANNEL).await?;
+
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let task = tokio::spawn(forward(listener, sender.clone()));
+        Ok(Self { sender, task })
+    }
+
+    /// Notifications from now on. A receiver that falls behind gets
+    /// `RecvError::Lagged`, which calls for a resync too.
+    pub fn subscribe(&self) -> broadcast::Receiver<FilmNotification> {
+        self.sender.subscribe()
+    }
+}
+
+impl Drop for PostgresFilmListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn forward(mut listener: PgListener, sender: broadcast::Sender<FilmNotification>) {
+    loop {
+        let notification = match listener.try_recv().await {
+            Ok(Some(notification)) => parse(notification.payload()),
+            // reconnected on the next call; whatever happened meanwhile is lost
+            Ok(None) => {
+                tracing::warn!("Lost the film change listener connection, reconnecting");
+                FilmNotification::Resync
+            }
+            Err(sqlx::Error::PoolClosed) => return,
+            Err(e) => {
+                tracing::error!("Film change listener failed: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                FilmNotification::Resync
+            }
+        };
+
+        // no subscribers is fine, they only want changes from when they joined
+        let _ = sender.send(notification);
+    }
+}
+
+/// Reads a trigger payload, asking for a resync when it can't be understood.
+fn parse(payload: &str) -> FilmNotification {
+    match serde_json::from_str::<FilmChange>(payload) {
+        Ok(change) => FilmNotification::Changed(Box::new(change)),
+        Err(e) => {
+            tracing::warn!("Unexpected film change payload {:?}: {}", payload, e);
+            FilmNotification::Resync
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film_repository::FilmChangeKind;
+    use shared::models::Film;
+
+    #[test]
+    fn trigger_payloads_are_parsed() {
+        let id = uuid::Uuid::new_v4();
+        let payload = format!(r#"{{"kind" : "updated", "id" : "{}", "version" : 3}}"#, id);
+
+        assert_eq!(
+            parse(&payload),
+            FilmNotification::Changed(Box::new(FilmChange {
+                kind: FilmChangeKind::Updated,
+                id,
+                version: 3,
+                film: None,
+                previous: None,
+            }))
+        );
+        assert_eq!(parse("{}"), FilmNotification::Resync);
+    }
+
+    #[test]
+    fn films_in_trigger_payloads_are_parsed() {
+        let id = uuid::Uuid::new_v4();
+        let film = |title: &str, version: i32| {
+            format!(
+                r#"{{"id": "{id}", "year": 1995, "title": "{title}", "poster": "", "version": {version}, "director": "Michael Mann", "created_at": "2024-01-02T10:20:30.123456+00:00", "updated_at": null}}"#
+            )
+        };
+        let payload = format!(
+            r#"{{"id": "{id}", "film": {}, "kind": "updated", "version": 2, "previous": {}}}"#,
+            film("Thief", 2),
+            film("Heat", 1)
+        );
+
+        let FilmNotification::Changed(change) = parse(&payload) else {
+            panic!("{} is a change", payload);
+        };
+        let film = change.film.unwrap();
+        assert_eq!(
+            (film.id, film.title.as_str(), film.version),
+            (id, "Thief", 2)
+        );
+        assert_eq!(
+            film.created_at.unwrap().to_rfc3339(),
+            "2024-01-02T10:20:30.123456+00:00"
+        );
+        assert_eq!(
+            change.previous,
+            Some(Film {
+                title: "Heat".to_string(),
+                version: 1,
+                ..film
+            })
+        );
+    }
+}
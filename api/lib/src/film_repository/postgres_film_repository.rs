@@ -1,6 +1,22 @@
-use super::{FilmRepository, FilmResult};
-use shared::models::{CreateFilm, Film};
+use super::search::ScoredFilm;
+use super::sql::{push_filter, push_keyset_condition, write_failure, FilmSql};
+use super::suggest::suggestion;
+use super::{
+    check_version, year_to_smallint, CollectionStamp, FilmError, FilmRepository, FilmResult,
+    FilmUpdate, ListFilms, SearchFilms, SuggestFilms,
+};
+use chrono::{DateTime, Utc};
+use shared::filter::TextMatch;
+use shared::models::{
+    CreateFilm, Film, FilmPatch, FilmSearchHit, FilmSort, FilmSuggestion, SortOrder, SuggestField,
+};
 
+/// Films in a Postgres database migrated with
+/// [`POSTGRES_MIGRATOR`](crate::migrations::POSTGRES_MIGRATOR).
+///
+/// The director and title filters fold case with `lower()`, which matches
+/// [`ListFilms::matches`] in a UTF-8 database with a UTF-8 `LC_CTYPE`; other
+/// locales only fold ASCII letters.
 pub struct PostgresFilmRepository {
     pool: sqlx::PgPool,
 }
@@ -11,56 +27,280 @@ impl PostgresFilmRepository {
     }
 }
 
+impl FilmSql for sqlx::Postgres {
+    const BYTEWISE: &'static str = r#" COLLATE "C""#;
+    const FIRST_PARAMETER: &'static str = "$1";
+
+    fn push_text_match<'a>(
+        builder: &mut sqlx::QueryBuilder<'a, Self>,
+        column: &str,
+        text: &'a TextMatch,
+    ) {
+        match text {
+            TextMatch::Contains(text) => builder
+                .push(format!("(strpos(lower({column}), lower("))
+                .push_bind(text)
+                .push(")) > 0)"),
+            TextMatch::Equals(text) => builder
+                .push(format!("(lower({column}) = lower("))
+                .push_bind(text)
+                .push("))"),
+        };
+    }
+
+    fn push_created_at<'a>(
+        builder: &mut sqlx::QueryBuilder<'a, Self>,
+        created_at: &'a DateTime<Utc>,
+    ) {
+        builder.push_bind(*created_at);
+    }
+}
+
 #[async_trait::async_trait]
 impl FilmRepository for PostgresFilmRepository {
-    async fn get_films(&self) -> FilmResult<Vec<Film>> {
-        sqlx::query_as::<_, Film>(
-            r#"SELECT id, title, director, year, poster, created_at, updated_at FROM films"#,
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| e.to_string())
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        let mut builder = sqlx::QueryBuilder::<sqlx::Postgres>::new(
+            r#"SELECT id, title, director, year, poster, created_at, updated_at, version FROM films WHERE TRUE"#,
+        );
+
+        if let Some(director) = &query.director {
+            builder
+                .push(" AND lower(director) = lower(")
+                .push_bind(director)
+                .push(")");
+        }
+        if let Some(title) = &query.title {
+            builder
+                .push(" AND strpos(lower(title), lower(")
+                .push_bind(title)
+                .push(")) > 0");
+        }
+        if let Some(year_from) = query.year_from {
+            builder.push(" AND year >= ").push_bind(year_from as i32);
+        }
+        if let Some(year_to) = query.year_to {
+            builder.push(" AND year <= ").push_bind(year_to as i32);
+        }
+
+        if let Some(filter) = &query.filter {
+            builder.push(" AND ");
+            push_filter(&mut builder, filter);
+        }
+
+        if let Some(after) = &query.after {
+            push_keyset_condition(&mut builder, after);
+        }
+
+        // byte-wise collation and NULLS placement mirror `ListFilms::compare`,
+        // and match the (column, id) indexes so keyset pages are index scans
+        let column = match query.sort {
+            FilmSort::CreatedAt => "created_at",
+            FilmSort::Title => r#"title COLLATE "C""#,
+            FilmSort::Year => "year",
+            FilmSort::Director => r#"director COLLATE "C""#,
+        };
+        let direction = match query.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        let nulls = match (query.sort, query.order) {
+            (FilmSort::CreatedAt, SortOrder::Asc) => " NULLS FIRST",
+            (FilmSort::CreatedAt, SortOrder::Desc) => " NULLS LAST",
+            _ => "",
+        };
+        builder.push(format!(
+            " ORDER BY {column} {direction}{nulls}, id {direction}"
+        ));
+
+        builder
+            .push(" LIMIT ")
+            .push_bind(query.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(query.offset as i64);
+
+        builder
+            .build_query_as::<Film>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(FilmError::from)
     }
 
     async fn get_film(&self, film_id: &uuid::Uuid) -> FilmResult<Film> {
         sqlx::query_as(
-            r#"SELECT id, title, director, year, poster, created_at, updated_at FROM films WHERE id = $1"#,
+            r#"SELECT id, title, director, year, poster, created_at, updated_at, version FROM films WHERE id = $1"#,
         ).bind(film_id)
-        .fetch_one(&self.pool).await.map_err(|e| e.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(FilmError::NotFound(*film_id))
+    }
+
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        let (count, version_sum, last_modified) =
+            sqlx::query_as::<_, (i64, i64, Option<chrono::DateTime<chrono::Utc>>)>(
+                r#"SELECT count(*), coalesce(sum(version), 0)::bigint, max(greatest(created_at, updated_at)) FROM films"#,
+            )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(CollectionStamp {
+            count,
+            version_sum,
+            last_modified,
+        })
     }
 
     async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
         sqlx::query_as::<_, Film>(
-            r#"INSERT INTO films (title, director, year, poster) VALUES ($1, $2, $3, $4) RETURNING id, title, director, year, poster, created_at, updated_at"#,
+            r#"INSERT INTO films (title, director, year, poster) VALUES ($1, $2, $3, $4) RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
         )
         .bind(&create_film.title)
         .bind(&create_film.director)
-        .bind(create_film.year as i16)
+        .bind(year_to_smallint(create_film.year)?)
         .bind(&create_film.poster)
         .fetch_one(&self.pool)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(FilmError::from)
     }
 
-    async fn update_film(&self, film: &Film) -> FilmResult<Film> {
-        sqlx::query_as::<_, Film>(
-            r#"UPDATE films SET title = $2, director = $3, year = $4, poster = $5 WHERE id = $1 RETURNING id, title, director, year, poster, created_at, updated_at"#,
+    // `updated_at` is stamped, and `created_at` kept, by the
+    // `films_maintain_timestamps` trigger on every UPDATE
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        // the update only applies to the version just read, so that read is
+        // exactly what it replaced; a concurrent write makes it read again
+        loop {
+            let previous = self.get_film(&film.id).await?;
+            check_version(&previous, if_version)?;
+
+            let updated = sqlx::query_as::<_, Film>(
+                r#"UPDATE films SET title = $2, director = $3, year = $4, poster = $5, version = version + 1 WHERE id = $1 AND version = $6 RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
+            )
+            .bind(film.id)
+            .bind(&film.title)
+            .bind(&film.director)
+            .bind(year_to_smallint(film.year)?)
+            .bind(&film.poster)
+            .bind(previous.version)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            if let Some(film) = updated {
+                return Ok(FilmUpdate { film, previous });
+            }
+        }
+    }
+
+    async fn patch_film(
+        &self,
+        film_id: &uuid::Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        // read first for the same reason as in `update_film`
+        loop {
+            let previous = self.get_film(film_id).await?;
+            check_version(&previous, if_version)?;
+            if patch.is_empty() {
+                return Ok(FilmUpdate {
+                    film: previous.clone(),
+                    previous,
+                });
+            }
+
+            let mut builder = sqlx::QueryBuilder::<sqlx::Postgres>::new("UPDATE films SET ");
+            let mut columns = builder.separated(", ");
+            if let Some(title) = &patch.title {
+                columns
+                    .push("title = ")
+                    .push_bind_unseparated(title.clone().unwrap_or_default());
+            }
+            if let Some(director) = &patch.director {
+                columns
+                    .push("director = ")
+                    .push_bind_unseparated(director.clone().unwrap_or_default());
+            }
+            if let Some(year) = patch.year {
+                columns
+                    .push("year = ")
+                    .push_bind_unseparated(year_to_smallint(year.unwrap_or_default())?);
+            }
+            if let Some(poster) = &patch.poster {
+                columns
+                    .push("poster = ")
+                    .push_bind_unseparated(poster.clone().unwrap_or_default());
+            }
+            columns.push("version = version + 1");
+
+            builder.push(" WHERE id = ").push_bind(film_id);
+            builder.push(" AND version = ").push_bind(previous.version);
+            builder.push(
+                " RETURNING id, title, director, year, poster, created_at, updated_at, version",
+            );
+
+            let patched = builder
+                .build_query_as::<Film>()
+                .fetch_optional(&self.pool)
+                .await?;
+
+            if let Some(film) = patched {
+                return Ok(FilmUpdate { film, previous });
+            }
+        }
+    }
+
+    async fn delete_film(&self, film_id: &uuid::Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        let deleted = sqlx::query_as::<_, Film>(
+            r#"DELETE FROM films WHERE id = $1 AND ($2::integer IS NULL OR version = $2) RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
         )
-        .bind(film.id)
-        .bind(&film.title)
-        .bind(&film.director)
-        .bind(film.year as i16)
-        .bind(&film.poster)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| e.to_string())
+        .bind(film_id)
+        .bind(if_version)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match deleted {
+            Some(film) => Ok(film),
+            None => Err(write_failure(&self.pool, film_id, if_version).await),
+        }
     }
 
-    async fn delete_film(&self, film_id: &uuid::Uuid) -> FilmResult<uuid::Uuid> {
-        sqlx::query_scalar::<_, uuid::Uuid>(r#"DELETE FROM films WHERE id = $1 RETURNING id"#)
-            .bind(film_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| e.to_string())
+    // the terms are bare letters and digits, so prefix-matching each of them
+    // cannot produce tsquery syntax; `search` is a generated column weighting
+    // the unaccented title A and director B, see the add_film_search migration
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        let tsquery = query
+            .terms
+            .iter()
+            .map(|term| format!("{term}:*"))
+            .collect::<Vec<_>>()
+            .join(" & ");
+
+        let rows = sqlx::query_as::<_, ScoredFilm>(
+            r#"SELECT id, title, director, year, poster, created_at, updated_at, version, ts_rank(search, query) AS score FROM films, to_tsquery('simple', $1) query WHERE search @@ query ORDER BY score DESC, title COLLATE "C", id LIMIT $2 OFFSET $3"#,
+        )
+        .bind(tsquery)
+        .bind(query.limit as i64)
+        .bind(query.offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(FilmSearchHit::from).collect())
+    }
+
+    // lower(films_unaccent()) folds the values the way `SuggestFilms` folds
+    // the prefix, and is what the trigram indexes of the
+    // add_film_suggest_indexes migration cover
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        let column = match query.field {
+            SuggestField::Title => "title",
+            SuggestField::Director => "director",
+        };
+        let rows = sqlx::query_as::<_, (String, i64)>(&format!(
+            r#"SELECT {column}, count(*) FROM films WHERE lower(films_unaccent({column})) LIKE $1 ESCAPE '\' GROUP BY {column} ORDER BY count(*) DESC, {column} COLLATE "C" LIMIT $2"#,
+        ))
+        .bind(query.like_pattern())
+        .bind(query.limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(suggestion).collect())
     }
 }
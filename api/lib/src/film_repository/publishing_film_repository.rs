@@ -0,0 +1,166 @@
+use super::{
+    CollectionStamp, FilmEvents, FilmRepository, FilmResult, FilmUpdate, ListFilms, SearchFilms,
+    SuggestFilms,
+};
+use shared::models::{CreateFilm, Film, FilmChangeKind, FilmPatch, FilmSearchHit, FilmSuggestion};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Any [`FilmRepository`] publishing each of its successful writes to
+/// [`FilmEvents`], whichever store is behind it.
+///
+/// Writes are published as soon as they succeed; the same change arriving
+/// later from [`FilmEvents::follow`] isn't published twice. Updates carry
+/// the film they replaced and deletions the film removed, both as the inner
+/// repository reports them; patches that change nothing aren't published.
+pub struct PublishingFilmRepository<R> {
+    inner: R,
+    events: Arc<FilmEvents>,
+}
+
+impl<R: FilmRepository> PublishingFilmRepository<R> {
+    pub fn new(inner: R, events: Arc<FilmEvents>) -> Self {
+        Self { inner, events }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn publish_update(&self, update: &FilmUpdate) {
+        if update.film.version != update.previous.version {
+            self.events.publish(
+                FilmChangeKind::Updated,
+                update.film.clone(),
+                Some(update.previous.clone()),
+            );
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: FilmRepository> FilmRepository for PublishingFilmRepository<R> {
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        self.inner.get_films(query).await
+    }
+
+    async fn get_film(&self, id: &Uuid) -> FilmResult<Film> {
+        self.inner.get_film(id).await
+    }
+
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        self.inner.collection_stamp().await
+    }
+
+    async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
+        let film = self.inner.create_film(create_film).await?;
+        self.events
+            .publish(FilmChangeKind::Created, film.clone(), None);
+        Ok(film)
+    }
+
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        let update = self.inner.update_film(film, if_version).await?;
+        self.publish_update(&update);
+        Ok(update)
+    }
+
+    async fn patch_film(
+        &self,
+        id: &Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        let update = self.inner.patch_film(id, patch, if_version).await?;
+        self.publish_update(&update);
+        Ok(update)
+    }
+
+    async fn delete_film(&self, id: &Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        let film = self.inner.delete_film(id, if_version).await?;
+        self.events
+            .publish(FilmChangeKind::Deleted, film.clone(), None);
+        Ok(film)
+    }
+
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        self.inner.search_films(query).await
+    }
+
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        self.inner.suggest_films(query).await
+    }
+
+    fn events(&self) -> Option<Arc<FilmEvents>> {
+        Some(self.events.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film_repository::{FilmError, MemoryFilmRepository};
+    use shared::models::EventId;
+
+    fn publishing_memory() -> PublishingFilmRepository<MemoryFilmRepository> {
+        PublishingFilmRepository::new(MemoryFilmRepository::new(), Arc::new(FilmEvents::new(10)))
+    }
+
+    fn create_film() -> CreateFilm {
+        CreateFilm {
+            title: "Heat".to_string(),
+            director: "Michael Mann".to_string(),
+            year: 1995,
+            poster: String::new(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn successful_writes_are_published_in_order() {
+        let repo = publishing_memory();
+        let film = repo.create_film(&create_film()).await.unwrap();
+        let updated = repo.update_film(&film, None).await.unwrap().film;
+        let patch = FilmPatch {
+            title: Some(Some("Thief".to_string())),
+            ..Default::default()
+        };
+        let patched = repo.patch_film(&film.id, &patch, None).await.unwrap().film;
+        repo.patch_film(&film.id, &FilmPatch::default(), None)
+            .await
+            .unwrap();
+        repo.delete_film(&film.id, None).await.unwrap();
+
+        let events = repo.events().unwrap();
+        let first = EventId {
+            seq: 0,
+            ..events.last_id()
+        };
+        let events = events.subscribe(Some(first)).replay;
+        let published = events
+            .into_iter()
+            .map(|event| (event.id.seq, event.kind, event.film, event.previous))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            published,
+            [
+                (1, FilmChangeKind::Created, film.clone(), None),
+                (2, FilmChangeKind::Updated, updated.clone(), Some(film)),
+                (3, FilmChangeKind::Updated, patched.clone(), Some(updated)),
+                (4, FilmChangeKind::Deleted, patched, None),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn failed_writes_and_missing_films_are_not_published() {
+        let repo = publishing_memory();
+        let film = repo.create_film(&create_film()).await.unwrap();
+
+        let stale = repo.update_film(&film, Some(film.version + 1)).await;
+        assert!(matches!(stale, Err(FilmError::VersionMismatch(_))));
+        let missing = repo.delete_film(&Uuid::new_v4(), None).await;
+        assert!(matches!(missing, Err(FilmError::NotFound(_))));
+
+        assert_eq!(repo.events().unwrap().last_id().seq, 1);
+    }
+}
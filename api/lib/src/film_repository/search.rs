@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+use shared::models::{Film, FilmSearchHit, FilmSearchQuery};
+use shared::validation::ValidationErrors;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use uuid::Uuid;
+
+use super::{FilmError, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+/// Longest accepted search, in characters.
+pub const MAX_SEARCH_CHARS: usize = 200;
+/// Most words a search may have.
+pub const MAX_SEARCH_TERMS: usize = 8;
+
+/// Weight of a match in the title, as Postgres ranks its `A` labelled words.
+pub const TITLE_WEIGHT: f32 = 1.0;
+/// Weight of a match in the director, as Postgres ranks its `B` labelled words.
+pub const DIRECTOR_WEIGHT: f32 = 0.4;
+
+/// Validated search parameters handed to
+/// [`super::FilmRepository::search_films`].
+///
+/// A film matches when each term is a prefix of some word of its title or
+/// director. Words are [`terms`] of the text, so matching ignores case,
+/// accents and punctuation. Backends rank matches their own way, but title
+/// matches always outrank director ones; ties are broken by title, then id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchFilms {
+    pub terms: Vec<String>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl SearchFilms {
+    pub fn from_query(query: &FilmSearchQuery) -> Result<Self, FilmError> {
+        let mut errors = ValidationErrors::new();
+
+        let terms = terms(&query.q);
+        if terms.is_empty() {
+            errors.add("q", "required", "must contain a word to search for");
+        }
+        if query.q.chars().count() > MAX_SEARCH_CHARS {
+            errors.add(
+                "q",
+                "length",
+                format!("must be at most {} characters", MAX_SEARCH_CHARS),
+            );
+        }
+        if terms.len() > MAX_SEARCH_TERMS {
+            errors.add(
+                "q",
+                "length",
+                format!("must have at most {} words", MAX_SEARCH_TERMS),
+            );
+        }
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            errors.add(
+                "limit",
+                "range",
+                format!("must be between 1 and {}", MAX_PAGE_SIZE),
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(FilmError::Validation(errors));
+        }
+        Ok(Self {
+            terms,
+            limit,
+            offset: query.offset.unwrap_or(0),
+        })
+    }
+}
+
+/// `text` lowercased and with accents stripped.
+pub fn fold(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// The words of `text`: runs of letters and digits, [`fold`]ed.
+pub fn terms(text: &str) -> Vec<String> {
+    fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Inverted index of film titles and directors, answering searches for
+/// [`super::MemoryFilmRepository`].
+///
+/// Words are kept in order, so the words a term is a prefix of form one
+/// contiguous range.
+#[derive(Debug, Default)]
+pub(super) struct SearchIndex {
+    /// Weight of the best field each word occurs in, by film.
+    words: BTreeMap<String, HashMap<Uuid, f32>>,
+}
+
+impl SearchIndex {
+    pub fn insert(&mut self, film: &Film) {
+        for (word, weight) in weighted_words(film) {
+            let best = self
+                .words
+                .entry(word)
+                .or_default()
+                .entry(film.id)
+                .or_default();
+            *best = best.max(weight);
+        }
+    }
+
+    pub fn remove(&mut self, film: &Film) {
+        for (word, _) in weighted_words(film) {
+            if let Some(films) = self.words.get_mut(&word) {
+                films.remove(&film.id);
+                if films.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Score of every film matching all `terms`: the sum, over the terms,
+    /// of the best weight among the words each one starts.
+    pub fn search(&self, terms: &[String]) -> HashMap<Uuid, f32> {
+        let mut scores: Option<HashMap<Uuid, f32>> = None;
+        for term in terms {
+            let mut matches = HashMap::<Uuid, f32>::new();
+            let words = self
+                .words
+                .range::<str, _>((Bound::Included(term.as_str()), Bound::Unbounded))
+                .take_while(|(word, _)| word.starts_with(term.as_str()));
+            for (_, films) in words {
+                for (id, weight) in films {
+                    let best = matches.entry(*id).or_default();
+                    *best = best.max(*weight);
+                }
+            }
+
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| matches.get(&id).map(|weight| (id, score + weight)))
+                    .collect(),
+            });
+        }
+        scores.unwrap_or_default()
+    }
+}
+
+/// A search result row of the SQL repositories.
+#[derive(sqlx::FromRow)]
+pub(super) struct ScoredFilm {
+    #[sqlx(flatten)]
+    film: Film,
+    score: f32,
+}
+
+impl From<ScoredFilm> for FilmSearchHit {
+    fn from(row: ScoredFilm) -> Self {
+        Self {
+            film: row.film,
+            score: row.score,
+        }
+    }
+}
+
+fn weighted_words(film: &Film) -> impl Iterator<Item = (String, f32)> {
+    let title = terms(&film.title)
+        .into_iter()
+        .map(|word| (word, TITLE_WEIGHT));
+    let director = terms(&film.director)
+        .into_iter()
+        .map(|word| (word, DIRECTOR_WEIGHT));
+    title.chain(director)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn film(title: &str, director: &str) -> Film {
+        Film {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            director: director.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn search(q: &str) -> Result<SearchFilms, FilmError> {
+        SearchFilms::from_query(&FilmSearchQuery {
+            q: q.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn terms_ignore_case_accents_and_punctuation() {
+        assert_eq!(
+            terms("Amélie — Jean-Pierre JEUNET, 2001"),
+            ["amelie", "jean", "pierre", "jeunet", "2001"]
+        );
+        assert!(terms(" -- ").is_empty());
+    }
+
+    #[test]
+    fn searches_need_a_word_and_a_sane_size() {
+        assert_eq!(search("Heat").unwrap().terms, ["heat"]);
+        for q in ["", "?!", &"word ".repeat(MAX_SEARCH_TERMS + 1)] {
+            assert!(matches!(search(q), Err(FilmError::Validation(_))), "{q:?}");
+        }
+    }
+
+    #[test]
+    fn every_term_must_start_a_word() {
+        let mut index = SearchIndex::default();
+        let amelie = film("Le Fabuleux Destin d'Amélie Poulain", "Jean-Pierre Jeunet");
+        let alien = film("Alien: Resurrection", "Jean-Pierre Jeunet");
+        index.insert(&amelie);
+        index.insert(&alien);
+
+        let found = index.search(&terms("AMEL jean"));
+        assert_eq!(found.keys().collect::<Vec<_>>(), [&amelie.id]);
+        assert_eq!(index.search(&terms("jeunet")).len(), 2);
+        assert!(index.search(&terms("melie")).is_empty());
+
+        index.remove(&amelie);
+        assert!(index.search(&terms("amelie")).is_empty());
+        assert_eq!(index.search(&terms("jeunet")).len(), 1);
+    }
+
+    #[test]
+    fn title_matches_outrank_director_matches() {
+        let mut index = SearchIndex::default();
+        let by_title = film("Mann Hunt", "Someone");
+        let by_director = film("Heat", "Michael Mann");
+        index.insert(&by_title);
+        index.insert(&by_director);
+
+        let scores = index.search(&terms("mann"));
+        assert_eq!(scores[&by_title.id], TITLE_WEIGHT);
+        assert_eq!(scores[&by_director.id], DIRECTOR_WEIGHT);
+    }
+}
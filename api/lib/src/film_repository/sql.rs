@@ -0,0 +1,167 @@
+//! Queries shared by [`PostgresFilmRepository`](super::PostgresFilmRepository)
+//! and [`SqliteFilmRepository`](super::SqliteFilmRepository), written once
+//! over [`FilmSql`] for what the two databases do differently.
+
+use chrono::{DateTime, Utc};
+use shared::filter::{Filter, TextMatch};
+use shared::models::SortOrder;
+use sqlx::database::HasArguments;
+use sqlx::{Encode, Executor, FromRow, IntoArguments, Pool, QueryBuilder, Type};
+use uuid::Uuid;
+
+use super::{FilmCursor, FilmError, SortKey};
+
+/// What a database does its own way in the film queries.
+pub(super) trait FilmSql: sqlx::Database {
+    /// Appended to text columns and values so they compare byte-wise, as
+    /// [`ListFilms::compare`](super::ListFilms::compare) does.
+    const BYTEWISE: &'static str;
+    /// Placeholder of the first value bound to a query.
+    const FIRST_PARAMETER: &'static str;
+
+    /// Appends `column` matching `text` whatever its case, as
+    /// [`ListFilms::matches`](super::ListFilms::matches) folds it.
+    fn push_text_match<'a>(builder: &mut QueryBuilder<'a, Self>, column: &str, text: &'a TextMatch);
+
+    /// Binds `created_at` as the column stores it.
+    fn push_created_at<'a>(builder: &mut QueryBuilder<'a, Self>, created_at: &'a DateTime<Utc>);
+}
+
+/// Appends `filter` as a parenthesised condition. Columns and operators
+/// come from the parsed filter's variants and every value is bound, so no
+/// filter text ends up in the SQL.
+pub(super) fn push_filter<'a, DB>(builder: &mut QueryBuilder<'a, DB>, filter: &'a Filter)
+where
+    DB: FilmSql,
+    i32: Encode<'a, DB> + Type<DB>,
+{
+    match filter {
+        Filter::And(filters) | Filter::Or(filters) => {
+            let join = match filter {
+                Filter::And(_) => " AND ",
+                _ => " OR ",
+            };
+            builder.push("(");
+            for (i, filter) in filters.iter().enumerate() {
+                if i > 0 {
+                    builder.push(join);
+                }
+                push_filter(builder, filter);
+            }
+            builder.push(")");
+        }
+        Filter::Not(filter) => {
+            builder.push("(NOT ");
+            push_filter(builder, filter);
+            builder.push(")");
+        }
+        Filter::Title(text) => DB::push_text_match(builder, "title", text),
+        Filter::Director(text) => DB::push_text_match(builder, "director", text),
+        Filter::Year(comparison, year) => {
+            builder
+                .push(format!("(year {} ", comparison.symbol()))
+                .push_bind(*year as i32)
+                .push(")");
+        }
+    }
+}
+
+/// Restricts the query to rows strictly after `after` using a row value
+/// comparison, which both databases answer straight from a (column, id)
+/// index.
+pub(super) fn push_keyset_condition<'a, DB>(
+    builder: &mut QueryBuilder<'a, DB>,
+    after: &'a FilmCursor,
+) where
+    DB: FilmSql,
+    i32: Encode<'a, DB> + Type<DB>,
+    Uuid: Encode<'a, DB> + Type<DB>,
+    &'a String: Encode<'a, DB> + Type<DB>,
+{
+    let cmp = match after.order {
+        SortOrder::Asc => ">",
+        SortOrder::Desc => "<",
+    };
+    let bytewise = DB::BYTEWISE;
+
+    match &after.key {
+        SortKey::Title(title) => {
+            builder
+                .push(format!(" AND (title{bytewise}, id) {cmp} ("))
+                .push_bind(title)
+                .push(format!("{bytewise}, "))
+                .push_bind(after.id)
+                .push(")");
+        }
+        SortKey::Director(director) => {
+            builder
+                .push(format!(" AND (director{bytewise}, id) {cmp} ("))
+                .push_bind(director)
+                .push(format!("{bytewise}, "))
+                .push_bind(after.id)
+                .push(")");
+        }
+        SortKey::Year(year) => {
+            builder
+                .push(format!(" AND (year, id) {cmp} ("))
+                .push_bind(*year as i32)
+                .push(", ")
+                .push_bind(after.id)
+                .push(")");
+        }
+        // NULL created_at sorts first, see `ListFilms::compare`
+        SortKey::CreatedAt(Some(created_at)) => {
+            let nulls = match after.order {
+                SortOrder::Asc => "created_at IS NOT NULL AND",
+                SortOrder::Desc => "created_at IS NULL OR",
+            };
+            builder.push(format!(" AND ({nulls} (created_at, id) {cmp} ("));
+            DB::push_created_at(builder, created_at);
+            builder.push(", ").push_bind(after.id).push("))");
+        }
+        SortKey::CreatedAt(None) => {
+            let rest = match after.order {
+                SortOrder::Asc => "created_at IS NOT NULL OR",
+                SortOrder::Desc => "",
+            };
+            builder
+                .push(format!(" AND ({rest} (created_at IS NULL AND id {cmp} "))
+                .push_bind(after.id)
+                .push("))");
+        }
+    }
+}
+
+/// Explains why a conditional write touched no row: either the film is
+/// gone or it no longer has the expected version.
+pub(super) async fn write_failure<DB>(
+    pool: &Pool<DB>,
+    film_id: &Uuid,
+    if_version: Option<i32>,
+) -> FilmError
+where
+    DB: FilmSql,
+    for<'q> Uuid: Encode<'q, DB> + Type<DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    (bool,): for<'r> FromRow<'r, DB::Row>,
+{
+    if if_version.is_none() {
+        return FilmError::NotFound(*film_id);
+    }
+
+    let exists = format!(
+        "SELECT EXISTS (SELECT 1 FROM films WHERE id = {})",
+        DB::FIRST_PARAMETER
+    );
+    let exists = sqlx::query_scalar::<DB, bool>(&exists)
+        .bind(*film_id)
+        .fetch_one(pool)
+        .await;
+
+    match exists {
+        Ok(true) => FilmError::VersionMismatch(*film_id),
+        Ok(false) => FilmError::NotFound(*film_id),
+        Err(e) => e.into(),
+    }
+}
@@ -0,0 +1,354 @@
+use super::search::{ScoredFilm, DIRECTOR_WEIGHT, TITLE_WEIGHT};
+use super::sql::{push_filter, push_keyset_condition, write_failure, FilmSql};
+use super::suggest::suggestion;
+use super::{
+    check_version, year_to_smallint, CollectionStamp, FilmError, FilmRepository, FilmResult,
+    FilmUpdate, ListFilms, SearchFilms, SuggestFilms,
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use shared::filter::TextMatch;
+use shared::models::{
+    CreateFilm, Film, FilmPatch, FilmSearchHit, FilmSort, FilmSuggestion, SortOrder, SuggestField,
+};
+
+/// Films in a SQLite database migrated with
+/// [`SQLITE_MIGRATOR`](crate::migrations::SQLITE_MIGRATOR).
+///
+/// Behaves like [`PostgresFilmRepository`](super::PostgresFilmRepository):
+/// ids and timestamps are generated here rather than by the database, and
+/// stored so that SQLite's byte-wise ordering matches the other
+/// repositories. SQLite's `lower()` only folds ASCII, so the director and
+/// title filters compare copies folded here, as [`ListFilms::matches`] folds
+/// them; suggestions are case-insensitive for ASCII letters only and don't
+/// ignore accents either.
+pub struct SqliteFilmRepository {
+    pool: sqlx::SqlitePool,
+}
+
+impl SqliteFilmRepository {
+    pub fn new(pool: sqlx::SqlitePool) -> SqliteFilmRepository {
+        Self { pool }
+    }
+}
+
+/// Current time at the microsecond precision Postgres stores.
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+/// Fixed-width RFC 3339 text, so the `text` columns sort chronologically.
+pub(crate) fn timestamp(at: &DateTime<Utc>) -> String {
+    at.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+/// Case-folded text of the `title_folded` and `director_folded` columns.
+fn fold(text: &str) -> String {
+    text.to_lowercase()
+}
+
+/// Folds the titles and directors of films written before the folded
+/// columns were kept, see [`crate::migrations::Migrations::complete`].
+pub(crate) async fn fold_films(pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let unfolded = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
+        r#"SELECT id, title, director FROM films WHERE title_folded IS NULL OR director_folded IS NULL"#,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    for (id, title, director) in unfolded {
+        sqlx::query(r#"UPDATE films SET title_folded = ?, director_folded = ? WHERE id = ?"#)
+            .bind(fold(&title))
+            .bind(fold(&director))
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+impl FilmSql for sqlx::Sqlite {
+    // the default BINARY collation already is
+    const BYTEWISE: &'static str = "";
+    const FIRST_PARAMETER: &'static str = "?";
+
+    fn push_text_match<'a>(
+        builder: &mut sqlx::QueryBuilder<'a, Self>,
+        column: &str,
+        text: &'a TextMatch,
+    ) {
+        match text {
+            TextMatch::Contains(text) => builder
+                .push(format!("(instr({column}_folded, "))
+                .push_bind(fold(text))
+                .push(") > 0)"),
+            TextMatch::Equals(text) => builder
+                .push(format!("({column}_folded = "))
+                .push_bind(fold(text))
+                .push(")"),
+        };
+    }
+
+    fn push_created_at<'a>(
+        builder: &mut sqlx::QueryBuilder<'a, Self>,
+        created_at: &'a DateTime<Utc>,
+    ) {
+        builder.push_bind(timestamp(created_at));
+    }
+}
+
+#[async_trait::async_trait]
+impl FilmRepository for SqliteFilmRepository {
+    async fn get_films(&self, query: &ListFilms) -> FilmResult<Vec<Film>> {
+        let mut builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new(
+            r#"SELECT id, title, director, year, poster, created_at, updated_at, version FROM films WHERE TRUE"#,
+        );
+
+        if let Some(director) = &query.director {
+            builder
+                .push(" AND director_folded = ")
+                .push_bind(fold(director));
+        }
+        if let Some(title) = &query.title {
+            builder
+                .push(" AND instr(title_folded, ")
+                .push_bind(fold(title))
+                .push(") > 0");
+        }
+        if let Some(year_from) = query.year_from {
+            builder.push(" AND year >= ").push_bind(year_from as i32);
+        }
+        if let Some(year_to) = query.year_to {
+            builder.push(" AND year <= ").push_bind(year_to as i32);
+        }
+
+        if let Some(filter) = &query.filter {
+            builder.push(" AND ");
+            push_filter(&mut builder, filter);
+        }
+
+        if let Some(after) = &query.after {
+            push_keyset_condition(&mut builder, after);
+        }
+
+        // SQLite's default BINARY collation is already byte-wise and puts
+        // NULLs first when ascending, as `ListFilms::compare` does
+        let column = match query.sort {
+            FilmSort::CreatedAt => "created_at",
+            FilmSort::Title => "title",
+            FilmSort::Year => "year",
+            FilmSort::Director => "director",
+        };
+        let direction = match query.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        builder.push(format!(" ORDER BY {column} {direction}, id {direction}"));
+
+        builder
+            .push(" LIMIT ")
+            .push_bind(query.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(query.offset as i64);
+
+        builder
+            .build_query_as::<Film>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(FilmError::from)
+    }
+
+    async fn get_film(&self, film_id: &uuid::Uuid) -> FilmResult<Film> {
+        sqlx::query_as(
+            r#"SELECT id, title, director, year, poster, created_at, updated_at, version FROM films WHERE id = ?"#,
+        )
+        .bind(film_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(FilmError::NotFound(*film_id))
+    }
+
+    async fn collection_stamp(&self) -> FilmResult<CollectionStamp> {
+        // scalar max() is NULL as soon as one argument is, unlike greatest()
+        let (count, version_sum, last_modified) =
+            sqlx::query_as::<_, (i64, i64, Option<DateTime<Utc>>)>(
+                r#"SELECT count(*), coalesce(sum(version), 0), max(max(created_at, coalesce(updated_at, created_at))) FROM films"#,
+            )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(CollectionStamp {
+            count,
+            version_sum,
+            last_modified,
+        })
+    }
+
+    async fn create_film(&self, create_film: &CreateFilm) -> FilmResult<Film> {
+        sqlx::query_as::<_, Film>(
+            r#"INSERT INTO films (id, title, director, title_folded, director_folded, year, poster, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind(&create_film.title)
+        .bind(&create_film.director)
+        .bind(fold(&create_film.title))
+        .bind(fold(&create_film.director))
+        .bind(year_to_smallint(create_film.year)?)
+        .bind(&create_film.poster)
+        .bind(timestamp(&now()))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(FilmError::from)
+    }
+
+    // `created_at` is kept by the `films_keep_created_at` trigger
+    async fn update_film(&self, film: &Film, if_version: Option<i32>) -> FilmResult<FilmUpdate> {
+        // the update only applies to the version just read, so that read is
+        // exactly what it replaced; a concurrent write makes it read again
+        loop {
+            let previous = self.get_film(&film.id).await?;
+            check_version(&previous, if_version)?;
+
+            let updated = sqlx::query_as::<_, Film>(
+                r#"UPDATE films SET title = ?, director = ?, title_folded = ?, director_folded = ?, year = ?, poster = ?, updated_at = ?, version = version + 1 WHERE id = ? AND version = ? RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
+            )
+            .bind(&film.title)
+            .bind(&film.director)
+            .bind(fold(&film.title))
+            .bind(fold(&film.director))
+            .bind(year_to_smallint(film.year)?)
+            .bind(&film.poster)
+            .bind(timestamp(&now()))
+            .bind(film.id)
+            .bind(previous.version)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            if let Some(film) = updated {
+                return Ok(FilmUpdate { film, previous });
+            }
+        }
+    }
+
+    async fn patch_film(
+        &self,
+        film_id: &uuid::Uuid,
+        patch: &FilmPatch,
+        if_version: Option<i32>,
+    ) -> FilmResult<FilmUpdate> {
+        // read first for the same reason as in `update_film`
+        loop {
+            let previous = self.get_film(film_id).await?;
+            check_version(&previous, if_version)?;
+            if patch.is_empty() {
+                return Ok(FilmUpdate {
+                    film: previous.clone(),
+                    previous,
+                });
+            }
+
+            let mut builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new("UPDATE films SET ");
+            let mut columns = builder.separated(", ");
+            if let Some(title) = &patch.title {
+                let title = title.clone().unwrap_or_default();
+                columns
+                    .push("title_folded = ")
+                    .push_bind_unseparated(fold(&title));
+                columns.push("title = ").push_bind_unseparated(title);
+            }
+            if let Some(director) = &patch.director {
+                let director = director.clone().unwrap_or_default();
+                columns
+                    .push("director_folded = ")
+                    .push_bind_unseparated(fold(&director));
+                columns.push("director = ").push_bind_unseparated(director);
+            }
+            if let Some(year) = patch.year {
+                columns
+                    .push("year = ")
+                    .push_bind_unseparated(year_to_smallint(year.unwrap_or_default())?);
+            }
+            if let Some(poster) = &patch.poster {
+                columns
+                    .push("poster = ")
+                    .push_bind_unseparated(poster.clone().unwrap_or_default());
+            }
+            columns
+                .push("updated_at = ")
+                .push_bind_unseparated(timestamp(&now()));
+            columns.push("version = version + 1");
+
+            builder.push(" WHERE id = ").push_bind(film_id);
+            builder.push(" AND version = ").push_bind(previous.version);
+            builder.push(
+                " RETURNING id, title, director, year, poster, created_at, updated_at, version",
+            );
+
+            let patched = builder
+                .build_query_as::<Film>()
+                .fetch_optional(&self.pool)
+                .await?;
+
+            if let Some(film) = patched {
+                return Ok(FilmUpdate { film, previous });
+            }
+        }
+    }
+
+    async fn delete_film(&self, film_id: &uuid::Uuid, if_version: Option<i32>) -> FilmResult<Film> {
+        let deleted = sqlx::query_as::<_, Film>(
+            r#"DELETE FROM films WHERE id = ? AND (? IS NULL OR version = ?) RETURNING id, title, director, year, poster, created_at, updated_at, version"#,
+        )
+        .bind(film_id)
+        .bind(if_version)
+        .bind(if_version)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match deleted {
+            Some(film) => Ok(film),
+            None => Err(write_failure(&self.pool, film_id, if_version).await),
+        }
+    }
+
+    // the terms are bare letters and digits, so quoting them is enough to
+    // keep them out of the FTS5 query syntax; bm25() is lower for better
+    // matches and its arguments weight the id, title and director columns
+    async fn search_films(&self, query: &SearchFilms) -> FilmResult<Vec<FilmSearchHit>> {
+        let pattern = query
+            .terms
+            .iter()
+            .map(|term| format!("\"{term}\"*"))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+
+        let rows = sqlx::query_as::<_, ScoredFilm>(
+            r#"SELECT films.id, films.title, films.director, year, poster, created_at, updated_at, version, -bm25(films_search, 0.0, ?, ?) AS score FROM films_search JOIN films ON films.id = films_search.id WHERE films_search MATCH ? ORDER BY score DESC, films.title, films.id LIMIT ? OFFSET ?"#,
+        )
+        .bind(TITLE_WEIGHT)
+        .bind(DIRECTOR_WEIGHT)
+        .bind(pattern)
+        .bind(query.limit as i64)
+        .bind(query.offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(FilmSearchHit::from).collect())
+    }
+
+    // lower() and LIKE only fold ASCII letters, and accents aren't stripped
+    async fn suggest_films(&self, query: &SuggestFilms) -> FilmResult<Vec<FilmSuggestion>> {
+        let column = match query.field {
+            SuggestField::Title => "title",
+            SuggestField::Director => "director",
+        };
+        let rows = sqlx::query_as::<_, (String, i64)>(&format!(
+            r#"SELECT {column}, count(*) FROM films WHERE lower({column}) LIKE ? ESCAPE '\' GROUP BY {column} ORDER BY count(*) DESC, {column} LIMIT ?"#,
+        ))
+        .bind(query.like_pattern())
+        .bind(query.limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(suggestion).collect())
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use shared::models::{Film, FilmSuggestQuery, FilmSuggestion, SuggestField};
+use shared::validation::ValidationErrors;
+
+use super::search::fold;
+use super::FilmError;
+
+/// Suggestions returned when the query doesn't ask for a number.
+pub const DEFAULT_SUGGESTIONS: u32 = 10;
+/// Most suggestions a query may ask for.
+pub const MAX_SUGGESTIONS: u32 = 50;
+/// Longest accepted prefix, in characters.
+pub const MAX_PREFIX_CHARS: usize = 100;
+
+/// Validated suggestion parameters handed to
+/// [`super::FilmRepository::suggest_films`].
+///
+/// Suggests the distinct values of `field` whose [`fold`]ed form starts
+/// with `prefix`, most used first, then in byte-wise order. Folding the
+/// stored values is up to the backend, see [`super::SqliteFilmRepository`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SuggestFilms {
+    pub field: SuggestField,
+    /// Already folded.
+    pub prefix: String,
+    pub limit: u32,
+}
+
+impl SuggestFilms {
+    pub fn from_query(query: &FilmSuggestQuery) -> Result<Self, FilmError> {
+        let mut errors = ValidationErrors::new();
+
+        if query.prefix.trim().is_empty() {
+            errors.add("prefix", "required", "must not be blank");
+        }
+        if query.prefix.chars().count() > MAX_PREFIX_CHARS {
+            errors.add(
+                "prefix",
+                "length",
+                format!("must be at most {} characters", MAX_PREFIX_CHARS),
+            );
+        }
+
+        let limit = query.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+        if limit == 0 || limit > MAX_SUGGESTIONS {
+            errors.add(
+                "limit",
+                "range",
+                format!("must be between 1 and {}", MAX_SUGGESTIONS),
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(FilmError::Validation(errors));
+        }
+        Ok(Self {
+            field: query.field,
+            prefix: fold(&query.prefix),
+            limit,
+        })
+    }
+
+    /// `prefix` as a `LIKE` pattern, escaped with `\`.
+    pub(super) fn like_pattern(&self) -> String {
+        let mut pattern = String::with_capacity(self.prefix.len() + 1);
+        for c in self.prefix.chars() {
+            if matches!(c, '\\' | '%' | '_') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('%');
+        pattern
+    }
+}
+
+/// A `(value, count)` row of the SQL repositories.
+pub(super) fn suggestion((value, count): (String, i64)) -> FilmSuggestion {
+    FilmSuggestion {
+        value,
+        count: count as u32,
+    }
+}
+
+/// How many films use each title and director, answering suggestions for
+/// [`super::MemoryFilmRepository`].
+///
+/// Values are kept in order of their folded form, so the values a prefix
+/// completes form one contiguous range.
+#[derive(Debug, Default)]
+pub(super) struct SuggestIndex {
+    title: Counts,
+    director: Counts,
+}
+
+/// Films using each value, by folded value and then value.
+type Counts = BTreeMap<(String, String), u32>;
+
+impl SuggestIndex {
+    pub fn insert(&mut self, film: &Film) {
+        for (field, value) in self.values(film) {
+            *field.entry((fold(value), value.clone())).or_default() += 1;
+        }
+    }
+
+    pub fn remove(&mut self, film: &Film) {
+        for (field, value) in self.values(film) {
+            let key = (fold(value), value.clone());
+            if let Some(count) = field.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    field.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn suggest(&self, query: &SuggestFilms) -> Vec<FilmSuggestion> {
+        let field = match query.field {
+            SuggestField::Title => &self.title,
+            SuggestField::Director => &self.director,
+        };
+        let start = (query.prefix.clone(), String::new());
+        let mut suggestions = field
+            .range((Bound::Included(start), Bound::Unbounded))
+            .take_while(|((folded, _), _)| folded.starts_with(&query.prefix))
+            .map(|((_, value), count)| FilmSuggestion {
+                value: value.clone(),
+                count: *count,
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        suggestions.truncate(query.limit as usize);
+        suggestions
+    }
+
+    fn values<'a>(&'a mut self, film: &'a Film) -> [(&'a mut Counts, &'a String); 2] {
+        [
+            (&mut self.title, &film.title),
+            (&mut self.director, &film.director),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn film(title: &str, director: &str) -> Film {
+        Film {
+            title: title.to_string(),
+            director: director.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn suggest(field: SuggestField, prefix: &str) -> SuggestFilms {
+        SuggestFilms::from_query(&FilmSuggestQuery {
+            field,
+            prefix: prefix.to_string(),
+            limit: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn prefixes_are_folded_and_escaped() {
+        let query = suggest(SuggestField::Title, "Amé_100%");
+        assert_eq!(query.prefix, "ame_100%");
+        assert_eq!(query.like_pattern(), r"ame\_100\%%");
+
+        let blank = SuggestFilms::from_query(&FilmSuggestQuery {
+            field: SuggestField::Title,
+            prefix: " ".to_string(),
+            limit: None,
+        });
+        assert!(matches!(blank, Err(FilmError::Validation(_))));
+    }
+
+    #[test]
+    fn suggestions_are_distinct_values_most_used_first() {
+        let mut index = SuggestIndex::default();
+        let films = [
+            film("Heat", "Michael Mann"),
+            film("Thief", "Michael Mann"),
+            film("Mishima", "Paul Schrader"),
+            film("Memento", "Christopher Nolan"),
+            film("Ali", "Michaël Mann"),
+        ];
+        films.iter().for_each(|film| index.insert(film));
+
+        let found = index.suggest(&suggest(SuggestField::Director, "MICHAEL"));
+        assert_eq!(
+            found,
+            [
+                FilmSuggestion {
+                    value: "Michael Mann".to_string(),
+                    count: 2,
+                },
+                FilmSuggestion {
+                    value: "Michaël Mann".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+
+        index.remove(&films[0]);
+        index.remove(&films[1]);
+        let found = index.suggest(&suggest(SuggestField::Director, "mich"));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, "Michaël Mann");
+        assert_eq!(index.suggest(&suggest(SuggestField::Title, "m")).len(), 2);
+    }
+}
@@ -1,74 +1,402 @@
+use std::time::SystemTime;
+
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ETag, EntityTag, Header, HttpDate, IfMatch, IfModifiedSince,
+    IfNoneMatch, LastModified, IF_MATCH, IF_NONE_MATCH,
+};
 use actix_web::web::{self, ServiceConfig};
-use actix_web::HttpResponse;
-use shared::models::{CreateFilm, Film};
+use actix_web::Scope;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::{DateTime, SubsecRound, Utc};
+use shared::models::{
+    CreateFilm, Film, FilmPage, FilmPatch, FilmQuery, FilmSearchPage, FilmSearchQuery,
+    FilmSuggestQuery, FilmSuggestions,
+};
+use shared::validation::{Validate, ValidationErrors};
 use uuid::Uuid;
 
-use crate::film_repository::FilmRepository;
+use crate::auth::FilmWriter;
+use crate::film_repository::{
+    CollectionStamp, CursorCodec, FilmCursor, FilmError, FilmRepository, FilmResult, ListFilms,
+    SearchFilms, SuggestFilms,
+};
+use crate::problem;
+use crate::settings::Features;
+
+/// `Cache-Control` sent with film reads, registered as app data to override
+/// the default of `no-cache` on both routes.
+///
+/// Every read carries a validator, so `no-cache` still lets clients and CDNs
+/// keep a copy and revalidate it with a cheap 304.
+#[derive(Debug, Clone)]
+pub struct FilmCachePolicy {
+    /// `GET /v1/films`
+    pub list: CacheControl,
+    /// `GET /v1/films/{film_id}`
+    pub item: CacheControl,
+}
+
+impl Default for FilmCachePolicy {
+    fn default() -> Self {
+        Self {
+            list: CacheControl(vec![CacheDirective::NoCache]),
+            item: CacheControl(vec![CacheDirective::NoCache]),
+        }
+    }
+}
+
+/// Default mount point of the film routes, see [`crate::settings::ApiSettings`].
+pub const FILMS_PATH: &str = "/v1/films";
 
+/// Film routes at [`FILMS_PATH`] with every feature enabled.
 pub fn service<R: FilmRepository>(cfg: &mut ServiceConfig) {
-    cfg.service(
-        web::scope("/v1/films")
-            .route("", web::get().to(get_films::<R>))
-            .route("/{film_id}", web::get().to(get_film::<R>))
-            .route("", web::post().to(post_film::<R>))
-            .route("", web::put().to(put_film::<R>))
-            .route("/{film_id}", web::delete().to(delete_film::<R>)),
-    );
+    cfg.service(scope::<R>(FILMS_PATH, &Features::default()));
+}
+
+/// Film routes mounted at `path`; the write routes only when
+/// [`Features::film_writes`] is on, for [`FilmWriter`]s.
+pub fn scope<R: FilmRepository>(path: &str, features: &Features) -> Scope {
+    let scope = web::scope(path)
+        .app_data(problem::json_config())
+        .app_data(problem::path_config())
+        .app_data(problem::query_config())
+        .route("", web::get().to(get_films::<R>))
+        .route("/events", web::get().to(crate::events::film_events))
+        .route("/search", web::get().to(search_films::<R>))
+        .route("/suggest", web::get().to(suggest_films::<R>))
+        .route("/{film_id}", web::get().to(get_film::<R>));
+
+    if !features.film_writes {
+        return scope;
+    }
+    scope
+        .route("", web::post().to(post_film::<R>))
+        .route("/{film_id}", web::put().to(put_film::<R>))
+        .route("/{film_id}", web::patch().to(patch_film::<R>))
+        .route("/{film_id}", web::delete().to(delete_film::<R>))
 }
 
-pub async fn get_films<R: FilmRepository>(repo: web::Data<R>) -> HttpResponse {
+pub async fn get_films<R: FilmRepository>(
+    req: HttpRequest,
+    repo: web::Data<R>,
+    cursor_codec: Option<web::Data<CursorCodec>>,
+    cache_policy: Option<web::Data<FilmCachePolicy>>,
+    query: web::Query<FilmQuery>,
+) -> FilmResult<HttpResponse> {
     tracing::info!("Getting a list of films");
 
-    match repo.get_films().await {
-        Ok(films) => HttpResponse::Ok().json(films),
-        Err(e) => HttpResponse::NotFound().body(format!("Internal server error: {:?}", e)),
+    let codec: &CursorCodec = match &cursor_codec {
+        Some(codec) => codec,
+        None => CursorCodec::global(),
+    };
+    let list = ListFilms::from_query(&query, codec)?;
+    let cache_control = cache_policy
+        .map(|policy| policy.list.clone())
+        .unwrap_or_else(|| FilmCachePolicy::default().list);
+
+    // the validator covers the whole collection, so a revalidation that
+    // still matches is answered without listing anything
+    let etag = collection_etag(&repo.collection_stamp().await?);
+    if none_match(&req, &etag) == Some(false) {
+        return Ok(not_modified(etag, cache_control, None));
     }
+
+    // fetch one extra film to find out whether there is a next page
+    let mut films = repo
+        .get_films(&ListFilms {
+            limit: list.limit + 1,
+            ..list.clone()
+        })
+        .await?;
+
+    let has_more = films.len() > list.limit as usize;
+    films.truncate(list.limit as usize);
+
+    let next = films
+        .last()
+        .filter(|_| has_more)
+        .map(|last| codec.encode(&FilmCursor::after(last, list.sort, list.order)));
+    let next_offset = (has_more && list.after.is_none()).then_some(list.offset + list.limit);
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(etag))
+        .insert_header(cache_control)
+        .json(FilmPage {
+            items: films,
+            limit: list.limit,
+            offset: list.offset,
+            next_offset,
+            next,
+        }))
 }
 
+/// Films matching `q`, best first; see [`SearchFilms`].
+pub async fn search_films<R: FilmRepository>(
+    repo: web::Data<R>,
+    query: web::Query<FilmSearchQuery>,
+) -> FilmResult<HttpResponse> {
+    tracing::info!("Searching films");
+
+    let search = SearchFilms::from_query(&query)?;
+
+    // fetch one extra hit to find out whether there is a next page
+    let mut hits = repo
+        .search_films(&SearchFilms {
+            limit: search.limit + 1,
+            ..search.clone()
+        })
+        .await?;
+
+    let has_more = hits.len() > search.limit as usize;
+    hits.truncate(search.limit as usize);
+
+    Ok(HttpResponse::Ok().json(FilmSearchPage {
+        items: hits,
+        limit: search.limit,
+        offset: search.offset,
+        next_offset: has_more.then_some(search.offset + search.limit),
+    }))
+}
+
+/// Titles or directors already in use that start with `prefix`, so forms
+/// can offer an existing spelling rather than a near duplicate of it.
+pub async fn suggest_films<R: FilmRepository>(
+    repo: web::Data<R>,
+    query: web::Query<FilmSuggestQuery>,
+) -> FilmResult<HttpResponse> {
+    tracing::info!("Suggesting film {:?} values", query.field);
+
+    let suggest = SuggestFilms::from_query(&query)?;
+    Ok(HttpResponse::Ok().json(FilmSuggestions {
+        items: repo.suggest_films(&suggest).await?,
+    }))
+}
+
+/// Gets a film, answering `If-None-Match` or else `If-Modified-Since` with a
+/// 304 when the client's copy is still current.
 pub async fn get_film<R: FilmRepository>(
+    req: HttpRequest,
     repo: web::Data<R>,
+    cache_policy: Option<web::Data<FilmCachePolicy>>,
     film_id: web::Path<Uuid>,
-) -> HttpResponse {
+) -> FilmResult<HttpResponse> {
     tracing::info!("Getting a specific film");
 
-    match repo.get_film(&film_id).await {
-        Ok(film) => HttpResponse::Ok().json(film),
-        Err(_) => HttpResponse::NotFound().body(format!("Film with id {} Not found", film_id)),
+    let film = repo.get_film(&film_id).await?;
+    let cache_control = cache_policy
+        .map(|policy| policy.item.clone())
+        .unwrap_or_else(|| FilmCachePolicy::default().item);
+
+    let etag = film_etag(&film);
+    let last_modified = film_last_modified(&film);
+    // If-Modified-Since is only considered without If-None-Match (RFC 9110)
+    let fresh = match none_match(&req, &etag) {
+        Some(matched) => !matched,
+        None => not_modified_since(&req, last_modified),
+    };
+    if fresh {
+        return Ok(not_modified(etag, cache_control, last_modified));
     }
+
+    Ok(film_ok(&film).insert_header(cache_control).json(film))
 }
 
 pub async fn post_film<R: FilmRepository>(
+    _: FilmWriter,
     repo: web::Data<R>,
     film: web::Json<CreateFilm>,
-) -> HttpResponse {
-    match repo.create_film(&film).await {
-        Ok(film) => HttpResponse::Ok().json(film),
-        Err(e) => {
-            HttpResponse::UnprocessableEntity().body(format!("Internal server error: {:?}", e))
-        }
-    }
+) -> FilmResult<HttpResponse> {
+    film.validate()?;
+    let film = repo.create_film(&film).await?;
+    Ok(film_response(film))
 }
 
+/// Replaces the editable fields of a film.
+///
+/// The path id is authoritative; any `id` or timestamps in the body are ignored.
 pub async fn put_film<R: FilmRepository>(
+    _: FilmWriter,
+    req: HttpRequest,
     repo: web::Data<R>,
-    film: web::Json<Film>,
-) -> HttpResponse {
-    match repo.update_film(&film).await {
-        Ok(film) => HttpResponse::Ok().json(film),
-        Err(e) => HttpResponse::NotFound().body(format!("Internal server error: {:?}", e)),
-    }
+    film_id: web::Path<Uuid>,
+    film: web::Json<CreateFilm>,
+) -> FilmResult<HttpResponse> {
+    film.validate()?;
+    let if_version = expected_version(&**repo, &film_id, &req).await?;
+    let film = film.into_inner();
+    let film = Film {
+        id: film_id.into_inner(),
+        title: film.title,
+        director: film.director,
+        year: film.year,
+        poster: film.poster,
+        ..Default::default()
+    };
+    let update = repo.update_film(&film, if_version).await?;
+    Ok(film_response(update.film))
+}
+
+/// Applies a JSON Merge Patch (`application/merge-patch+json`) to a film.
+pub async fn patch_film<R: FilmRepository>(
+    _: FilmWriter,
+    req: HttpRequest,
+    repo: web::Data<R>,
+    film_id: web::Path<Uuid>,
+    patch: web::Json<FilmPatch>,
+) -> FilmResult<HttpResponse> {
+    tracing::info!("Patching a specific film");
+
+    patch.validate()?;
+    let if_version = expected_version(&**repo, &film_id, &req).await?;
+    let update = repo.patch_film(&film_id, &patch, if_version).await?;
+    Ok(film_response(update.film))
 }
 
 pub async fn delete_film<R: FilmRepository>(
+    _: FilmWriter,
+    req: HttpRequest,
     repo: web::Data<R>,
     film_id: web::Path<Uuid>,
-) -> HttpResponse {
+) -> FilmResult<HttpResponse> {
     tracing::info!("Deleting a specific film");
 
-    match repo.delete_film(&film_id).await {
-        Ok(film_id) => HttpResponse::Ok().json(film_id),
-        Err(e) => {
-            HttpResponse::InternalServerError().body(format!("Internal server error: {:?}", e))
+    let if_version = expected_version(&**repo, &film_id, &req).await?;
+    let film = repo.delete_film(&film_id, if_version).await?;
+    Ok(HttpResponse::Ok().json(film.id))
+}
+
+/// Strong entity tag of a film, derived from its version.
+pub fn film_etag(film: &Film) -> EntityTag {
+    EntityTag::new_strong(film.version.to_string())
+}
+
+/// When a film last changed, falling back to its creation time.
+pub fn film_last_modified(film: &Film) -> Option<DateTime<Utc>> {
+    film.updated_at.or(film.created_at)
+}
+
+/// Weak entity tag of the film list, derived from [`CollectionStamp`].
+///
+/// It is weak because pages of the same collection are serialised from
+/// whatever the query selects, not byte-for-byte from the stamp.
+pub fn collection_etag(stamp: &CollectionStamp) -> EntityTag {
+    let last_modified = stamp
+        .last_modified
+        .map(|at| at.timestamp_micros())
+        .unwrap_or_default();
+    EntityTag::new_weak(format!(
+        "{}-{}-{}",
+        stamp.count, stamp.version_sum, last_modified
+    ))
+}
+
+/// 200 response builder carrying the film's validators.
+fn film_ok(film: &Film) -> HttpResponseBuilder {
+    let mut res = HttpResponse::Ok();
+    res.insert_header(ETag(film_etag(film)));
+    if let Some(last_modified) = film_last_modified(film) {
+        res.insert_header(LastModified(http_date(last_modified)));
+    }
+    res
+}
+
+fn film_response(film: Film) -> HttpResponse {
+    film_ok(&film).json(film)
+}
+
+fn not_modified(
+    etag: EntityTag,
+    cache_control: CacheControl,
+    last_modified: Option<DateTime<Utc>>,
+) -> HttpResponse {
+    let mut res = HttpResponse::NotModified();
+    res.insert_header(ETag(etag)).insert_header(cache_control);
+    if let Some(last_modified) = last_modified {
+        res.insert_header(LastModified(http_date(last_modified)));
+    }
+    res.finish()
+}
+
+/// HTTP dates carry whole seconds, so sub-second precision is dropped.
+fn http_date(at: DateTime<Utc>) -> HttpDate {
+    SystemTime::from(at.trunc_subsecs(0)).into()
+}
+
+/// Evaluates `If-None-Match` against `etag` with the weak comparison.
+///
+/// Returns `None` without the header, otherwise whether the request should
+/// proceed, i.e. `Some(false)` when the client's copy is current. An
+/// unparseable header is ignored rather than answered with a 304.
+fn none_match(req: &HttpRequest, etag: &EntityTag) -> Option<bool> {
+    if !req.headers().contains_key(IF_NONE_MATCH) {
+        return None;
+    }
+
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => Some(false),
+        Ok(IfNoneMatch::Items(tags)) => Some(!tags.iter().any(|tag| tag.weak_eq(etag))),
+        Err(_) => None,
+    }
+}
+
+/// Whether `If-Modified-Since` shows the client already has `last_modified`.
+///
+/// HTTP dates have whole second precision, so sub-second changes within the
+/// client's second still count as seen; the ETag covers those.
+fn not_modified_since(req: &HttpRequest, last_modified: Option<DateTime<Utc>>) -> bool {
+    let (Ok(IfModifiedSince(since)), Some(last_modified)) =
+        (IfModifiedSince::parse(req), last_modified)
+    else {
+        return false;
+    };
+
+    let since = SystemTime::from(since);
+    let last_modified = SystemTime::from(http_date(last_modified));
+    last_modified <= since
+}
+
+/// Version a conditional write must find, taken from the `If-Match` header.
+///
+/// `If-Match: *` and a missing header make the write unconditional. When
+/// several tags are listed the write is pinned to whichever one is current.
+async fn expected_version<R: FilmRepository>(
+    repo: &R,
+    film_id: &Uuid,
+    req: &HttpRequest,
+) -> FilmResult<Option<i32>> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Ok(None);
+    }
+
+    let tags = match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => return Ok(None),
+        Ok(IfMatch::Items(tags)) => tags,
+        Err(_) => {
+            return Err(ValidationErrors::single(
+                "If-Match",
+                "invalid",
+                "is not a list of entity tags",
+            )
+            .into())
+        }
+    };
+
+    // If-Match uses the strong comparison, weak tags never match
+    let versions = tags
+        .iter()
+        .filter(|tag| !tag.weak)
+        .filter_map(|tag| tag.tag().parse::<i32>().ok())
+        .collect::<Vec<_>>();
+
+    match versions.as_slice() {
+        [version] => Ok(Some(*version)),
+        _ => {
+            let film = repo.get_film(film_id).await?;
+            if versions.contains(&film.version) {
+                Ok(Some(film.version))
+            } else {
+                Err(FilmError::VersionMismatch(*film_id))
+            }
         }
     }
 }
@@ -3,17 +3,25 @@ use actix_web::{
     HttpResponse,
 };
 
+use crate::settings::Settings;
+
 pub const API_VERSION: &str = "v0.0.1";
 
 pub fn service(cfg: &mut ServiceConfig) {
     cfg.route("/health_check", web::get().to(health_check));
 }
 
-pub async fn health_check() -> HttpResponse {
+/// Reports the API as up, with its version from [`Settings`] when they
+/// are registered and [`API_VERSION`] otherwise.
+pub async fn health_check(settings: Option<web::Data<Settings>>) -> HttpResponse {
     tracing::info!("Getting health check");
 
+    let version = match &settings {
+        Some(settings) => settings.api.version.as_str(),
+        None => API_VERSION,
+    };
     HttpResponse::Ok()
-        .append_header(("version", API_VERSION))
+        .append_header(("version", version))
         .finish()
 }
 
@@ -25,7 +33,7 @@ mod tests {
 
     #[actix_rt::test]
     async fn health_check_works() {
-        let res = health_check().await;
+        let res = health_check(None).await;
 
         assert!(res.status().is_success());
         assert_eq!(res.status(), StatusCode::OK);
@@ -1,7 +1,16 @@
+pub mod api_key_repository;
+pub mod api_keys;
+pub mod app;
+pub mod auth;
+pub mod events;
 pub mod film_repository;
 pub mod films;
 pub mod health;
+pub mod migrations;
+pub mod problem;
 pub mod routes;
+pub mod settings;
+pub mod ws;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
@@ -0,0 +1,269 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::{Pool, Postgres, Sqlite};
+
+/// Postgres migrations in `api/db/migrations`, embedded at compile time.
+///
+/// Each one is a reversible `<version>_<description>.up.sql` /
+/// `.down.sql` pair; applied versions and their checksums are tracked in
+/// the `_sqlx_migrations` table.
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("../db/migrations");
+
+/// SQLite migrations in `api/db/sqlite_migrations`, laid out the same way.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("../db/sqlite_migrations");
+
+/// A database the films schema can be migrated on.
+#[async_trait::async_trait]
+pub trait Migrations: sqlx::Database {
+    fn migrator() -> &'static Migrator;
+
+    /// Brings rows up to date where SQL can't, once every migration ran.
+    async fn complete(_pool: &Pool<Self>) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+}
+
+impl Migrations for Postgres {
+    fn migrator() -> &'static Migrator {
+        &POSTGRES_MIGRATOR
+    }
+}
+
+#[async_trait::async_trait]
+impl Migrations for Sqlite {
+    fn migrator() -> &'static Migrator {
+        &SQLITE_MIGRATOR
+    }
+
+    async fn complete(pool: &Pool<Self>) -> Result<(), sqlx::Error> {
+        crate::film_repository::sqlite_fold_films(pool).await
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("database is at migration {database} but this binary only knows up to {binary}")]
+    DatabaseAhead { database: i64, binary: i64 },
+    #[error("no migration {0} in this binary")]
+    UnknownVersion(i64),
+    #[error(transparent)]
+    Migrate(#[from] MigrateError),
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Migrate(e.into())
+    }
+}
+
+/// A migration known to the binary, the database or both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    /// `None` for versions only the database knows about.
+    pub description: Option<String>,
+    pub applied: bool,
+    /// Whether the applied script differs from the embedded one.
+    pub modified: bool,
+}
+
+/// Newest version embedded in the binary for `DB`.
+pub fn latest_version<DB: Migrations>() -> i64 {
+    DB::migrator().iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Applies every pending migration, then [`Migrations::complete`]s.
+///
+/// Refuses to touch a database migrated by a newer binary, and fails when an
+/// applied migration was edited after the fact.
+pub async fn run<DB>(pool: &Pool<DB>) -> Result<(), MigrationError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let database = applied_version(pool).await?;
+    let binary = latest_version::<DB>();
+    if database > binary {
+        return Err(MigrationError::DatabaseAhead { database, binary });
+    }
+
+    DB::migrator().run(pool).await?;
+    DB::complete(pool).await?;
+    Ok(())
+}
+
+/// Reverts applied migrations newer than `target`; `None` reverts the
+/// latest one only.
+pub async fn undo<DB>(pool: &Pool<DB>, target: Option<i64>) -> Result<(), MigrationError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let migrator = DB::migrator();
+    let target = match target {
+        Some(target) if target != 0 && !migrator.iter().any(|m| m.version == target) => {
+            return Err(MigrationError::UnknownVersion(target))
+        }
+        Some(target) => target,
+        None => {
+            let mut applied = applied_versions(pool).await?;
+            applied.pop();
+            applied.pop().unwrap_or(0)
+        }
+    };
+
+    migrator.undo(pool, target).await?;
+    Ok(())
+}
+
+/// Every migration the binary or the database knows, oldest first.
+pub async fn status<DB>(pool: &Pool<DB>) -> Result<Vec<MigrationStatus>, MigrationError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let migrator = DB::migrator();
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    let mut statuses = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| {
+            let applied = applied.iter().find(|a| a.version == m.version);
+            MigrationStatus {
+                version: m.version,
+                description: Some(m.description.to_string()),
+                applied: applied.is_some(),
+                modified: applied.is_some_and(|a| a.checksum != m.checksum),
+            }
+        })
+        .collect::<Vec<_>>();
+    statuses.extend(
+        applied
+            .iter()
+            .filter(|a| !migrator.iter().any(|m| m.version == a.version))
+            .map(|a| MigrationStatus {
+                version: a.version,
+                description: None,
+                applied: true,
+                modified: false,
+            }),
+    );
+    statuses.sort_by_key(|s| s.version);
+
+    Ok(statuses)
+}
+
+async fn applied_versions<DB>(pool: &Pool<DB>) -> Result<Vec<i64>, MigrationError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+async fn applied_version<DB>(pool: &Pool<DB>) -> Result<i64, MigrationError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    Ok(applied_versions(pool).await?.last().copied().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+
+    #[test]
+    fn every_migration_can_be_reverted() {
+        for migrator in [&POSTGRES_MIGRATOR, &SQLITE_MIGRATOR] {
+            let ups = migrator
+                .iter()
+                .filter(|m| m.migration_type == MigrationType::ReversibleUp);
+            for up in ups {
+                assert!(
+                    migrator.iter().any(|m| m.version == up.version
+                        && m.migration_type == MigrationType::ReversibleDown),
+                    "migration {} has no down script",
+                    up.version
+                );
+            }
+            assert!(migrator
+                .iter()
+                .all(|m| m.migration_type != MigrationType::Simple));
+        }
+    }
+
+    #[test]
+    fn migrations_are_ordered_and_unique() {
+        for (migrator, latest) in [
+            (&POSTGRES_MIGRATOR, latest_version::<Postgres>()),
+            (&SQLITE_MIGRATOR, latest_version::<Sqlite>()),
+        ] {
+            let versions = migrator
+                .iter()
+                .filter(|m| !m.migration_type.is_down_migration())
+                .map(|m| m.version)
+                .collect::<Vec<_>>();
+            assert!(versions.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(versions.last().copied(), Some(latest));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn sqlite_migrations_apply_and_revert() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+
+        run(&pool).await.unwrap();
+        let statuses = status(&pool).await.unwrap();
+        assert!(statuses.iter().all(|s| s.applied && !s.modified));
+
+        undo(&pool, Some(0)).await.unwrap();
+        let statuses = status(&pool).await.unwrap();
+        assert!(statuses.iter().all(|s| !s.applied));
+    }
+
+    #[actix_rt::test]
+    async fn sqlite_films_written_before_folding_are_folded() {
+        use crate::film_repository::{FilmRepository, ListFilms, SqliteFilmRepository};
+
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run(&pool).await.unwrap();
+        undo(&pool, Some(20240101000000)).await.unwrap();
+        let id = uuid::Uuid::new_v4();
+        sqlx::query(
+            r#"INSERT INTO films (id, title, director, year, poster, created_at) VALUES (?, 'ÉLAN', 'Åsa', 2010, '', '2024-01-02T10:20:30.000000Z')"#,
+        )
+        .bind(id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        run(&pool).await.unwrap();
+        let query = ListFilms {
+            director: Some("ÅSA".to_string()),
+            title: Some("élan".to_string()),
+            ..Default::default()
+        };
+        let films = SqliteFilmRepository::new(pool)
+            .get_films(&query)
+            .await
+            .unwrap();
+        assert_eq!(films.iter().map(|film| film.id).collect::<Vec<_>>(), [id]);
+    }
+}
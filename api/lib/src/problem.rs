@@ -0,0 +1,244 @@
+use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{web, Error, HttpResponse, ResponseError};
+use shared::problem::{ProblemDetails, PROBLEM_JSON};
+use shared::validation::FieldError;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Returns the id of the request currently being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// An API error rendered as an `application/problem+json` body.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    status: StatusCode,
+    problem_type: &'static str,
+    title: &'static str,
+    detail: Option<String>,
+    errors: Vec<FieldError>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, problem_type: &'static str, title: &'static str) -> Self {
+        Self {
+            status,
+            problem_type,
+            title,
+            detail: None,
+            errors: vec![],
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_errors(mut self, errors: &[FieldError]) -> Self {
+        self.errors = errors.to_vec();
+        self
+    }
+
+    pub fn details(&self) -> ProblemDetails {
+        ProblemDetails {
+            problem_type: self.problem_type.to_string(),
+            title: self.title.to_string(),
+            status: self.status.as_u16(),
+            detail: self.detail.clone(),
+            request_id: current_request_id(),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {}", self.title, detail),
+            None => f.write_str(self.title),
+        }
+    }
+}
+
+impl ResponseError for Problem {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = serde_json::to_string(&self.details()).unwrap_or_default();
+        HttpResponse::build(self.status)
+            .content_type(PROBLEM_JSON)
+            .body(body)
+    }
+}
+
+/// JSON extractor configuration reporting malformed bodies as problems.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _req| {
+        let problem = match &err {
+            JsonPayloadError::ContentType => Problem::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "/problems/unsupported-media-type",
+                "Unsupported media type",
+            )
+            .with_detail("Expected an `application/json` body"),
+            JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                Problem::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "/problems/payload-too-large",
+                    "Payload too large",
+                )
+                .with_detail(err.to_string())
+            }
+            _ => Problem::new(
+                StatusCode::BAD_REQUEST,
+                "/problems/malformed-body",
+                "Malformed request body",
+            )
+            .with_detail(err.to_string()),
+        };
+
+        problem.into()
+    })
+}
+
+/// Path extractor configuration reporting unparseable segments as problems.
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|err: PathError, _req| {
+        Problem::new(
+            StatusCode::NOT_FOUND,
+            "/problems/not-found",
+            "Resource not found",
+        )
+        .with_detail(err.to_string())
+        .into()
+    })
+}
+
+/// Query extractor configuration reporting invalid parameters as problems.
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err: QueryPayloadError, _req| {
+        Problem::new(
+            StatusCode::BAD_REQUEST,
+            "/problems/invalid-query",
+            "Invalid query parameters",
+        )
+        .with_detail(err.to_string())
+        .into()
+    })
+}
+
+/// Middleware assigning every request an id, echoed back in the
+/// `x-request-id` header and included in problem bodies.
+///
+/// A client supplied `x-request-id` is reused so ids can be correlated
+/// across services.
+pub struct RequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware { service }))
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .map(str::to_owned)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        let fut = REQUEST_ID.scope(request_id.clone(), self.service.call(req));
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn problem_carries_the_scoped_request_id() {
+        let problem = Problem::new(
+            StatusCode::NOT_FOUND,
+            "/problems/not-found",
+            "Resource not found",
+        );
+
+        assert_eq!(problem.details().request_id, None);
+
+        let details = REQUEST_ID
+            .scope("abc".to_string(), async { problem.details() })
+            .await;
+        assert_eq!(details.request_id.as_deref(), Some("abc"));
+        assert_eq!(details.status, 404);
+    }
+
+    #[test]
+    fn problem_response_uses_problem_json() {
+        let res = Problem::new(
+            StatusCode::BAD_REQUEST,
+            "/problems/malformed-body",
+            "Malformed request body",
+        )
+        .error_response();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.headers()
+                .get("content-type")
+                .and_then(|h| h.to_str().ok()),
+            Some(PROBLEM_JSON)
+        );
+    }
+}
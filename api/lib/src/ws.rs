@@ -0,0 +1,776 @@
+//! `/v1/ws`: film changes and editing presence over a WebSocket, speaking
+//! the protocol of [`shared::ws`].
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::web::{self, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, Session};
+use serde_json::Value;
+use shared::models::{FilmChangeKind, FilmEvent};
+use shared::ws::{
+    ClientMessage, Editor, Envelope, ErrorCode, ServerMessage, Topic, PROTOCOL_VERSION,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::events::{self, KEEP_ALIVE};
+use crate::film_repository::{FilmError, FilmEvents, FilmRepository, LiveFilmEvent};
+
+pub const WS_PATH: &str = "/v1/ws";
+
+/// Subscriptions one connection may hold.
+pub const MAX_SUBSCRIPTIONS: usize = 32;
+/// Films one subscription may follow by id.
+pub const MAX_SUBSCRIPTION_FILMS: usize = 100;
+/// Films one connection may edit at once.
+pub const MAX_EDITING_FILMS: usize = MAX_SUBSCRIPTION_FILMS;
+/// Longest subscription or editor name, in characters.
+pub const MAX_NAME_CHARS: usize = 100;
+/// Largest message accepted from a client, in bytes.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+/// A client not heard from for this long, not even a pong, is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Presence changes buffered per connection before it lags.
+const PRESENCE_CAPACITY: usize = 256;
+
+pub fn service<R: FilmRepository>(cfg: &mut ServiceConfig) {
+    cfg.route(WS_PATH, web::get().to(film_socket::<R>));
+}
+
+/// Upgrades to a WebSocket following film changes and who is editing what.
+///
+/// Needs [`FilmEvents`], [`Presence`] and the film repository registered as
+/// app data, answering 404 without events like `GET /v1/films/events`.
+pub async fn film_socket<R: FilmRepository>(
+    req: HttpRequest,
+    body: web::Payload,
+    events: Option<web::Data<FilmEvents>>,
+    presence: web::Data<Presence>,
+    repo: web::Data<R>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(events) = events else {
+        return Err(events::not_published().into());
+    };
+
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    let messages = messages
+        .max_frame_size(MAX_MESSAGE_SIZE)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_SIZE);
+    actix_web::rt::spawn(run(
+        session,
+        messages,
+        events.into_inner(),
+        presence.into_inner(),
+        repo.into_inner(),
+    ));
+    Ok(response)
+}
+
+async fn run<R: FilmRepository>(
+    mut session: Session,
+    mut messages: AggregatedMessageStream,
+    events: Arc<FilmEvents>,
+    presence: Arc<Presence>,
+    repo: Arc<R>,
+) {
+    let mut connection = Connection::new();
+    let mut changes = events.subscribe(None).receiver;
+    let mut editors = presence.subscribe();
+    let mut heartbeat = tokio::time::interval(KEEP_ALIVE);
+    let mut last_heard = Instant::now();
+    let mut close_reason = None;
+    tracing::debug!(connection = %connection.id(), "WebSocket connected");
+
+    let mut replies = vec![connection.welcome()];
+    'connection: loop {
+        for reply in replies.drain(..) {
+            let text = serde_json::to_string(&Envelope::new(reply)).expect("messages serialize");
+            if session.text(text).await.is_err() {
+                break 'connection;
+            }
+        }
+
+        replies = tokio::select! {
+            message = messages.recv() => {
+                last_heard = Instant::now();
+                match message {
+                    Some(Ok(AggregatedMessage::Text(text))) => {
+                        connection.handle(&text, &presence, repo.as_ref()).await
+                    }
+                    Some(Ok(AggregatedMessage::Binary(_))) => {
+                        vec![refuse(ErrorCode::Malformed, "messages must be text frames")]
+                    }
+                    Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    Some(Ok(AggregatedMessage::Pong(_))) => continue,
+                    Some(Ok(AggregatedMessage::Close(reason))) => {
+                        close_reason = reason;
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        let id = connection.id();
+                        tracing::debug!(connection = %id, "WebSocket protocol error: {}", e);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+            change = changes.recv() => match change {
+                Ok(LiveFilmEvent::Event(event)) => connection.on_event(&event),
+                Ok(LiveFilmEvent::Resync(_)) | Err(RecvError::Lagged(_)) => {
+                    vec![ServerMessage::Resync]
+                }
+                Err(RecvError::Closed) => break,
+            },
+            change = editors.recv() => match change {
+                Ok(change) => connection.on_presence(&change).into_iter().collect(),
+                Err(RecvError::Lagged(_)) => connection.presence(&presence),
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_heard.elapsed() > CLIENT_TIMEOUT || session.ping(b"").await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+    }
+
+    connection.close(&presence);
+    let _ = session.close(close_reason).await;
+    tracing::debug!(connection = %connection.id(), "WebSocket disconnected");
+}
+
+/// Who is editing which film, across every connection of the process.
+pub struct Presence {
+    editors: Mutex<HashMap<Uuid, BTreeMap<Uuid, Option<String>>>>,
+    sender: broadcast::Sender<PresenceChange>,
+}
+
+/// The editors of a film, sent whenever someone starts or stops editing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceChange {
+    pub film_id: Uuid,
+    pub editors: Vec<Editor>,
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Presence {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(PRESENCE_CAPACITY);
+        Self {
+            editors: Mutex::new(HashMap::new()),
+            sender,
+        }
+    }
+
+    pub fn editors(&self, film_id: &Uuid) -> Vec<Editor> {
+        let editors = lock(&self.editors);
+        editors.get(film_id).map(list).unwrap_or_default()
+    }
+
+    pub fn start_editing(&self, film_id: Uuid, editor: Editor) {
+        let mut editors = lock(&self.editors);
+        let film = editors.entry(film_id).or_default();
+        if film.get(&editor.connection) == Some(&editor.name) {
+            return;
+        }
+        film.insert(editor.connection, editor.name);
+        self.announce(film_id, list(film));
+    }
+
+    pub fn stop_editing(&self, film_id: &Uuid, connection: &Uuid) {
+        let mut editors = lock(&self.editors);
+        let Some(film) = editors.get_mut(film_id) else {
+            return;
+        };
+        if film.remove(connection).is_none() {
+            return;
+        }
+        let remaining = list(film);
+        if remaining.is_empty() {
+            editors.remove(film_id);
+        }
+        self.announce(*film_id, remaining);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceChange> {
+        self.sender.subscribe()
+    }
+
+    // sent under the lock, so receivers see changes in the order they happened
+    fn announce(&self, film_id: Uuid, editors: Vec<Editor>) {
+        let _ = self.sender.send(PresenceChange { film_id, editors });
+    }
+}
+
+fn list(editors: &BTreeMap<Uuid, Option<String>>) -> Vec<Editor> {
+    editors
+        .iter()
+        .map(|(connection, name)| Editor {
+            connection: *connection,
+            name: name.clone(),
+        })
+        .collect()
+}
+
+/// One client's side of the protocol: its subscriptions and the films it
+/// edits, turning its messages and the process' events into replies.
+pub struct Connection {
+    id: Uuid,
+    subscriptions: BTreeMap<String, Topic>,
+    editing: BTreeSet<Uuid>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Connection {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            subscriptions: BTreeMap::new(),
+            editing: BTreeSet::new(),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn welcome(&self) -> ServerMessage {
+        ServerMessage::Welcome {
+            connection: self.id,
+        }
+    }
+
+    /// Answers a text frame from the client, looking films it starts
+    /// editing up in `repo`.
+    pub async fn handle<R: FilmRepository>(
+        &mut self,
+        text: &str,
+        presence: &Presence,
+        repo: &R,
+    ) -> Vec<ServerMessage> {
+        let message = match parse(text) {
+            Ok(message) => message,
+            Err(refusal) => return vec![refusal],
+        };
+
+        match message {
+            ClientMessage::Subscribe {
+                subscription,
+                topic,
+            } => {
+                if subscription.chars().count() > MAX_NAME_CHARS {
+                    return vec![too_long("subscription")];
+                }
+                if !self.subscriptions.contains_key(&subscription)
+                    && self.subscriptions.len() == MAX_SUBSCRIPTIONS
+                {
+                    return vec![refuse(
+                        ErrorCode::LimitExceeded,
+                        format!("at most {} subscriptions are allowed", MAX_SUBSCRIPTIONS),
+                    )];
+                }
+                if matches!(&topic, Topic::Films(ids) if ids.len() > MAX_SUBSCRIPTION_FILMS) {
+                    return vec![refuse(
+                        ErrorCode::LimitExceeded,
+                        format!(
+                            "a subscription follows at most {} films",
+                            MAX_SUBSCRIPTION_FILMS
+                        ),
+                    )];
+                }
+
+                let mut replies = vec![ServerMessage::Subscribed {
+                    subscription: subscription.clone(),
+                }];
+                if let Topic::Films(ids) = &topic {
+                    replies.extend(ids.iter().filter_map(|id| present(presence, id)));
+                }
+                self.subscriptions.insert(subscription, topic);
+                replies
+            }
+            ClientMessage::Unsubscribe { subscription } => {
+                match self.subscriptions.remove(&subscription) {
+                    Some(_) => vec![ServerMessage::Unsubscribed { subscription }],
+                    None => vec![refuse(
+                        ErrorCode::UnknownSubscription,
+                        format!("no subscription named {:?}", subscription),
+                    )],
+                }
+            }
+            ClientMessage::StartEditing { film_id, name } => {
+                if name
+                    .as_ref()
+                    .is_some_and(|name| name.chars().count() > MAX_NAME_CHARS)
+                {
+                    return vec![too_long("name")];
+                }
+                if !self.editing.contains(&film_id) && self.editing.len() == MAX_EDITING_FILMS {
+                    return vec![refuse(
+                        ErrorCode::LimitExceeded,
+                        format!("at most {} films may be edited at once", MAX_EDITING_FILMS),
+                    )];
+                }
+                match repo.get_film(&film_id).await {
+                    Ok(_) => {}
+                    Err(FilmError::NotFound(_)) => {
+                        return vec![refuse(
+                            ErrorCode::UnknownFilm,
+                            format!("no film has id {}", film_id),
+                        )]
+                    }
+                    Err(e) => {
+                        tracing::warn!("Can't look up film {} being edited: {}", film_id, e);
+                        return vec![refuse(
+                            ErrorCode::Unavailable,
+                            "the film can't be looked up, try again later",
+                        )];
+                    }
+                }
+                let editor = Editor {
+                    connection: self.id,
+                    name,
+                };
+                presence.start_editing(film_id, editor);
+                self.editing.insert(film_id);
+                Vec::new()
+            }
+            ClientMessage::StopEditing { film_id } => {
+                presence.stop_editing(&film_id, &self.id);
+                self.editing.remove(&film_id);
+                Vec::new()
+            }
+            ClientMessage::Ping => vec![ServerMessage::Pong],
+        }
+    }
+
+    /// The change as seen by each subscription it concerns.
+    pub fn on_event(&self, event: &FilmEvent) -> Vec<ServerMessage> {
+        let deleted = event.kind == FilmChangeKind::Deleted;
+        self.subscriptions
+            .iter()
+            .filter_map(|(subscription, topic)| {
+                let (matching, concerned) = match topic {
+                    Topic::Films(ids) => {
+                        let followed = ids.contains(&event.film.id);
+                        (followed && !deleted, followed)
+                    }
+                    Topic::Filter(filter) => {
+                        let now = filter.matches(&event.film);
+                        let before = event
+                            .previous
+                            .as_ref()
+                            .is_some_and(|film| filter.matches(film));
+                        (now && !deleted, now || before)
+                    }
+                };
+                concerned.then(|| ServerMessage::Changed {
+                    subscription: subscription.clone(),
+                    matching,
+                    event: Box::new(event.clone()),
+                })
+            })
+            .collect()
+    }
+
+    /// The change, when a subscription follows the film by id.
+    pub fn on_presence(&self, change: &PresenceChange) -> Option<ServerMessage> {
+        self.follows(&change.film_id)
+            .then(|| ServerMessage::Presence {
+                film_id: change.film_id,
+                editors: change.editors.clone(),
+            })
+    }
+
+    /// Current editors of every film followed by id that has any.
+    pub fn presence(&self, presence: &Presence) -> Vec<ServerMessage> {
+        let followed = self
+            .subscriptions
+            .values()
+            .filter_map(|topic| match topic {
+                Topic::Films(ids) => Some(ids),
+                Topic::Filter(_) => None,
+            })
+            .flatten()
+            .collect::<BTreeSet<_>>();
+        followed
+            .into_iter()
+            .filter_map(|id| present(presence, id))
+            .collect()
+    }
+
+    /// Stops editing everything, for the other clients to see.
+    pub fn close(&mut self, presence: &Presence) {
+        for film_id in std::mem::take(&mut self.editing) {
+            presence.stop_editing(&film_id, &self.id);
+        }
+    }
+
+    fn follows(&self, film_id: &Uuid) -> bool {
+        self.subscriptions
+            .values()
+            .any(|topic| matches!(topic, Topic::Films(ids) if ids.contains(film_id)))
+    }
+}
+
+/// Reads a client envelope, refusing other protocol versions.
+fn parse(text: &str) -> Result<ClientMessage, ServerMessage> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|e| refuse(ErrorCode::Malformed, e.to_string()))?;
+    match value.get("version").and_then(Value::as_u64) {
+        Some(version) if version == u64::from(PROTOCOL_VERSION) => {}
+        Some(version) => {
+            return Err(refuse(
+                ErrorCode::UnsupportedVersion,
+                format!(
+                    "protocol version {} is not supported, use {}",
+                    version, PROTOCOL_VERSION
+                ),
+            ))
+        }
+        None => return Err(refuse(ErrorCode::Malformed, "missing protocol version")),
+    }
+    serde_json::from_value::<Envelope<ClientMessage>>(value)
+        .map(|envelope| envelope.message)
+        .map_err(|e| refuse(ErrorCode::Malformed, e.to_string()))
+}
+
+fn present(presence: &Presence, film_id: &Uuid) -> Option<ServerMessage> {
+    let editors = presence.editors(film_id);
+    (!editors.is_empty()).then_some(ServerMessage::Presence {
+        film_id: *film_id,
+        editors,
+    })
+}
+
+fn refuse(code: ErrorCode, message: impl Into<String>) -> ServerMessage {
+    ServerMessage::Error {
+        code,
+        message: message.into(),
+    }
+}
+
+fn too_long(field: &str) -> ServerMessage {
+    refuse(
+        ErrorCode::LimitExceeded,
+        format!("{} must be at most {} characters", field, MAX_NAME_CHARS),
+    )
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film_repository::MemoryFilmRepository;
+    use shared::models::{CreateFilm, EventId, Film};
+    use shared::ws::FilmFilter;
+
+    async fn send(
+        connection: &mut Connection,
+        presence: &Presence,
+        message: ClientMessage,
+    ) -> Vec<ServerMessage> {
+        send_with(connection, presence, &MemoryFilmRepository::new(), message).await
+    }
+
+    async fn send_with(
+        connection: &mut Connection,
+        presence: &Presence,
+        repo: &MemoryFilmRepository,
+        message: ClientMessage,
+    ) -> Vec<ServerMessage> {
+        let text = serde_json::to_string(&Envelope::new(message)).unwrap();
+        connection.handle(&text, presence, repo).await
+    }
+
+    async fn subscribe(connection: &mut Connection, presence: &Presence, name: &str, topic: Topic) {
+        let replies = send(
+            connection,
+            presence,
+            ClientMessage::Subscribe {
+                subscription: name.to_string(),
+                topic,
+            },
+        )
+        .await;
+        assert_eq!(
+            replies[0],
+            ServerMessage::Subscribed {
+                subscription: name.to_string()
+            }
+        );
+    }
+
+    fn film(director: &str) -> Film {
+        Film {
+            id: Uuid::new_v4(),
+            title: "Film".to_string(),
+            director: director.to_string(),
+            year: 2000,
+            ..Default::default()
+        }
+    }
+
+    fn event(kind: FilmChangeKind, film: Film, previous: Option<Film>) -> FilmEvent {
+        FilmEvent {
+            id: EventId { epoch: 1, seq: 1 },
+            kind,
+            film,
+            previous,
+        }
+    }
+
+    /// `(subscription, matching)` of each `Changed` reply.
+    fn seen(replies: Vec<ServerMessage>) -> Vec<(String, bool)> {
+        replies
+            .into_iter()
+            .map(|reply| match reply {
+                ServerMessage::Changed {
+                    subscription,
+                    matching,
+                    ..
+                } => (subscription, matching),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    async fn create_film(repo: &MemoryFilmRepository) -> Film {
+        repo.create_film(&CreateFilm {
+            title: "Heat".to_string(),
+            director: "Michael Mann".to_string(),
+            year: 1995,
+            poster: String::new(),
+        })
+        .await
+        .unwrap()
+    }
+
+    fn start_editing(film_id: Uuid) -> ClientMessage {
+        ClientMessage::StartEditing {
+            film_id,
+            name: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn changes_reach_the_subscriptions_they_concern() {
+        let presence = Presence::new();
+        let mut connection = Connection::new();
+        let heat = film("Michael Mann");
+        subscribe(
+            &mut connection,
+            &presence,
+            "heat",
+            Topic::Films(vec![heat.id]),
+        )
+        .await;
+        let mann = FilmFilter {
+            director: Some("michael mann".to_string()),
+            ..Default::default()
+        };
+        subscribe(&mut connection, &presence, "mann", Topic::Filter(mann)).await;
+
+        let created = event(FilmChangeKind::Created, heat.clone(), None);
+        assert_eq!(
+            seen(connection.on_event(&created)),
+            [("heat".to_string(), true), ("mann".to_string(), true)]
+        );
+
+        // updated out of the filter: the filter hears it one last time
+        let mut renamed = heat.clone();
+        renamed.director = "Someone Else".to_string();
+        let updated = event(FilmChangeKind::Updated, renamed.clone(), Some(heat));
+        assert_eq!(
+            seen(connection.on_event(&updated)),
+            [("heat".to_string(), true), ("mann".to_string(), false)]
+        );
+
+        let deleted = event(FilmChangeKind::Deleted, renamed, None);
+        assert_eq!(
+            seen(connection.on_event(&deleted)),
+            [("heat".to_string(), false)]
+        );
+
+        let unrelated = event(FilmChangeKind::Created, film("Ridley Scott"), None);
+        assert!(connection.on_event(&unrelated).is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn editors_are_announced_to_followers_and_dropped_on_close() {
+        let presence = Presence::new();
+        let repo = MemoryFilmRepository::new();
+        let mut changes = presence.subscribe();
+        let film_id = create_film(&repo).await.id;
+        let mut editor = Connection::new();
+        let mut follower = Connection::new();
+
+        let replies = send_with(
+            &mut editor,
+            &presence,
+            &repo,
+            ClientMessage::StartEditing {
+                film_id,
+                name: Some("Ann".to_string()),
+            },
+        )
+        .await;
+        assert!(replies.is_empty(), "{:?}", replies);
+        let editing = vec![Editor {
+            connection: editor.id(),
+            name: Some("Ann".to_string()),
+        }];
+        let change = changes.try_recv().unwrap();
+        assert_eq!(change.editors, editing);
+        assert_eq!(follower.on_presence(&change), None);
+
+        // subscribing tells who is already editing
+        let replies = send(
+            &mut follower,
+            &presence,
+            ClientMessage::Subscribe {
+                subscription: "film".to_string(),
+                topic: Topic::Films(vec![film_id]),
+            },
+        )
+        .await;
+        assert_eq!(
+            replies[1],
+            ServerMessage::Presence {
+                film_id,
+                editors: editing
+            }
+        );
+
+        editor.close(&presence);
+        let change = changes.try_recv().unwrap();
+        assert_eq!(
+            follower.on_presence(&change),
+            Some(ServerMessage::Presence {
+                film_id,
+                editors: vec![]
+            })
+        );
+        assert!(presence.editors(&film_id).is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn bad_messages_are_refused_without_closing() {
+        let presence = Presence::new();
+        let repo = MemoryFilmRepository::new();
+        let mut connection = Connection::new();
+        let code = |replies: Vec<ServerMessage>| match &replies[..] {
+            [ServerMessage::Error { code, .. }] => *code,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(
+            code(connection.handle("not json", &presence, &repo).await),
+            ErrorCode::Malformed
+        );
+        assert_eq!(
+            code(
+                connection
+                    .handle(r#"{"version": 2, "type": "ping"}"#, &presence, &repo)
+                    .await
+            ),
+            ErrorCode::UnsupportedVersion
+        );
+        assert_eq!(
+            code(
+                connection
+                    .handle(r#"{"version": 1, "type": "dance"}"#, &presence, &repo)
+                    .await
+            ),
+            ErrorCode::Malformed
+        );
+        let unsubscribe = ClientMessage::Unsubscribe {
+            subscription: "none".to_string(),
+        };
+        assert_eq!(
+            code(send(&mut connection, &presence, unsubscribe).await),
+            ErrorCode::UnknownSubscription
+        );
+        let too_many = Topic::Films(vec![Uuid::new_v4(); MAX_SUBSCRIPTION_FILMS + 1]);
+        let subscribe = ClientMessage::Subscribe {
+            subscription: "all".to_string(),
+            topic: too_many,
+        };
+        assert_eq!(
+            code(send(&mut connection, &presence, subscribe).await),
+            ErrorCode::LimitExceeded
+        );
+        let missing = start_editing(Uuid::new_v4());
+        assert_eq!(
+            code(send(&mut connection, &presence, missing).await),
+            ErrorCode::UnknownFilm
+        );
+
+        assert_eq!(
+            send(&mut connection, &presence, ClientMessage::Ping).await,
+            [ServerMessage::Pong]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn a_connection_edits_a_limited_number_of_films() {
+        let presence = Presence::new();
+        let repo = MemoryFilmRepository::new();
+        let mut connection = Connection::new();
+        let mut films = vec![];
+        for _ in 0..=MAX_EDITING_FILMS {
+            films.push(create_film(&repo).await.id);
+        }
+        let (last, editable) = films.split_last().unwrap();
+        for film_id in editable {
+            let replies =
+                send_with(&mut connection, &presence, &repo, start_editing(*film_id)).await;
+            assert!(replies.is_empty(), "{:?}", replies);
+        }
+
+        let replies = send_with(&mut connection, &presence, &repo, start_editing(*last)).await;
+        assert!(
+            matches!(
+                &replies[..],
+                [ServerMessage::Error {
+                    code: ErrorCode::LimitExceeded,
+                    ..
+                }]
+            ),
+            "{:?}",
+            replies
+        );
+        assert!(presence.editors(last).is_empty());
+
+        // films already edited can be started again, and stopping makes room
+        let again = send_with(&mut connection, &presence, &repo, start_editing(films[0])).await;
+        assert!(again.is_empty(), "{:?}", again);
+        let stop = ClientMessage::StopEditing { film_id: films[0] };
+        send_with(&mut connection, &presence, &repo, stop).await;
+        let replies = send_with(&mut connection, &presence, &repo, start_editing(*last)).await;
+        assert!(replies.is_empty(), "{:?}", replies);
+    }
+}
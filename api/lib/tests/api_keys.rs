@@ -0,0 +1,258 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, App};
+use api_lib::api_key_repository::{ApiKeyRepository, MemoryApiKeyRepository};
+use api_lib::app::configure;
+use api_lib::film_repository::MemoryFilmRepository;
+use api_lib::settings::{AuthSettings, Secret, Settings};
+use jsonwebtoken::{EncodingKey, Header};
+use shared::models::{ApiKey, ApiKeyScope, CreateApiKey, CreatedApiKey};
+use shared::problem::ProblemDetails;
+
+mod common;
+
+const SECRET: &str = "films-test-secret";
+
+fn settings() -> Settings {
+    let mut settings = common::settings();
+    settings.auth = AuthSettings {
+        enabled: true,
+        public_reads: false,
+        hs256_secret: Some(Secret::new(SECRET)),
+        ..Default::default()
+    };
+    settings
+}
+
+fn token(scope: &str) -> String {
+    let claims = serde_json::json!({
+        "sub": "ann",
+        "scope": scope,
+        "exp": jsonwebtoken::get_current_timestamp() + 3600,
+    });
+    let key = EncodingKey::from_secret(SECRET.as_bytes());
+    let token = jsonwebtoken::encode(&Header::default(), &claims, &key).unwrap();
+    format!("Bearer {}", token)
+}
+
+#[actix_rt::test]
+async fn admins_manage_keys_that_authenticate_every_route() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings()),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let mut keys = vec![];
+    for scope in [ApiKeyScope::Read, ApiKeyScope::Write] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/v1/api-keys")
+            .insert_header((header::AUTHORIZATION, token("admin")))
+            .set_json(CreateApiKey {
+                name: format!("nightly {}", scope.as_str()),
+                scope,
+            })
+            .to_request();
+        let created: CreatedApiKey = actix_web::test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created.api_key.scope, scope);
+        keys.push(created);
+    }
+    let (read, write) = (&keys[0], &keys[1]);
+
+    // reads of every service work with either key, in either header
+    for uri in ["/api/health_check", "/api/v1/films"] {
+        let req = actix_web::test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("x-api-key", read.key.as_str()))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+
+        let req = actix_web::test::TestRequest::get()
+            .uri(uri)
+            .insert_header((header::AUTHORIZATION, format!("ApiKey {}", write.key)))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+    }
+
+    // only write keys may write
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/films")
+        .insert_header(("x-api-key", read.key.as_str()))
+        .set_json(common::film())
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.problem_type, "/problems/forbidden");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/films")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", write.key)))
+        .set_json(common::film())
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // listings show when keys were last used, never the keys themselves
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/api-keys")
+        .insert_header((header::AUTHORIZATION, token("admin")))
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    assert!(!String::from_utf8_lossy(&body).contains(&read.key));
+    let listed: Vec<ApiKey> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(listed.len(), 2);
+    assert!(listed.iter().all(|key| key.last_used_at.is_some()));
+
+    // revoked keys are refused
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/api/v1/api-keys/{}", write.api_key.id))
+        .insert_header((header::AUTHORIZATION, token("admin")))
+        .to_request();
+    let revoked: ApiKey = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert!(revoked.revoked_at.is_some());
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/health_check")
+        .insert_header(("x-api-key", write.key.as_str()))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn only_admins_manage_keys() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings()),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/api-keys")
+        .insert_header((header::AUTHORIZATION, token("admin")))
+        .set_json(CreateApiKey {
+            name: "partner".to_string(),
+            scope: ApiKeyScope::Write,
+        })
+        .to_request();
+    let created: CreatedApiKey = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    for (authorization, status) in [
+        (None, StatusCode::UNAUTHORIZED),
+        (Some(token("films:write")), StatusCode::FORBIDDEN),
+        (
+            Some(format!("Bearer {}", created.key)),
+            StatusCode::FORBIDDEN,
+        ),
+    ] {
+        let mut req = actix_web::test::TestRequest::get().uri("/api/v1/api-keys");
+        if let Some(authorization) = authorization {
+            req = req.insert_header((header::AUTHORIZATION, authorization));
+        }
+        let res = actix_web::test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), status);
+    }
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/api-keys")
+        .insert_header((header::AUTHORIZATION, token("admin")))
+        .set_json(serde_json::json!({ "name": " " }))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/api/v1/api-keys/{}", uuid::Uuid::new_v4()))
+        .insert_header((header::AUTHORIZATION, token("admin")))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn writes_need_a_write_key_with_default_settings() {
+    let settings = common::settings();
+    let api_keys = web::Data::new(MemoryApiKeyRepository::new());
+    let mut keys = vec![];
+    for scope in [ApiKeyScope::Read, ApiKeyScope::Write] {
+        let create = CreateApiKey {
+            name: format!("reports {}", scope.as_str()),
+            scope,
+        };
+        keys.push(api_keys.create_api_key(&create).await.unwrap().key);
+    }
+    let (read, write) = (&keys[0], &keys[1]);
+    let app = App::new().configure(configure(
+        web::Data::new(settings),
+        web::Data::new(MemoryFilmRepository::new()),
+        api_keys,
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    // a read key is refused where no key is, and no further
+    for (key, status) in [
+        (None, StatusCode::UNAUTHORIZED),
+        (Some(read.clone()), StatusCode::FORBIDDEN),
+        (Some(format!("{}x", read)), StatusCode::UNAUTHORIZED),
+        (Some(write.clone()), StatusCode::OK),
+    ] {
+        let mut req = actix_web::test::TestRequest::post()
+            .uri("/api/v1/films")
+            .set_json(common::film());
+        if let Some(key) = key {
+            req = req.insert_header(("x-api-key", key));
+        }
+        let res = actix_web::test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), status);
+    }
+
+    for key in [None, Some(read)] {
+        let mut req = actix_web::test::TestRequest::get().uri("/api/v1/films");
+        if let Some(key) = key {
+            req = req.insert_header(("x-api-key", key.as_str()));
+        }
+        let res = actix_web::test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
+
+#[actix_rt::test]
+async fn the_admin_key_creates_the_first_keys() {
+    let admin_key = "fk_bootstrap-0123456789abcdef0123456789";
+    let mut settings = common::settings();
+    settings.auth.admin_key = Some(Secret::new(admin_key));
+    let app = App::new().configure(configure(
+        web::Data::new(settings),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/api-keys")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin_key)))
+        .set_json(CreateApiKey {
+            name: "nightly import".to_string(),
+            scope: ApiKeyScope::Write,
+        })
+        .to_request();
+    let created: CreatedApiKey = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    // the admin key only manages keys
+    for (key, status) in [
+        (admin_key, StatusCode::FORBIDDEN),
+        (created.key.as_str(), StatusCode::OK),
+    ] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/v1/films")
+            .insert_header(("x-api-key", key))
+            .set_json(common::film())
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), status);
+    }
+}
@@ -0,0 +1,77 @@
+use actix_web::{http::StatusCode, web, App};
+use api_lib::api_key_repository::MemoryApiKeyRepository;
+use api_lib::app::configure;
+use api_lib::film_repository::MemoryFilmRepository;
+
+mod common;
+
+#[actix_rt::test]
+async fn settings_shape_the_routes() {
+    let mut settings = common::settings();
+    settings.api.version = "v9.9.9".to_string();
+    settings.api.films_path = "/v2/movies".to_string();
+    settings.features.film_writes = false;
+
+    let app = App::new().configure(configure(
+        web::Data::new(settings),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/health_check")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(
+        res.headers().get("version").and_then(|h| h.to_str().ok()),
+        Some("v9.9.9")
+    );
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v2/movies")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v2/movies")
+        .set_json(serde_json::json!({ "title": "Heat" }))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert!(res.status().is_client_error());
+    assert_ne!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_rt::test]
+async fn configured_origins_pass_cors() {
+    let mut settings = common::settings();
+    settings.cors.allowed_origins = vec!["https://films.example.com".to_string()];
+
+    let app = App::new().configure(configure(
+        web::Data::new(settings),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films")
+        .insert_header(("origin", "https://films.example.com"))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()
+            .get("access-control-allow-origin")
+            .and_then(|h| h.to_str().ok()),
+        Some("https://films.example.com")
+    );
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films")
+        .insert_header(("origin", "https://elsewhere.example.com"))
+        .to_request();
+    let res = actix_web::test::try_call_service(&app, req).await;
+    assert!(!matches!(res, Ok(res) if res.headers().contains_key("access-control-allow-origin")));
+}
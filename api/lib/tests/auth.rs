@@ -0,0 +1,308 @@
+use std::path::PathBuf;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, App, HttpResponse};
+use api_lib::api_key_repository::MemoryApiKeyRepository;
+use api_lib::app::configure;
+use api_lib::auth::{Authentication, Principal};
+use api_lib::film_repository::MemoryFilmRepository;
+use api_lib::settings::{AuthSettings, Secret, Settings};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use shared::models::Film;
+use shared::problem::{ProblemDetails, PROBLEM_JSON};
+
+mod common;
+
+const HS256_SECRET: &str = "films-test-secret";
+const RS256_KID: &str = "test-rs256";
+const RS256_PEM: &[u8] = include_bytes!("fixtures/jwt_rs256.pem");
+
+fn auth_settings() -> AuthSettings {
+    AuthSettings {
+        enabled: true,
+        hs256_secret: Some(Secret::new(HS256_SECRET)),
+        jwks_file: Some(PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/jwks.json"
+        ))),
+        issuer: Some("https://auth.example.com".to_string()),
+        ..Default::default()
+    }
+}
+
+fn claims(sub: &str, exp: u64) -> serde_json::Value {
+    serde_json::json!({ "sub": sub, "iss": "https://auth.example.com", "exp": exp })
+}
+
+/// Claims of a token allowed to write films.
+fn writer(sub: &str) -> serde_json::Value {
+    let mut claims = claims(sub, in_an_hour());
+    claims["scope"] = "films:write".into();
+    claims
+}
+
+fn in_an_hour() -> u64 {
+    jsonwebtoken::get_current_timestamp() + 3600
+}
+
+fn hs256(claims: &serde_json::Value) -> String {
+    let key = EncodingKey::from_secret(HS256_SECRET.as_bytes());
+    jsonwebtoken::encode(&Header::default(), claims, &key).unwrap()
+}
+
+fn rs256(claims: &serde_json::Value) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(RS256_KID.to_string());
+    let key = EncodingKey::from_rsa_pem(RS256_PEM).unwrap();
+    jsonwebtoken::encode(&header, claims, &key).unwrap()
+}
+
+fn bearer(token: &str) -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", token))
+}
+
+fn settings(auth: AuthSettings) -> Settings {
+    let mut settings = common::settings();
+    settings.auth = auth;
+    settings
+}
+
+#[actix_rt::test]
+async fn writes_need_a_valid_token() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings(auth_settings())),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/films")
+        .set_json(common::film())
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        res.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+        "Bearer"
+    );
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        PROBLEM_JSON
+    );
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.problem_type, "/problems/unauthorized");
+    assert!(problem.request_id.is_some());
+
+    for token in [hs256(&writer("ann")), rs256(&writer("bob"))] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/v1/films")
+            .insert_header(bearer(&token))
+            .set_json(common::film())
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn writes_need_the_write_scope() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings(auth_settings())),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+    let created: Film = actix_web::test::call_and_read_body_json(
+        &app,
+        actix_web::test::TestRequest::post()
+            .uri("/api/v1/films")
+            .insert_header(bearer(&hs256(&writer("ann"))))
+            .set_json(common::film())
+            .to_request(),
+    )
+    .await;
+
+    let mut admin = claims("ann", in_an_hour());
+    admin["scope"] = "admin".into();
+    for token in [hs256(&claims("ann", in_an_hour())), rs256(&admin)] {
+        for req in [
+            actix_web::test::TestRequest::post().uri("/api/v1/films"),
+            actix_web::test::TestRequest::put().uri(&format!("/api/v1/films/{}", created.id)),
+            actix_web::test::TestRequest::delete().uri(&format!("/api/v1/films/{}", created.id)),
+        ] {
+            let req = req
+                .insert_header(bearer(&token))
+                .set_json(common::film())
+                .to_request();
+            let res = actix_web::test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::FORBIDDEN);
+            let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+            assert_eq!(problem.problem_type, "/problems/forbidden");
+        }
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/v1/films/{}", created.id))
+        .insert_header(bearer(&hs256(&claims("ann", in_an_hour()))))
+        .to_request();
+    let film: Film = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(film, created);
+}
+
+#[actix_rt::test]
+async fn bad_tokens_are_refused_even_on_reads() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings(auth_settings())),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let forged = jsonwebtoken::encode(
+        &Header::default(),
+        &claims("ann", in_an_hour()),
+        &EncodingKey::from_secret(b"guessed"),
+    )
+    .unwrap();
+    let mut wrong_issuer = claims("ann", in_an_hour());
+    wrong_issuer["iss"] = "https://elsewhere.example.com".into();
+
+    for token in [
+        forged,
+        hs256(&claims("ann", 1_000_000)),
+        rs256(&claims("bob", 1_000_000)),
+        hs256(&wrong_issuer),
+        "garbage".to_string(),
+    ] {
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/v1/films")
+            .insert_header(bearer(&token))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{}", token);
+        assert_eq!(
+            res.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            r#"Bearer error="invalid_token""#
+        );
+    }
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/api/v1/films/{}", uuid::Uuid::new_v4()))
+        .insert_header((header::AUTHORIZATION, "Basic YW5uOmh1bnRlcjI="))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn reads_can_be_made_private() {
+    let auth = AuthSettings {
+        public_reads: false,
+        ..auth_settings()
+    };
+    let app = App::new().configure(configure(
+        web::Data::new(settings(auth)),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films")
+        .insert_header(bearer(&rs256(&claims("bob", in_an_hour()))))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn anonymous_writes_need_opting_in_while_disabled() {
+    let open = AuthSettings {
+        anonymous_writes: true,
+        ..Default::default()
+    };
+    let enabled = AuthSettings {
+        anonymous_writes: true,
+        ..auth_settings()
+    };
+
+    for (auth, status) in [
+        (AuthSettings::default(), StatusCode::UNAUTHORIZED),
+        (open, StatusCode::OK),
+        (enabled, StatusCode::UNAUTHORIZED),
+    ] {
+        let app = App::new().configure(configure(
+            web::Data::new(settings(auth)),
+            web::Data::new(MemoryFilmRepository::new()),
+            web::Data::new(MemoryApiKeyRepository::new()),
+        ));
+        let app = actix_web::test::init_service(app).await;
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/v1/films")
+            .set_json(common::film())
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), status);
+    }
+}
+
+#[actix_rt::test]
+async fn handlers_see_the_principal() {
+    async fn whoami(principal: Option<Principal>) -> HttpResponse {
+        match principal {
+            Some(principal) => HttpResponse::Ok().body(principal.subject),
+            None => HttpResponse::Ok().body("anonymous"),
+        }
+    }
+    async fn scopes(principal: Principal) -> HttpResponse {
+        HttpResponse::Ok().body(principal.scopes.join(","))
+    }
+
+    let app = App::new()
+        .wrap(Authentication::new(&auth_settings()))
+        .route("/whoami", web::get().to(whoami))
+        .route("/scopes", web::get().to(scopes));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/whoami")
+        .insert_header(bearer(&rs256(&claims("bob", in_an_hour()))))
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    assert_eq!(body, "bob");
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/whoami")
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    assert_eq!(body, "anonymous");
+
+    let mut scoped = claims("ann", in_an_hour());
+    scoped["scope"] = "films:write admin".into();
+    let req = actix_web::test::TestRequest::get()
+        .uri("/scopes")
+        .insert_header(bearer(&hs256(&scoped)))
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    assert_eq!(body, "films:write,admin");
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/scopes")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
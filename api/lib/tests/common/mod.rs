@@ -0,0 +1,23 @@
+//! Helpers shared by the integration tests, each of which includes this
+//! with `mod common;` and uses what it needs of it.
+#![allow(dead_code)]
+
+use api_lib::settings::Settings;
+use shared::models::CreateFilm;
+
+/// Default settings, but for the static files, which tests don't serve.
+pub fn settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.features.static_files = false;
+    settings
+}
+
+/// The film tests create when any will do.
+pub fn film() -> CreateFilm {
+    CreateFilm {
+        title: "Heat".to_string(),
+        director: "Michael Mann".to_string(),
+        year: 1995,
+        poster: "https://example.com/heat.png".to_string(),
+    }
+}
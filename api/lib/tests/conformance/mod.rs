@@ -0,0 +1,813 @@
+//! Generic `FilmRepository` and `ApiKeyRepository` conformance suites.
+//!
+//! Every case is an `async fn` over any repository. Backends opt in with
+//! [`film_repository_conformance!`] or [`api_key_repository_conformance!`],
+//! which expand to one test per case:
+//!
+//! ```ignore
+//! film_repository_conformance!(memory, async { Some(MemoryFilmRepository::new()) });
+//! ```
+//!
+//! The repository expression yields `None` to skip the backend, e.g. when
+//! its server isn't configured. Backends may share state between cases
+//! running in parallel, so every case only looks at films it created: each
+//! one tags its films with a unique director and filters listings by it,
+//! or searches for a unique word.
+
+use api_lib::api_key_repository::{ApiKeyError, ApiKeyRepository};
+use api_lib::film_repository::{
+    CursorCodec, FilmCursor, FilmError, FilmRepository, ListFilms, SearchFilms, SuggestFilms,
+};
+use shared::filter::Filter;
+use shared::models::{
+    ApiKeyScope, CreateApiKey, CreateFilm, Film, FilmPatch, FilmQuery, FilmSearchHit,
+    FilmSearchQuery, FilmSort, FilmSuggestQuery, FilmSuggestion, SortOrder, SuggestField,
+};
+use uuid::Uuid;
+
+/// Expands to `mod $backend` holding one test per conformance case.
+macro_rules! film_repository_conformance {
+    ($backend:ident, $repo:expr) => {
+        mod $backend {
+            #[allow(unused_imports)]
+            use super::*;
+
+            film_repository_conformance!(@cases $repo;
+                created_film_can_be_fetched,
+                missing_film_is_not_found,
+                update_replaces_editable_fields,
+                patch_changes_only_supplied_fields,
+                concurrent_updates_report_what_they_replaced,
+                deleting_a_missing_film_is_not_found,
+                stale_version_is_rejected_without_writing,
+                timestamps_are_maintained,
+                filters_select_matching_films,
+                filter_expressions_select_matching_films,
+                text_filters_ignore_case_beyond_ascii,
+                sorts_follow_the_reference_order,
+                offset_pages_partition_the_listing,
+                cursor_pages_partition_the_listing,
+                collection_stamp_moves_with_writes,
+                search_matches_word_prefixes_ignoring_case_and_accents,
+                search_ranks_title_matches_first,
+                search_pages_partition_the_results,
+                suggestions_count_distinct_values
+            );
+        }
+    };
+    (@cases $repo:expr; $($case:ident),* $(,)?) => {
+        $(
+            #[actix_rt::test]
+            async fn $case() {
+                let Some(repo) = $repo.await else {
+                    eprintln!("{} backend is not configured, skipping", module_path!());
+                    return;
+                };
+                $crate::conformance::$case(&repo).await;
+            }
+        )*
+    };
+}
+
+/// Expands to `mod $backend` holding one test per API key case.
+macro_rules! api_key_repository_conformance {
+    ($backend:ident, $repo:expr) => {
+        mod $backend {
+            #[allow(unused_imports)]
+            use super::*;
+
+            film_repository_conformance!(@cases $repo;
+                api_keys_are_found_by_key_until_revoked
+            );
+        }
+    };
+}
+
+/// A director no other case or run uses.
+fn marker() -> String {
+    format!("director-{}", Uuid::new_v4())
+}
+
+/// A search word no other case or run uses.
+fn search_marker() -> String {
+    format!("w{}", Uuid::new_v4().simple())
+}
+
+fn search(q: &str, limit: u32, offset: u32) -> SearchFilms {
+    SearchFilms::from_query(&FilmSearchQuery {
+        q: q.to_string(),
+        limit: Some(limit),
+        offset: Some(offset),
+    })
+    .unwrap()
+}
+
+fn hit_ids(hits: &[FilmSearchHit]) -> Vec<Uuid> {
+    hits.iter().map(|hit| hit.film.id).collect()
+}
+
+fn film(director: &str, title: &str, year: u16) -> CreateFilm {
+    CreateFilm {
+        title: title.to_string(),
+        director: director.to_string(),
+        year,
+        poster: String::new(),
+    }
+}
+
+async fn create_all<R: FilmRepository>(repo: &R, films: &[CreateFilm]) -> Vec<Film> {
+    let mut created = vec![];
+    for film in films {
+        created.push(repo.create_film(film).await.unwrap());
+    }
+    created
+}
+
+/// Five films by `director` with distinct titles and some shared years.
+async fn create_catalogue<R: FilmRepository>(repo: &R, director: &str) -> Vec<Film> {
+    create_all(
+        repo,
+        &[
+            film(director, "Heat", 1995),
+            film(director, "Collateral", 2004),
+            film(director, "Thief", 1981),
+            film(director, "Ali", 2001),
+            film(director, "Manhunter", 1995),
+        ],
+    )
+    .await
+}
+
+fn by(director: &str) -> ListFilms {
+    ListFilms {
+        director: Some(director.to_string()),
+        limit: 100,
+        ..Default::default()
+    }
+}
+
+/// The reference answer for `query` over `films`, see [`ListFilms`].
+fn expected(films: &[Film], query: &ListFilms) -> Vec<Film> {
+    let mut films = films
+        .iter()
+        .filter(|film| query.matches(film))
+        .cloned()
+        .collect::<Vec<_>>();
+    films.sort_by(|a, b| query.compare(a, b));
+    films
+        .into_iter()
+        .skip(query.offset as usize)
+        .take(query.limit as usize)
+        .collect()
+}
+
+fn ids(films: &[Film]) -> Vec<Uuid> {
+    films.iter().map(|film| film.id).collect()
+}
+
+pub async fn created_film_can_be_fetched<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let create = CreateFilm {
+        poster: "https://example.com/posters/heat.jpg".to_string(),
+        ..film(&director, "Heat", 1995)
+    };
+
+    let created = repo.create_film(&create).await.unwrap();
+    assert_eq!(created.title, create.title);
+    assert_eq!(created.director, create.director);
+    assert_eq!(created.year, create.year);
+    assert_eq!(created.poster, create.poster);
+    assert_eq!(created.version, 1);
+
+    assert_eq!(repo.get_film(&created.id).await.unwrap(), created);
+    assert_eq!(repo.get_films(&by(&director)).await.unwrap(), vec![created]);
+}
+
+pub async fn missing_film_is_not_found<R: FilmRepository>(repo: &R) {
+    let id = Uuid::new_v4();
+    let missing = Film {
+        id,
+        ..Default::default()
+    };
+    let patch = FilmPatch {
+        year: Some(Some(2000)),
+        ..Default::default()
+    };
+
+    assert!(matches!(repo.get_film(&id).await, Err(FilmError::NotFound(i)) if i == id));
+    assert!(matches!(
+        repo.update_film(&missing, None).await,
+        Err(FilmError::NotFound(_))
+    ));
+    assert!(matches!(
+        repo.patch_film(&id, &patch, None).await,
+        Err(FilmError::NotFound(_))
+    ));
+    assert!(matches!(
+        repo.update_film(&missing, Some(1)).await,
+        Err(FilmError::NotFound(_))
+    ));
+    assert!(matches!(
+        repo.delete_film(&id, Some(1)).await,
+        Err(FilmError::NotFound(_))
+    ));
+}
+
+pub async fn update_replaces_editable_fields<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+
+    let replacement = Film {
+        title: "Heat (Director's Definitive Edition)".to_string(),
+        year: 2022,
+        poster: "https://example.com/posters/heat.jpg".to_string(),
+        ..created.clone()
+    };
+    let update = repo.update_film(&replacement, None).await.unwrap();
+    assert_eq!(update.previous, created);
+    let updated = update.film;
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.title, replacement.title);
+    assert_eq!(updated.director, director);
+    assert_eq!(updated.year, 2022);
+    assert_eq!(updated.poster, replacement.poster);
+    assert_eq!(updated.version, created.version + 1);
+
+    assert_eq!(repo.get_film(&created.id).await.unwrap(), updated);
+}
+
+pub async fn patch_changes_only_supplied_fields<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&CreateFilm {
+            poster: "https://example.com/posters/heat.jpg".to_string(),
+            ..film(&director, "Heat", 1995)
+        })
+        .await
+        .unwrap();
+
+    let patch = FilmPatch {
+        year: Some(Some(1996)),
+        poster: Some(None),
+        ..Default::default()
+    };
+    let patched = repo
+        .patch_film(&created.id, &patch, None)
+        .await
+        .unwrap()
+        .film;
+    assert_eq!(patched.title, created.title);
+    assert_eq!(patched.director, created.director);
+    assert_eq!(patched.year, 1996);
+    assert_eq!(patched.poster, "");
+    assert_eq!(patched.version, created.version + 1);
+
+    // an empty patch changes nothing, not even the version
+    let unchanged = repo
+        .patch_film(&created.id, &FilmPatch::default(), Some(patched.version))
+        .await
+        .unwrap();
+    assert_eq!(unchanged.film, patched);
+    assert_eq!(unchanged.previous, patched);
+}
+
+pub async fn concurrent_updates_report_what_they_replaced<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+
+    let updates = futures_util::future::join_all((0..8).map(|i| {
+        let film = Film {
+            title: format!("Heat {i}"),
+            ..created.clone()
+        };
+        async move { repo.update_film(&film, None).await.unwrap() }
+    }))
+    .await;
+
+    // each update replaced exactly the film the one before it left
+    let mut versions = updates
+        .iter()
+        .map(|update| {
+            assert_eq!(update.previous.version + 1, update.film.version);
+            update.previous.version
+        })
+        .collect::<Vec<_>>();
+    versions.sort_unstable();
+    assert_eq!(versions, (1..=8).collect::<Vec<_>>());
+}
+
+pub async fn deleting_a_missing_film_is_not_found<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+
+    assert_eq!(repo.delete_film(&created.id, None).await.unwrap(), created);
+    assert!(matches!(
+        repo.get_film(&created.id).await,
+        Err(FilmError::NotFound(_))
+    ));
+    assert!(repo.get_films(&by(&director)).await.unwrap().is_empty());
+
+    assert!(matches!(
+        repo.delete_film(&created.id, None).await,
+        Err(FilmError::NotFound(id)) if id == created.id
+    ));
+}
+
+pub async fn stale_version_is_rejected_without_writing<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+    let current = repo
+        .update_film(&created, Some(created.version))
+        .await
+        .unwrap()
+        .film;
+
+    let stale = Film {
+        title: "Stale".to_string(),
+        ..created.clone()
+    };
+    let patch = FilmPatch {
+        title: Some(Some("Stale".to_string())),
+        ..Default::default()
+    };
+    assert!(matches!(
+        repo.update_film(&stale, Some(created.version)).await,
+        Err(FilmError::VersionMismatch(id)) if id == created.id
+    ));
+    assert!(matches!(
+        repo.patch_film(&created.id, &patch, Some(created.version))
+            .await,
+        Err(FilmError::VersionMismatch(_))
+    ));
+    assert!(matches!(
+        repo.patch_film(&created.id, &FilmPatch::default(), Some(created.version))
+            .await,
+        Err(FilmError::VersionMismatch(_))
+    ));
+    assert!(matches!(
+        repo.delete_film(&created.id, Some(created.version)).await,
+        Err(FilmError::VersionMismatch(_))
+    ));
+
+    assert_eq!(repo.get_film(&created.id).await.unwrap(), current);
+}
+
+pub async fn timestamps_are_maintained<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+    let created_at = created.created_at.expect("created_at is set on insert");
+    assert_eq!(created.updated_at, None);
+
+    // a client supplied created_at is ignored, updated_at is stamped
+    let updated = repo
+        .update_film(
+            &Film {
+                created_at: Some(created_at - chrono::Duration::days(1)),
+                updated_at: None,
+                ..created.clone()
+            },
+            None,
+        )
+        .await
+        .unwrap()
+        .film;
+    assert_eq!(updated.created_at, Some(created_at));
+    let updated_at = updated.updated_at.expect("updated_at is set on update");
+    assert!(updated_at >= created_at);
+
+    let patch = FilmPatch {
+        year: Some(Some(1996)),
+        ..Default::default()
+    };
+    let patched = repo
+        .patch_film(&created.id, &patch, None)
+        .await
+        .unwrap()
+        .film;
+    assert_eq!(patched.created_at, Some(created_at));
+    assert!(patched.updated_at.unwrap() >= updated_at);
+
+    // what a write returns is what a read sees
+    assert_eq!(repo.get_film(&created.id).await.unwrap(), patched);
+}
+
+pub async fn filters_select_matching_films<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let films = create_catalogue(repo, &director).await;
+
+    let queries = [
+        by(&director),
+        ListFilms {
+            // director matches ignore case
+            director: Some(director.to_uppercase()),
+            ..by(&director)
+        },
+        ListFilms {
+            title: Some("HUNT".to_string()),
+            ..by(&director)
+        },
+        ListFilms {
+            title: Some("a".to_string()),
+            ..by(&director)
+        },
+        ListFilms {
+            year_from: Some(1995),
+            year_to: Some(2001),
+            ..by(&director)
+        },
+        ListFilms {
+            year_from: Some(2002),
+            ..by(&director)
+        },
+        ListFilms {
+            title: Some("nothing like it".to_string()),
+            ..by(&director)
+        },
+    ];
+    for query in queries {
+        let mut listed = ids(&repo.get_films(&query).await.unwrap());
+        let mut expected = ids(&expected(&films, &query));
+        listed.sort();
+        expected.sort();
+        assert_eq!(listed, expected, "{:?}", query);
+    }
+}
+
+pub async fn filter_expressions_select_matching_films<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let films = create_catalogue(repo, &director).await;
+    let id = &director["director-".len()..];
+
+    for filter in [
+        format!(r#"director:="{director}" year:>=1995 -title:HEAT"#),
+        format!(r#"director:"{id}" (title:ali OR year:<1990)"#),
+        format!(r#"director:{id} NOT (year:1995 OR title:"'; DROP TABLE films; --")"#),
+    ] {
+        let query = ListFilms {
+            filter: Some(Filter::parse(&filter).unwrap()),
+            ..by(&director)
+        };
+        let want = expected(&films, &query);
+        assert!(!want.is_empty(), "{}", filter);
+        let listed = repo.get_films(&query).await.unwrap();
+        assert_eq!(ids(&listed), ids(&want), "{}", filter);
+    }
+}
+
+pub async fn text_filters_ignore_case_beyond_ascii<R: FilmRepository>(repo: &R) {
+    let director = format!("{} Åsa Öberg", marker());
+    let films = create_all(
+        repo,
+        &[
+            film(&director, "Élan Vital", 2010),
+            film(&director, "L'ÉTÉ", 2012),
+            film(&director, "Elan", 2014),
+        ],
+    )
+    .await;
+    let upper = director.to_uppercase();
+
+    let mut queries = vec![
+        ListFilms {
+            director: Some(upper.clone()),
+            title: Some("éLAN".to_string()),
+            ..by(&director)
+        },
+        ListFilms {
+            title: Some("été".to_string()),
+            ..by(&director)
+        },
+    ];
+    for filter in [
+        format!(r#"director:="{upper}" title:"ÉLAN""#),
+        r#"director:"åsa öBERG" title:"l'été""#.to_string(),
+    ] {
+        queries.push(ListFilms {
+            filter: Some(Filter::parse(&filter).unwrap()),
+            ..by(&director)
+        });
+    }
+
+    for query in queries {
+        let want = expected(&films, &query);
+        assert_eq!(want.len(), 1, "{:?}", query);
+        let listed = repo.get_films(&query).await.unwrap();
+        assert_eq!(ids(&listed), ids(&want), "{:?}", query);
+    }
+}
+
+pub async fn sorts_follow_the_reference_order<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let films = create_catalogue(repo, &director).await;
+
+    for sort in [
+        FilmSort::CreatedAt,
+        FilmSort::Title,
+        FilmSort::Year,
+        FilmSort::Director,
+    ] {
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let query = ListFilms {
+                sort,
+                order,
+                ..by(&director)
+            };
+            let listed = repo.get_films(&query).await.unwrap();
+            assert_eq!(
+                ids(&listed),
+                ids(&expected(&films, &query)),
+                "{:?} {:?}",
+                sort,
+                order
+            );
+        }
+    }
+}
+
+pub async fn offset_pages_partition_the_listing<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let films = create_catalogue(repo, &director).await;
+
+    let mut listed = vec![];
+    for offset in (0..films.len() as u32 + 2).step_by(2) {
+        let query = ListFilms {
+            sort: FilmSort::Year,
+            limit: 2,
+            offset,
+            ..by(&director)
+        };
+        let page = repo.get_films(&query).await.unwrap();
+        assert_eq!(ids(&page), ids(&expected(&films, &query)));
+        listed.extend(page);
+    }
+
+    let all = ListFilms {
+        sort: FilmSort::Year,
+        ..by(&director)
+    };
+    assert_eq!(ids(&listed), ids(&expected(&films, &all)));
+}
+
+pub async fn cursor_pages_partition_the_listing<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let films = create_catalogue(repo, &director).await;
+    let codec = CursorCodec::new("conformance");
+
+    for sort in [FilmSort::Title, FilmSort::Year, FilmSort::CreatedAt] {
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let mut query = FilmQuery {
+                director: Some(director.clone()),
+                sort: Some(sort),
+                order: Some(order),
+                limit: Some(2),
+                ..Default::default()
+            };
+            let mut listed = vec![];
+            loop {
+                let page = repo
+                    .get_films(&ListFilms::from_query(&query, &codec).unwrap())
+                    .await
+                    .unwrap();
+                let Some(last) = page.last() else { break };
+                query.cursor = Some(codec.encode(&FilmCursor::after(last, sort, order)));
+                listed.extend(page);
+                assert!(listed.len() <= films.len(), "cursor walk doesn't end");
+            }
+
+            let all = ListFilms {
+                sort,
+                order,
+                ..by(&director)
+            };
+            assert_eq!(
+                ids(&listed),
+                ids(&expected(&films, &all)),
+                "{:?} {:?}",
+                sort,
+                order
+            );
+        }
+    }
+}
+
+pub async fn collection_stamp_moves_with_writes<R: FilmRepository>(repo: &R) {
+    let director = marker();
+    let before = repo.collection_stamp().await.unwrap();
+    let created = repo
+        .create_film(&film(&director, "Heat", 1995))
+        .await
+        .unwrap();
+    let after_create = repo.collection_stamp().await.unwrap();
+    assert_ne!(after_create, before);
+    assert!(after_create.last_modified >= created.created_at);
+
+    repo.update_film(&created, None).await.unwrap();
+    let after_update = repo.collection_stamp().await.unwrap();
+    assert_ne!(after_update, after_create);
+
+    repo.delete_film(&created.id, None).await.unwrap();
+    assert_ne!(repo.collection_stamp().await.unwrap(), after_update);
+}
+
+pub async fn search_matches_word_prefixes_ignoring_case_and_accents<R: FilmRepository>(repo: &R) {
+    let word = search_marker();
+    let films = create_all(
+        repo,
+        &[
+            film(
+                "Jean-Pierre Jeunet",
+                &format!("Le Fabuleux Destin d'Amélie Poulain {word}"),
+                2001,
+            ),
+            film("Jean-Pierre Jeunet", &format!("Alien {word}"), 1997),
+        ],
+    )
+    .await;
+    let prefix = word[..12].to_uppercase();
+
+    let hits = repo
+        .search_films(&search(&format!("amel {prefix}"), 10, 0))
+        .await
+        .unwrap();
+    assert_eq!(hit_ids(&hits), [films[0].id]);
+
+    let hits = repo
+        .search_films(&search(&format!("{prefix} JEAN"), 10, 0))
+        .await
+        .unwrap();
+    let mut found = hit_ids(&hits);
+    found.sort();
+    let mut all = ids(&films);
+    all.sort();
+    assert_eq!(found, all);
+
+    // terms only match the start of words, and every one must match
+    for q in [format!("melie {word}"), format!("{word} resurrection")] {
+        let hits = repo.search_films(&search(&q, 10, 0)).await.unwrap();
+        assert!(hits.is_empty(), "{:?} found {:?}", q, hits);
+    }
+
+    repo.delete_film(&films[0].id, None).await.unwrap();
+    let hits = repo
+        .search_films(&search(&format!("amelie {word}"), 10, 0))
+        .await
+        .unwrap();
+    assert!(hits.is_empty());
+}
+
+pub async fn search_ranks_title_matches_first<R: FilmRepository>(repo: &R) {
+    let word = search_marker();
+    let films = create_all(
+        repo,
+        &[
+            film(&format!("Michael {word}"), "Heat", 1995),
+            film("Someone", &format!("{word} Hunt"), 1995),
+        ],
+    )
+    .await;
+
+    let hits = repo.search_films(&search(&word, 10, 0)).await.unwrap();
+    assert_eq!(hit_ids(&hits), [films[1].id, films[0].id]);
+    assert!(hits[0].score > hits[1].score, "{:?}", hits);
+
+    // moving the word from the director to the title moves the film up
+    let mut renamed = films[0].clone();
+    renamed.title = format!("{word} Heat");
+    renamed.director = "Michael Mann".to_string();
+    repo.update_film(&renamed, None).await.unwrap();
+    let director_score = hits[1].score;
+
+    let hits = repo.search_films(&search(&word, 10, 0)).await.unwrap();
+    let renamed = hits.iter().find(|hit| hit.film.id == films[0].id).unwrap();
+    assert!(renamed.score > director_score, "{:?}", hits);
+}
+
+pub async fn search_pages_partition_the_results<R: FilmRepository>(repo: &R) {
+    let word = search_marker();
+    let films = create_all(
+        repo,
+        &["Heat", "Collateral", "Thief", "Ali", "Manhunter"]
+            .map(|title| film("Michael Mann", &format!("{title} {word}"), 1995)),
+    )
+    .await;
+
+    let all = repo.search_films(&search(&word, 100, 0)).await.unwrap();
+    assert_eq!(all.len(), films.len());
+
+    let mut paged = vec![];
+    for offset in (0..films.len() as u32 + 2).step_by(2) {
+        paged.extend(repo.search_films(&search(&word, 2, offset)).await.unwrap());
+    }
+    assert_eq!(hit_ids(&paged), hit_ids(&all));
+}
+
+pub async fn suggestions_count_distinct_values<R: FilmRepository>(repo: &R) {
+    let word = search_marker();
+    create_all(
+        repo,
+        &[
+            film(&format!("{word} Mann"), "Heat", 1995),
+            film(&format!("{word} Nolan"), "Memento", 2000),
+            film(&format!("{word} Mann"), "Thief", 1981),
+            film(&format!("{word} Mann_"), "Ali", 2001),
+            film("Someone", &format!("{word} 100%"), 2001),
+        ],
+    )
+    .await;
+    let suggest = |field, prefix: String, limit| {
+        SuggestFilms::from_query(&FilmSuggestQuery {
+            field,
+            prefix,
+            limit: Some(limit),
+        })
+        .unwrap()
+    };
+    let suggestion = |value: String, count| FilmSuggestion { value, count };
+
+    let found = repo
+        .suggest_films(&suggest(SuggestField::Director, word.to_uppercase(), 10))
+        .await
+        .unwrap();
+    assert_eq!(
+        found,
+        [
+            suggestion(format!("{word} Mann"), 2),
+            suggestion(format!("{word} Mann_"), 1),
+            suggestion(format!("{word} Nolan"), 1),
+        ]
+    );
+
+    // LIKE wildcards in the prefix are matched literally
+    let found = repo
+        .suggest_films(&suggest(
+            SuggestField::Director,
+            format!("{word} Mann_"),
+            10,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(found, [suggestion(format!("{word} Mann_"), 1)]);
+    let found = repo
+        .suggest_films(&suggest(SuggestField::Title, format!("{word} 1%"), 10))
+        .await
+        .unwrap();
+    assert!(found.is_empty(), "{:?}", found);
+
+    let found = repo
+        .suggest_films(&suggest(SuggestField::Director, word.clone(), 1))
+        .await
+        .unwrap();
+    assert_eq!(found, [suggestion(format!("{word} Mann"), 2)]);
+}
+
+pub async fn api_keys_are_found_by_key_until_revoked<K: ApiKeyRepository>(api_keys: &K) {
+    let created = api_keys
+        .create_api_key(&CreateApiKey {
+            name: marker(),
+            scope: ApiKeyScope::Write,
+        })
+        .await
+        .unwrap();
+    assert!(created.key.starts_with(&created.api_key.prefix));
+    assert_eq!(created.api_key.last_used_at, None);
+
+    let listed = api_keys.list_api_keys().await.unwrap();
+    assert!(listed.contains(&created.api_key));
+
+    let used = api_keys.use_api_key(&created.key).await.unwrap().unwrap();
+    assert_eq!(used.id, created.api_key.id);
+    assert_eq!(used.scope, ApiKeyScope::Write);
+    assert!(used.last_used_at.is_some());
+    let reused = api_keys.use_api_key(&created.key).await.unwrap().unwrap();
+    assert_eq!(reused.last_used_at, used.last_used_at);
+    let wrong = format!("{}x", created.key);
+    assert_eq!(api_keys.use_api_key(&wrong).await.unwrap(), None);
+
+    let revoked = api_keys.revoke_api_key(&created.api_key.id).await.unwrap();
+    assert!(revoked.revoked_at.is_some());
+    assert_eq!(revoked.last_used_at, used.last_used_at);
+    assert_eq!(api_keys.use_api_key(&created.key).await.unwrap(), None);
+    let again = api_keys.revoke_api_key(&created.api_key.id).await.unwrap();
+    assert_eq!(again.revoked_at, revoked.revoked_at);
+
+    let missing = Uuid::new_v4();
+    assert!(matches!(
+        api_keys.revoke_api_key(&missing).await,
+        Err(ApiKeyError::NotFound(id)) if id == missing
+    ));
+}
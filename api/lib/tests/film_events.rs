@@ -0,0 +1,209 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::{http::StatusCode, web, App};
+use api_lib::api_key_repository::MemoryApiKeyRepository;
+use api_lib::app::configure;
+use api_lib::film_repository::FilmRepository;
+use api_lib::film_repository::{FilmEvents, MemoryFilmRepository, PublishingFilmRepository};
+use api_lib::settings::Settings;
+use shared::models::{CreateFilm, EventId, Film, FilmChangeKind, FilmEvent};
+
+mod common;
+
+type Repo = PublishingFilmRepository<MemoryFilmRepository>;
+
+fn settings() -> Settings {
+    let mut settings = common::settings();
+    settings.auth.anonymous_writes = true;
+    settings
+}
+
+fn publishing(replay: usize) -> web::Data<Repo> {
+    web::Data::new(PublishingFilmRepository::new(
+        MemoryFilmRepository::new(),
+        Arc::new(FilmEvents::new(replay)),
+    ))
+}
+
+fn test_film(title: &str) -> CreateFilm {
+    CreateFilm {
+        title: title.to_string(),
+        ..common::film()
+    }
+}
+
+/// Reads whole SSE messages off a streaming body.
+struct Events<B> {
+    body: Pin<Box<B>>,
+    buffer: String,
+}
+
+impl<B: MessageBody> Events<B>
+where
+    B::Error: std::fmt::Debug,
+{
+    fn new(body: B) -> Self {
+        Self {
+            body: Box::pin(body),
+            buffer: String::new(),
+        }
+    }
+
+    /// Next message that isn't a comment or the retry hint.
+    async fn next(&mut self) -> String {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let message = self.buffer[..end].to_string();
+                self.buffer.drain(..end + 2);
+                if message.starts_with(':') || message.starts_with("retry:") {
+                    continue;
+                }
+                return message;
+            }
+
+            let body = &mut self.body;
+            let chunk = tokio::time::timeout(
+                Duration::from_secs(5),
+                std::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+            )
+            .await
+            .expect("an event within 5s")
+            .expect("the stream stays open")
+            .unwrap();
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    async fn next_event(&mut self) -> (String, FilmEvent) {
+        let message = self.next().await;
+        let field = |name: &str| {
+            message
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .unwrap_or_else(|| panic!("no {} in {:?}", name, message))
+                .to_string()
+        };
+        let event: FilmEvent = serde_json::from_str(&field("data: ")).unwrap();
+        assert_eq!(field("id: "), event.id.to_string());
+        (field("event: "), event)
+    }
+}
+
+fn events_request(last_event_id: Option<&str>) -> actix_web::test::TestRequest {
+    let req = actix_web::test::TestRequest::get().uri("/api/v1/films/events");
+    match last_event_id {
+        Some(id) => req.insert_header(("Last-Event-ID", id)),
+        None => req,
+    }
+}
+
+fn stream(res: ServiceResponse) -> Events<BoxBody> {
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()
+            .get("content-type")
+            .and_then(|h| h.to_str().ok()),
+        Some("text/event-stream")
+    );
+    Events::new(res.into_body())
+}
+
+#[actix_rt::test]
+async fn writes_are_streamed_as_they_happen() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings()),
+        publishing(10),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+    let res = actix_web::test::call_service(&app, events_request(None).to_request()).await;
+    let mut events = stream(res);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/v1/films")
+        .set_json(test_film("Heat"))
+        .to_request();
+    let film: Film = actix_web::test::call_and_read_body_json(&app, req).await;
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/api/v1/films/{}", film.id))
+        .to_request();
+    actix_web::test::call_service(&app, req).await;
+
+    let (name, event) = events.next_event().await;
+    assert_eq!(name, "created");
+    assert_eq!((event.id.seq, event.kind), (1, FilmChangeKind::Created));
+    assert_eq!(event.film, film);
+
+    let (name, event) = events.next_event().await;
+    assert_eq!(name, "deleted");
+    assert_eq!(event.id.seq, 2);
+    assert_eq!(event.film.id, film.id);
+}
+
+#[actix_rt::test]
+async fn last_event_id_resumes_from_the_replay_buffer() {
+    let repo = publishing(2);
+    let app = App::new().configure(configure(
+        web::Data::new(settings()),
+        repo.clone(),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+    for title in ["Heat", "Thief", "Collateral"] {
+        repo.create_film(&test_film(title)).await.unwrap();
+    }
+    let epoch = repo.events().unwrap().last_id().epoch;
+    let id = |seq| EventId { epoch, seq }.to_string();
+
+    let res = actix_web::test::call_service(&app, events_request(Some(&id(1))).to_request()).await;
+    let mut events = stream(res);
+    assert_eq!(events.next_event().await.1.film.title, "Thief");
+    assert_eq!(events.next_event().await.1.film.title, "Collateral");
+    repo.create_film(&test_film("Ali")).await.unwrap();
+    assert_eq!(events.next_event().await.1.id.to_string(), id(4));
+
+    // event 1 is gone by now, so a client that saw none must reload first
+    let res = actix_web::test::call_service(&app, events_request(Some(&id(0))).to_request()).await;
+    let mut events = stream(res);
+    assert_eq!(
+        events.next().await,
+        format!("id: {}\nevent: resync\ndata: {{}}", id(2))
+    );
+    assert_eq!(events.next_event().await.1.id.to_string(), id(3));
+    assert_eq!(events.next_event().await.1.id.to_string(), id(4));
+
+    // ids handed out by another instance, or before a restart, mean nothing
+    // here even where their numbers do
+    let other = EventId {
+        epoch: epoch.wrapping_add(1),
+        seq: 3,
+    };
+    for last_event_id in [other.to_string(), "4".to_string()] {
+        let req = events_request(Some(&last_event_id)).to_request();
+        let mut events = stream(actix_web::test::call_service(&app, req).await);
+        assert_eq!(
+            events.next().await,
+            format!("id: {}\nevent: resync\ndata: {{}}", id(4))
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn repositories_without_events_have_no_stream() {
+    let app = App::new().configure(configure(
+        web::Data::new(settings()),
+        web::Data::new(MemoryFilmRepository::new()),
+        web::Data::new(MemoryApiKeyRepository::new()),
+    ));
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/films/events")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
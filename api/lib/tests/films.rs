@@ -0,0 +1,677 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{http::StatusCode, web, App};
+use api_lib::film_repository::{CursorCodec, FilmRepository, MemoryFilmRepository};
+use api_lib::films::{service, FilmCachePolicy};
+use api_lib::problem::RequestId;
+use api_lib::settings::Settings;
+use shared::models::{CreateFilm, Film, FilmPage, FilmSearchPage, FilmSuggestions};
+use shared::problem::{ProblemDetails, PROBLEM_JSON};
+
+fn content_type<B>(res: &actix_web::dev::ServiceResponse<B>) -> Option<&str> {
+    res.headers()
+        .get("content-type")
+        .and_then(|h| h.to_str().ok())
+}
+
+/// Settings letting requests without credentials write, as the routes
+/// here are served without [`api_lib::auth::Authentication`].
+fn anonymous_writes() -> web::Data<Settings> {
+    let mut settings = Settings::default();
+    settings.auth.anonymous_writes = true;
+    web::Data::new(settings)
+}
+
+fn test_film() -> CreateFilm {
+    CreateFilm {
+        title: String::from("Star Wars: The Force Awakens"),
+        director: String::from("J. J. Abrams"),
+        year: 2015,
+        poster: String::from("https://example.com/posters/force-awakens.jpg"),
+    }
+}
+
+#[actix_rt::test]
+async fn get_unknown_film_returns_not_found() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/v1/films/{}", uuid::Uuid::new_v4()))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.status, 404);
+    assert_eq!(problem.problem_type, "/problems/film-not-found");
+}
+
+#[actix_rt::test]
+async fn put_unknown_film_returns_not_found() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::put()
+        .uri(&format!("/v1/films/{}", uuid::Uuid::new_v4()))
+        .set_json(test_film())
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn created_film_can_be_fetched() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/films")
+        .set_json(test_film())
+        .to_request();
+    let created: Film = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/v1/films/{}", created.id))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let stored = repo.get_film(&created.id).await.unwrap();
+    assert_eq!(stored, created);
+}
+
+#[actix_rt::test]
+async fn malformed_body_is_reported_as_problem_with_request_id() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .wrap(RequestId)
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/films")
+        .insert_header(("content-type", "application/json"))
+        .insert_header(("x-request-id", "req-42"))
+        .set_payload(r#"{"title": "Heat"}"#)
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+    assert_eq!(
+        res.headers()
+            .get("x-request-id")
+            .and_then(|h| h.to_str().ok()),
+        Some("req-42")
+    );
+
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.problem_type, "/problems/malformed-body");
+    assert_eq!(problem.request_id.as_deref(), Some("req-42"));
+    assert!(problem.detail.unwrap().contains("director"));
+}
+
+#[actix_rt::test]
+async fn invalid_film_id_is_reported_as_problem() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .wrap(RequestId)
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/not-a-uuid")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+    let generated_id = res
+        .headers()
+        .get("x-request-id")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_owned);
+    assert!(generated_id.is_some());
+
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.status, 404);
+    assert_eq!(problem.request_id, generated_id);
+}
+
+#[actix_rt::test]
+async fn films_are_listed_in_pages() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    for year in [2003, 2001, 2002] {
+        let film = CreateFilm {
+            year,
+            ..test_film()
+        };
+        repo.create_film(&film).await.unwrap();
+    }
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?sort=year&order=desc&limit=2")
+        .to_request();
+    let page: FilmPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let years = page.items.iter().map(|f| f.year).collect::<Vec<_>>();
+    assert_eq!(years, vec![2003, 2002]);
+    assert_eq!(page.next_offset, Some(2));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?sort=year&order=desc&limit=2&offset=2")
+        .to_request();
+    let page: FilmPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let years = page.items.iter().map(|f| f.year).collect::<Vec<_>>();
+    assert_eq!(years, vec![2001]);
+    assert_eq!(page.next_offset, None);
+}
+
+#[actix_rt::test]
+async fn invalid_list_parameters_are_rejected() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?sort=rating")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?limit=1000")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_rt::test]
+async fn films_are_searched_in_pages_of_ranked_hits() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    for (title, director) in [
+        ("Amélie", "Jean-Pierre Jeunet"),
+        ("Heat", "Michael Mann"),
+        ("Mann Hunt", "Someone"),
+    ] {
+        let film = CreateFilm {
+            title: title.to_string(),
+            director: director.to_string(),
+            ..test_film()
+        };
+        repo.create_film(&film).await.unwrap();
+    }
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/search?q=MAN&limit=1")
+        .to_request();
+    let page: FilmSearchPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let titles = page.items.iter().map(|hit| hit.film.title.as_str());
+    assert_eq!(titles.collect::<Vec<_>>(), ["Mann Hunt"]);
+    assert_eq!(page.next_offset, Some(1));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/search?q=MAN&limit=1&offset=1")
+        .to_request();
+    let page: FilmSearchPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let titles = page.items.iter().map(|hit| hit.film.title.as_str());
+    assert_eq!(titles.collect::<Vec<_>>(), ["Heat"]);
+    assert_eq!(page.next_offset, None);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/search?q=amelie")
+        .to_request();
+    let page: FilmSearchPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page.items.len(), 1);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/search?q=%20-")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+}
+
+#[actix_rt::test]
+async fn suggestions_are_distinct_and_most_used_first() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    for director in ["J. J. Abrams", "Jon Favreau", "J. J. Abrams", "Jon Watts"] {
+        let film = CreateFilm {
+            director: director.to_string(),
+            ..test_film()
+        };
+        repo.create_film(&film).await.unwrap();
+    }
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/suggest?field=director&prefix=j")
+        .to_request();
+    let suggestions: FilmSuggestions = actix_web::test::call_and_read_body_json(&app, req).await;
+    let values = suggestions
+        .items
+        .iter()
+        .map(|s| (s.value.as_str(), s.count));
+    assert_eq!(
+        values.collect::<Vec<_>>(),
+        [("J. J. Abrams", 2), ("Jon Favreau", 1), ("Jon Watts", 1)]
+    );
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/suggest?field=director&prefix=JON%20W")
+        .to_request();
+    let suggestions: FilmSuggestions = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(suggestions.items.len(), 1);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/suggest?field=year&prefix=2")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films/suggest?field=title&prefix=")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_rt::test]
+async fn films_are_listed_by_filter_expression() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    for (title, year) in [
+        ("Batman Begins", 2005),
+        ("Inception", 2010),
+        ("Memento", 2000),
+    ] {
+        let film = CreateFilm {
+            title: title.to_string(),
+            director: "Christopher Nolan".to_string(),
+            year,
+            ..test_film()
+        };
+        repo.create_film(&film).await.unwrap();
+    }
+    let app = App::new()
+        .app_data(repo)
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?sort=year&filter=director%3A%22Nolan%22%20year%3A%3E%3D2000%20-title%3Abatman")
+        .to_request();
+    let page: FilmPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let titles = page
+        .items
+        .iter()
+        .map(|f| f.title.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["Memento", "Inception"]);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?filter=year%3A%3E%3D2000%20rating%3A5")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.errors[0].field, "filter");
+    assert!(problem.errors[0].message.ends_with("at position 12"));
+}
+
+#[actix_rt::test]
+async fn cursor_walk_does_not_repeat_or_skip_films_inserted_meanwhile() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    for title in ["B", "D", "F"] {
+        let film = CreateFilm {
+            title: title.to_string(),
+            ..test_film()
+        };
+        repo.create_film(&film).await.unwrap();
+    }
+    let app = App::new()
+        .app_data(repo.clone())
+        .app_data(web::Data::new(CursorCodec::new("test-secret")))
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?sort=title&limit=2")
+        .to_request();
+    let page: FilmPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let titles = page
+        .items
+        .iter()
+        .map(|f| f.title.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec!["B", "D"]);
+
+    // a film sorting before the cursor would shift an offset based walk
+    let film = CreateFilm {
+        title: "A".to_string(),
+        ..test_film()
+    };
+    repo.create_film(&film).await.unwrap();
+
+    let next = page.next.expect("first page has a next cursor");
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/v1/films?limit=2&cursor={}", next))
+        .to_request();
+    let page: FilmPage = actix_web::test::call_and_read_body_json(&app, req).await;
+    let titles = page
+        .items
+        .iter()
+        .map(|f| f.title.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec!["F"]);
+    assert_eq!(page.next, None);
+    assert_eq!(page.next_offset, None);
+}
+
+#[actix_rt::test]
+async fn tampered_cursor_is_rejected() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(repo)
+        .app_data(web::Data::new(CursorCodec::new("test-secret")))
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films?cursor=eyJzb3J0IjoieWVhciJ9.c2lnbmF0dXJl")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+}
+
+#[actix_rt::test]
+async fn invalid_film_is_rejected_with_every_offending_field() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let film = CreateFilm {
+        title: String::new(),
+        director: String::new(),
+        year: 65535,
+        poster: String::from("AWAKEN THE FORCE WITHIN"),
+    };
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/films")
+        .set_json(film)
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+
+    let problem: ProblemDetails = actix_web::test::read_body_json(res).await;
+    assert_eq!(problem.problem_type, "/problems/validation-failed");
+    let mut fields = problem
+        .errors
+        .iter()
+        .map(|e| e.field.as_str())
+        .collect::<Vec<_>>();
+    fields.sort();
+    assert_eq!(fields, vec!["director", "poster", "title", "year"]);
+
+    let films = repo
+        .get_films(&api_lib::film_repository::ListFilms::default())
+        .await
+        .unwrap();
+    assert!(films.is_empty());
+}
+
+#[actix_rt::test]
+async fn put_uses_the_path_id() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let created = repo.create_film(&test_film()).await.unwrap();
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let body = Film {
+        id: uuid::Uuid::new_v4(),
+        title: String::from("Star Wars: The Last Jedi"),
+        director: String::from("Rian Johnson"),
+        year: 2017,
+        poster: String::new(),
+        ..Default::default()
+    };
+    let req = actix_web::test::TestRequest::put()
+        .uri(&format!("/v1/films/{}", created.id))
+        .set_json(&body)
+        .to_request();
+    let updated: Film = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.title, body.title);
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(repo.get_film(&body.id).await.is_err());
+}
+
+#[actix_rt::test]
+async fn patch_changes_only_the_supplied_fields() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let created = repo.create_film(&test_film()).await.unwrap();
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/v1/films/{}", created.id))
+        .insert_header(("content-type", "application/merge-patch+json"))
+        .set_payload(r#"{"year": 2016, "poster": null}"#)
+        .to_request();
+    let patched: Film = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(patched.year, 2016);
+    assert_eq!(patched.poster, "");
+    assert_eq!(patched.title, created.title);
+    assert_eq!(patched.director, created.director);
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/v1/films/{}", created.id))
+        .set_json(serde_json::json!({ "title": null }))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_rt::test]
+async fn stale_if_match_is_rejected_with_precondition_failed() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let created = repo.create_film(&test_film()).await.unwrap();
+    let app = App::new()
+        .app_data(anonymous_writes())
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/v1/films/{}", created.id))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    let etag = res
+        .headers()
+        .get("etag")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_owned)
+        .expect("film responses carry an ETag");
+
+    // first editor wins and gets a new tag
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/v1/films/{}", created.id))
+        .insert_header(("if-match", etag.as_str()))
+        .set_json(serde_json::json!({ "year": 2016 }))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_ne!(
+        res.headers().get("etag").and_then(|h| h.to_str().ok()),
+        Some(etag.as_str())
+    );
+
+    // second editor still holds the old tag
+    let req = actix_web::test::TestRequest::put()
+        .uri(&format!("/v1/films/{}", created.id))
+        .insert_header(("if-match", etag.as_str()))
+        .set_json(test_film())
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(content_type(&res), Some(PROBLEM_JSON));
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/v1/films/{}", created.id))
+        .insert_header(("if-match", etag.as_str()))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+    let stored = repo.get_film(&created.id).await.unwrap();
+    assert_eq!(stored.year, 2016);
+}
+
+fn header<'a, B>(res: &'a actix_web::dev::ServiceResponse<B>, name: &str) -> Option<&'a str> {
+    res.headers().get(name).and_then(|h| h.to_str().ok())
+}
+
+#[actix_rt::test]
+async fn unchanged_film_is_revalidated_with_not_modified() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let created = repo.create_film(&test_film()).await.unwrap();
+    let app = App::new()
+        .app_data(repo.clone())
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let uri = format!("/v1/films/{}", created.id);
+    let req = actix_web::test::TestRequest::get().uri(&uri).to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(header(&res, "cache-control"), Some("no-cache"));
+    let etag = header(&res, "etag").unwrap().to_owned();
+    let last_modified = header(&res, "last-modified").unwrap().to_owned();
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-none-match", format!("W/{}", etag)))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(header(&res, "etag"), Some(etag.as_str()));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-modified-since", last_modified.as_str()))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    // a matching date doesn't override a stale tag
+    repo.patch_film(
+        &created.id,
+        &serde_json::from_value(serde_json::json!({ "year": 2016 })).unwrap(),
+        None,
+    )
+    .await
+    .unwrap();
+    let req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-none-match", etag.as_str()))
+        .insert_header(("if-modified-since", "Fri, 31 Dec 2100 23:59:59 GMT"))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn list_validator_changes_with_any_film() {
+    let repo = web::Data::new(MemoryFilmRepository::new());
+    let created = repo.create_film(&test_film()).await.unwrap();
+    let app = App::new()
+        .app_data(repo.clone())
+        .app_data(web::Data::new(FilmCachePolicy {
+            list: CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(60)]),
+            ..Default::default()
+        }))
+        .configure(service::<MemoryFilmRepository>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films")
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(header(&res, "cache-control"), Some("public, max-age=60"));
+    let etag = header(&res, "etag").unwrap().to_owned();
+    assert!(etag.starts_with("W/"));
+
+    let revalidate = || {
+        actix_web::test::TestRequest::get()
+            .uri("/v1/films")
+            .insert_header(("if-none-match", etag.as_str()))
+            .to_request()
+    };
+    let res = actix_web::test::call_service(&app, revalidate()).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    repo.update_film(
+        &Film {
+            year: 2016,
+            ..created.clone()
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    let res = actix_web::test::call_service(&app, revalidate()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let etag = header(&res, "etag").unwrap().to_owned();
+    repo.delete_film(&created.id, None).await.unwrap();
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/films")
+        .insert_header(("if-none-match", etag.as_str()))
+        .to_request();
+    let res = actix_web::test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
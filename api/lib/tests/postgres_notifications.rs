@@ -0,0 +1,182 @@
+//! Film change notifications through Postgres `LISTEN`/`NOTIFY`.
+//!
+//! Skipped unless `DATABASE_URL` points at a server the tests may write to.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use api_lib::film_repository::{
+    CacheConfig, CachedFilmRepository, FilmChange, FilmChangeKind, FilmEvents, FilmNotification,
+    FilmRepository, LiveFilmEvent, PostgresFilmListener, PostgresFilmRepository,
+    PublishingFilmRepository,
+};
+use shared::models::{CreateFilm, Film};
+use tokio::sync::broadcast;
+
+mod common;
+
+async fn pool() -> Option<sqlx::PgPool> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    let pool = sqlx::PgPool::connect(&url)
+        .await
+        .expect("DATABASE_URL is set but the server can't be reached");
+    api_lib::migrations::run(&pool)
+        .await
+        .expect("migrations apply");
+    Some(pool)
+}
+
+fn create_film() -> CreateFilm {
+    CreateFilm {
+        director: format!("notifications-{}", uuid::Uuid::new_v4()),
+        ..common::film()
+    }
+}
+
+/// Next change to film `id`, skipping other tests' writes.
+async fn next_change(
+    notifications: &mut broadcast::Receiver<FilmNotification>,
+    id: uuid::Uuid,
+) -> FilmChange {
+    let wait = async {
+        loop {
+            match notifications.recv().await.unwrap() {
+                FilmNotification::Changed(change) if change.id == id => return *change,
+                _ => continue,
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait)
+        .await
+        .expect("notified within 5s")
+}
+
+#[actix_rt::test]
+async fn every_write_is_announced() {
+    let Some(pool) = pool().await else {
+        return;
+    };
+    let listener = PostgresFilmListener::start(&pool).await.unwrap();
+    let mut notifications = listener.subscribe();
+    let repo = PostgresFilmRepository::new(pool);
+
+    let film = repo.create_film(&create_film()).await.unwrap();
+    let updated = repo.update_film(&film, None).await.unwrap().film;
+    repo.delete_film(&film.id, None).await.unwrap();
+
+    for (kind, written, previous) in [
+        (FilmChangeKind::Created, &film, None),
+        (FilmChangeKind::Updated, &updated, Some(&film)),
+        (FilmChangeKind::Deleted, &updated, None),
+    ] {
+        let change = next_change(&mut notifications, film.id).await;
+        assert_eq!(
+            change,
+            FilmChange {
+                kind,
+                id: film.id,
+                version: written.version,
+                film: Some(written.clone()),
+                previous: previous.cloned(),
+            }
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn events_stream_writes_of_every_instance_once() {
+    let Some(pool) = pool().await else {
+        return;
+    };
+    let listener = PostgresFilmListener::start(&pool).await.unwrap();
+    let events = Arc::new(FilmEvents::new(100));
+    let mut live = events.subscribe(None).receiver;
+    tokio::spawn({
+        let events = events.clone();
+        let notifications = listener.subscribe();
+        async move { events.follow(notifications).await }
+    });
+    let local = PublishingFilmRepository::new(PostgresFilmRepository::new(pool.clone()), events);
+    let other = PostgresFilmRepository::new(pool);
+
+    let film = local.create_film(&create_film()).await.unwrap();
+    let id = film.id;
+    let renamed = Film {
+        title: "Thief".to_string(),
+        ..film.clone()
+    };
+    let renamed = other.update_film(&renamed, None).await.unwrap().film;
+    local.delete_film(&film.id, None).await.unwrap();
+
+    let mut seen = vec![];
+    while seen.len() < 3 {
+        let next = tokio::time::timeout(Duration::from_secs(5), live.recv());
+        match next.await.expect("an event within 5s").unwrap() {
+            LiveFilmEvent::Event(event) if event.film.id == id => {
+                seen.push((event.kind, event.film, event.previous));
+            }
+            LiveFilmEvent::Event(_) => continue,
+            resync => panic!("unexpected {:?}", resync),
+        }
+    }
+    assert_eq!(
+        seen,
+        [
+            (FilmChangeKind::Created, film.clone(), None),
+            (FilmChangeKind::Updated, renamed.clone(), Some(film)),
+            (FilmChangeKind::Deleted, renamed, None),
+        ]
+    );
+
+    // the notifications of the local writes came too, and were dropped
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    while let Ok(event) = live.try_recv() {
+        if let LiveFilmEvent::Event(event) = event {
+            assert_ne!(event.film.id, id);
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn caches_drop_films_written_by_other_instances() {
+    let Some(pool) = pool().await else {
+        return;
+    };
+    let listener = PostgresFilmListener::start(&pool).await.unwrap();
+    let config = CacheConfig {
+        ttl: Duration::from_secs(600),
+        ..Default::default()
+    };
+    let writer = CachedFilmRepository::new(PostgresFilmRepository::new(pool.clone()), config);
+    let reader = Arc::new(CachedFilmRepository::new(
+        PostgresFilmRepository::new(pool),
+        config,
+    ));
+    tokio::spawn({
+        let reader = reader.clone();
+        let notifications = listener.subscribe();
+        async move { reader.follow(notifications).await }
+    });
+
+    let film = writer.create_film(&create_film()).await.unwrap();
+    assert_eq!(reader.get_film(&film.id).await.unwrap(), film);
+
+    let mut notifications = listener.subscribe();
+    let mut renamed = film.clone();
+    renamed.title = "Thief".to_string();
+    let renamed = writer.update_film(&renamed, None).await.unwrap().film;
+    next_change(&mut notifications, film.id).await;
+
+    // the reader's follower got the same notification; give it a moment
+    let mut seen = reader.get_film(&film.id).await.unwrap();
+    for _ in 0..50 {
+        if seen == renamed {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        seen = reader.get_film(&film.id).await.unwrap();
+    }
+    assert_eq!(seen, renamed);
+
+    writer.delete_film(&film.id, None).await.unwrap();
+}
@@ -0,0 +1,67 @@
+//! Runs the conformance suites in `conformance/` against every backend.
+//!
+//! The memory and SQLite repositories are always tested, the latter in an
+//! in-memory database, as is the memory film repository behind the cache;
+//! Postgres only when `DATABASE_URL` points at a server the tests may write
+//! to, in a UTF-8 database with a UTF-8 `LC_CTYPE` so that `lower()` folds
+//! more than ASCII.
+
+#[macro_use]
+mod conformance;
+
+use api_lib::api_key_repository::{
+    MemoryApiKeyRepository, PostgresApiKeyRepository, SqliteApiKeyRepository,
+};
+use api_lib::film_repository::{
+    CacheConfig, CachedFilmRepository, MemoryFilmRepository, PostgresFilmRepository,
+    SqliteFilmRepository,
+};
+use sqlx::sqlite::SqlitePoolOptions;
+
+async fn postgres() -> Option<sqlx::PgPool> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    let pool = sqlx::PgPool::connect(&url)
+        .await
+        .expect("DATABASE_URL is set but the server can't be reached");
+    api_lib::migrations::run(&pool)
+        .await
+        .expect("migrations apply");
+    Some(pool)
+}
+
+async fn sqlite() -> Option<sqlx::SqlitePool> {
+    // every connection to `:memory:` opens its own database, so keep just one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("in-memory database opens");
+    api_lib::migrations::run(&pool)
+        .await
+        .expect("migrations apply");
+    Some(pool)
+}
+
+film_repository_conformance!(memory, async { Some(MemoryFilmRepository::new()) });
+film_repository_conformance!(postgres, async {
+    postgres().await.map(PostgresFilmRepository::new)
+});
+film_repository_conformance!(cached_memory, async {
+    Some(CachedFilmRepository::new(
+        MemoryFilmRepository::new(),
+        CacheConfig::default(),
+    ))
+});
+film_repository_conformance!(sqlite, async {
+    sqlite().await.map(SqliteFilmRepository::new)
+});
+
+api_key_repository_conformance!(memory_api_keys, async {
+    Some(MemoryApiKeyRepository::new())
+});
+api_key_repository_conformance!(postgres_api_keys, async {
+    postgres().await.map(PostgresApiKeyRepository::new)
+});
+api_key_repository_conformance!(sqlite_api_keys, async {
+    sqlite().await.map(SqliteApiKeyRepository::new)
+});
@@ -0,0 +1,140 @@
+//! The `/api/v1/ws` protocol over a real socket.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{web, App, HttpServer};
+use api_lib::api_key_repository::MemoryApiKeyRepository;
+use api_lib::app::configure;
+use api_lib::film_repository::{
+    FilmEvents, FilmRepository, MemoryFilmRepository, PublishingFilmRepository,
+};
+use futures_util::{SinkExt, StreamExt};
+use shared::models::FilmChangeKind;
+use shared::ws::{ClientMessage, Editor, Envelope, ServerMessage, Topic};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+mod common;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type Repo = PublishingFilmRepository<MemoryFilmRepository>;
+
+/// Serves the app on a free port, returning the socket URL.
+fn serve(repo: web::Data<Repo>) -> String {
+    let config = configure(
+        web::Data::new(common::settings()),
+        repo,
+        web::Data::new(MemoryApiKeyRepository::new()),
+    );
+
+    let server = HttpServer::new(move || App::new().configure(config.clone()))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let url = format!("ws://{}/api/v1/ws", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    url
+}
+
+async fn connect(url: &str) -> (Socket, uuid::Uuid) {
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    match receive(&mut socket).await {
+        ServerMessage::Welcome { connection } => (socket, connection),
+        other => panic!("expected a welcome, got {:?}", other),
+    }
+}
+
+async fn send(socket: &mut Socket, message: ClientMessage) {
+    let text = serde_json::to_string(&Envelope::new(message)).unwrap();
+    socket.send(Message::Text(text)).await.unwrap();
+}
+
+async fn receive(socket: &mut Socket) -> ServerMessage {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("a message within 5s")
+            .expect("the socket stays open")
+            .unwrap();
+        if let Message::Text(text) = message {
+            let envelope: Envelope<ServerMessage> = serde_json::from_str(&text).unwrap();
+            assert_eq!(envelope.version, shared::ws::PROTOCOL_VERSION);
+            return envelope.message;
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn subscribers_see_changes_and_editors() {
+    let repo = web::Data::new(PublishingFilmRepository::new(
+        MemoryFilmRepository::new(),
+        Arc::new(FilmEvents::new(10)),
+    ));
+    let url = serve(repo.clone());
+    let film = repo.create_film(&common::film()).await.unwrap();
+
+    let (mut follower, _) = connect(&url).await;
+    send(
+        &mut follower,
+        ClientMessage::Subscribe {
+            subscription: "heat".to_string(),
+            topic: Topic::Films(vec![film.id]),
+        },
+    )
+    .await;
+    assert_eq!(
+        receive(&mut follower).await,
+        ServerMessage::Subscribed {
+            subscription: "heat".to_string()
+        }
+    );
+
+    let (mut editor, editor_id) = connect(&url).await;
+    send(
+        &mut editor,
+        ClientMessage::StartEditing {
+            film_id: film.id,
+            name: Some("Ann".to_string()),
+        },
+    )
+    .await;
+    assert_eq!(
+        receive(&mut follower).await,
+        ServerMessage::Presence {
+            film_id: film.id,
+            editors: vec![Editor {
+                connection: editor_id,
+                name: Some("Ann".to_string()),
+            }],
+        }
+    );
+
+    let mut renamed = film.clone();
+    renamed.title = "Thief".to_string();
+    let renamed = repo.update_film(&renamed, None).await.unwrap().film;
+    match receive(&mut follower).await {
+        ServerMessage::Changed {
+            subscription,
+            matching,
+            event,
+        } => {
+            assert_eq!(subscription, "heat");
+            assert!(matching);
+            assert_eq!(event.kind, FilmChangeKind::Updated);
+            assert_eq!(event.film, renamed);
+        }
+        other => panic!("expected a change, got {:?}", other),
+    }
+
+    // leaving stops the editing
+    editor.close(None).await.unwrap();
+    assert_eq!(
+        receive(&mut follower).await,
+        ServerMessage::Presence {
+            film_id: film.id,
+            editors: vec![],
+        }
+    );
+}
@@ -0,0 +1,165 @@
+//! Standalone API server, the same services as the Shuttle deployment
+//! without the Shuttle runtime.
+//!
+//! Configured through [`Settings`]: defaults, then `api.toml` (or the file
+//! named by `API_CONFIG`), then `API__<SECTION>__<KEY>` environment
+//! variables. A `sqlite:` database URL keeps films in a local file, created
+//! on first start. Without `database.url` (or `DATABASE_URL`) films are kept
+//! in memory, handy for local development; they are lost on exit unless
+//! `database.snapshot` names a file to save them to. API keys are lost on
+//! exit either way.
+
+use actix_web::web::{self, ServiceConfig};
+use actix_web::{App, HttpServer};
+use api_lib::api_key_repository::{
+    MemoryApiKeyRepository, PostgresApiKeyRepository, SqliteApiKeyRepository,
+};
+use api_lib::film_repository::{
+    CachedFilmRepository, FilmEvents, MemoryFilmRepository, PostgresFilmListener,
+    PostgresFilmRepository, PublishingFilmRepository, SqliteFilmRepository,
+};
+use api_lib::routes::version;
+use api_lib::settings::Settings;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+#[actix_web::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("api-server: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> std::io::Result<()> {
+    let settings = Settings::load().map_err(|e| other(e.to_string()))?;
+
+    let filter = EnvFilter::try_new(&settings.log.level)
+        .map_err(|e| other(format!("invalid log.level: {}", e)))?;
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    tracing::info!(?settings, "Loaded configuration");
+
+    let settings = web::Data::new(settings);
+    let bind_address = settings.server.bind_address.clone();
+    // every write made through this server, or through any other sharing
+    // its Postgres database, is streamed to its event clients
+    let events = Arc::new(FilmEvents::new(settings.api.event_replay));
+
+    match &settings.database.url {
+        Some(url) if url.expose().starts_with("sqlite:") => {
+            let options = SqliteConnectOptions::from_str(url.expose())
+                .map_err(|e| other(format!("invalid database.url: {}", e)))?
+                .create_if_missing(true);
+            let pool = SqlitePoolOptions::new()
+                .max_connections(settings.database.max_connections)
+                .min_connections(settings.database.min_connections)
+                .acquire_timeout(settings.database.acquire_timeout())
+                .connect_with(options)
+                .await
+                .map_err(|e| other(format!("can't open the database: {}", e)))?;
+            api_lib::migrations::run(&pool)
+                .await
+                .map_err(|e| other(format!("can't migrate the database: {}", e)))?;
+
+            let api_keys = web::Data::new(SqliteApiKeyRepository::new(pool.clone()));
+            let repo = web::Data::new(PublishingFilmRepository::new(
+                CachedFilmRepository::new(SqliteFilmRepository::new(pool), settings.cache.config()),
+                events,
+            ));
+            serve(
+                &bind_address,
+                api_lib::app::configure(settings.clone(), repo, api_keys),
+            )
+            .await
+        }
+        Some(url) => {
+            let pool = PgPoolOptions::new()
+                .max_connections(settings.database.max_connections)
+                .min_connections(settings.database.min_connections)
+                .acquire_timeout(settings.database.acquire_timeout())
+                .connect(url.expose())
+                .await
+                .map_err(|e| other(format!("can't connect to the database: {}", e)))?;
+            api_lib::migrations::run(&pool)
+                .await
+                .map_err(|e| other(format!("can't migrate the database: {}", e)))?;
+
+            let api_keys = web::Data::new(PostgresApiKeyRepository::new(pool.clone()));
+            let repo = web::Data::new(PublishingFilmRepository::new(
+                CachedFilmRepository::new(
+                    PostgresFilmRepository::new(pool.clone()),
+                    settings.cache.config(),
+                ),
+                events.clone(),
+            ));
+
+            // every instance's writes reach the event streams, and drop what
+            // this cache holds, through NOTIFY; the events task owns the
+            // listener, keeping it alive as long as the server
+            let listener = PostgresFilmListener::start(&pool)
+                .await
+                .map_err(|e| other(format!("can't listen for film changes: {}", e)))?;
+            if settings.cache.enabled {
+                let repo = repo.clone();
+                let notifications = listener.subscribe();
+                actix_web::rt::spawn(async move { repo.inner().follow(notifications).await });
+            }
+            let notifications = listener.subscribe();
+            actix_web::rt::spawn(async move {
+                let _listener = listener;
+                events.follow(notifications).await
+            });
+
+            let app = api_lib::app::configure(settings.clone(), repo, api_keys);
+            let pool = web::Data::new(pool);
+            serve(&bind_address, move |cfg: &mut ServiceConfig| {
+                cfg.app_data(pool.clone()).service(version);
+                app(cfg);
+            })
+            .await
+        }
+        None => {
+            let repo = match &settings.database.snapshot {
+                Some(path) => {
+                    tracing::info!(
+                        "No database configured, films are saved to {}",
+                        path.display()
+                    );
+                    MemoryFilmRepository::open(path, settings.database.snapshot_policy())
+                        .map_err(|e| other(format!("can't load the film snapshot: {}", e)))?
+                }
+                None => {
+                    tracing::warn!("No database configured, films are kept in memory only");
+                    Arc::new(MemoryFilmRepository::new())
+                }
+            };
+            serve(
+                &bind_address,
+                api_lib::app::configure(
+                    settings.clone(),
+                    web::Data::new(PublishingFilmRepository::new(repo, events)),
+                    web::Data::new(MemoryApiKeyRepository::new()),
+                ),
+            )
+            .await
+        }
+    }
+}
+
+async fn serve<F>(bind_address: &str, config: F) -> std::io::Result<()>
+where
+    F: Fn(&mut ServiceConfig) + Send + Clone + 'static,
+{
+    tracing::info!("Listening on {}", bind_address);
+    HttpServer::new(move || App::new().configure(config.clone()))
+        .bind(bind_address)?
+        .run()
+        .await
+}
+
+fn other(message: String) -> std::io::Error {
+    std::io::Error::other(message)
+}
@@ -1,40 +1,63 @@
 use actix_web::web::{self, ServiceConfig};
 use shuttle_actix_web::ShuttleActixWeb;
 use shuttle_runtime::CustomError;
-use sqlx::Executor;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use api_lib::film_repository::PostgresFilmRepository;
-use api_lib::routes::{hello_world, ping, version};
-use api_lib::{films, health};
+use api_lib::api_key_repository::PostgresApiKeyRepository;
+use api_lib::film_repository::{
+    CachedFilmRepository, FilmEvents, PostgresFilmListener, PostgresFilmRepository,
+    PublishingFilmRepository,
+};
+use api_lib::routes::version;
+use api_lib::settings::Settings;
 
 #[shuttle_runtime::main]
 async fn actix_web(
     #[shuttle_shared_db::Postgres()] pool: sqlx::PgPool,
     #[shuttle_static_folder::StaticFolder(folder = "static")] static_folder: PathBuf,
 ) -> ShuttleActixWeb<impl FnOnce(&mut ServiceConfig) + Send + Clone + 'static> {
-    // initialize the database if not already initialized
-    pool.execute(include_str!("../../db/schema.sql"))
+    // Shuttle provides the pool and static folder, the rest is configurable
+    let mut settings = Settings::load().map_err(CustomError::new)?;
+    settings.server.static_dir = static_folder;
+    tracing::info!(?settings, "Loaded configuration");
+
+    // bring the schema up to date, refusing a database migrated by a newer build
+    api_lib::migrations::run(&pool)
+        .await
+        .map_err(CustomError::new)?;
+
+    // every instance's writes reach the event streams, and drop what this
+    // cache holds, through NOTIFY; the events task owns the listener, keeping
+    // it alive as long as the service
+    let events = Arc::new(FilmEvents::new(settings.api.event_replay));
+    let film_repo = web::Data::new(PublishingFilmRepository::new(
+        CachedFilmRepository::new(
+            PostgresFilmRepository::new(pool.clone()),
+            settings.cache.config(),
+        ),
+        events.clone(),
+    ));
+    let listener = PostgresFilmListener::start(&pool)
         .await
         .map_err(CustomError::new)?;
+    if settings.cache.enabled {
+        let film_repo = film_repo.clone();
+        let notifications = listener.subscribe();
+        actix_web::rt::spawn(async move { film_repo.inner().follow(notifications).await });
+    }
+    let notifications = listener.subscribe();
+    actix_web::rt::spawn(async move {
+        let _listener = listener;
+        events.follow(notifications).await
+    });
 
-    let film_repo = api_lib::film_repository::PostgresFilmRepository::new(pool);
-    let film_repo = web::Data::new(film_repo);
+    let api_keys = web::Data::new(PostgresApiKeyRepository::new(pool.clone()));
+    let app = api_lib::app::configure(web::Data::new(settings), film_repo, api_keys);
+    let pool = web::Data::new(pool);
     let config = move |cfg: &mut ServiceConfig| {
-        cfg.service(
-            web::scope("/api")
-                .app_data(film_repo)
-                .configure(health::service)
-                .configure(films::service::<PostgresFilmRepository>),
-        )
-        .service(hello_world)
-        .service(ping)
-        .service(version)
-        .service(
-            actix_files::Files::new("/", static_folder)
-                .show_files_listing()
-                .index_file("index.html"),
-        );
+        cfg.app_data(pool).service(version);
+        app(cfg);
     };
 
     Ok(config.into())
@@ -0,0 +1,499 @@
+//! The filter language of `GET /api/v1/films?filter=...`.
+//!
+//! A filter is a list of conditions that must all hold, e.g.
+//!
+//! ```text
+//! director:"Nolan" year:>=2000 -title:batman
+//! ```
+//!
+//! - `title:word` and `director:"some words"` match values containing the
+//!   text, ignoring case; `title:="The Prestige"` matches the whole value.
+//! - `year:1995` matches that year; `year:>=2000`, `year:>`, `year:<=` and
+//!   `year:<` compare.
+//! - `-condition` or `NOT condition` negates it, `a OR b` needs either and
+//!   parentheses group, e.g. `(director:mann OR director:nolan) -year:<2000`.
+//!   `AND` may be written between conditions but is implied.
+//!
+//! Quoted text may contain `\"` and `\\`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::Film;
+
+/// Longest accepted filter, in characters.
+pub const MAX_FILTER_CHARS: usize = 500;
+/// Most conditions a filter may have.
+pub const MAX_FILTER_CONDITIONS: usize = 32;
+/// Deepest nesting of parentheses and negations.
+pub const MAX_FILTER_DEPTH: usize = 16;
+
+/// A parsed filter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// Every one of two or more filters.
+    And(Vec<Filter>),
+    /// Any of two or more filters.
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Title(TextMatch),
+    Director(TextMatch),
+    Year(Comparison, u16),
+}
+
+/// How a text field is matched, ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextMatch {
+    Contains(String),
+    Equals(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// The operator, as written in filters and SQL.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+impl TextMatch {
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            TextMatch::Contains(text) => value.to_lowercase().contains(&text.to_lowercase()),
+            TextMatch::Equals(text) => value.to_lowercase() == text.to_lowercase(),
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Filter, FilterError> {
+        let chars = input.chars().count();
+        if chars > MAX_FILTER_CHARS {
+            return Err(FilterError::new(
+                MAX_FILTER_CHARS,
+                format!("filters are limited to {} characters", MAX_FILTER_CHARS),
+            ));
+        }
+
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+            depth: 0,
+            conditions: 0,
+        };
+        let filter = parser.or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(filter),
+            Some(')') => Err(parser.error("unmatched ')'")),
+            Some(_) => Err(parser.error("expected a condition")),
+        }
+    }
+
+    /// Whether `film` passes the filter; the reference every backend's
+    /// translation must agree with.
+    pub fn matches(&self, film: &Film) -> bool {
+        match self {
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(film)),
+            Filter::Or(filters) => filters.iter().any(|filter| filter.matches(film)),
+            Filter::Not(filter) => !filter.matches(film),
+            Filter::Title(text) => text.matches(&film.title),
+            Filter::Director(text) => text.matches(&film.director),
+            Filter::Year(comparison, year) => match comparison {
+                Comparison::Eq => film.year == *year,
+                Comparison::Lt => film.year < *year,
+                Comparison::Le => film.year <= *year,
+                Comparison::Gt => film.year > *year,
+                Comparison::Ge => film.year >= *year,
+            },
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Filter::parse(input)
+    }
+}
+
+/// Why a filter couldn't be parsed, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError {
+    /// Offset of the offending character, counted in characters from 0.
+    pub position: usize,
+    pub message: String,
+}
+
+impl FilterError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+    conditions: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut filters = vec![self.and()?];
+        while self.keyword("OR") {
+            filters.push(self.and()?);
+        }
+        Ok(combine(filters, Filter::Or))
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filters = vec![];
+        loop {
+            self.skip_whitespace();
+            if matches!(self.peek(), None | Some(')')) || self.at_keyword("OR") {
+                break;
+            }
+            if !filters.is_empty() {
+                self.keyword("AND");
+            }
+            filters.push(self.unary()?);
+        }
+        if filters.is_empty() {
+            return Err(self.error("expected a condition"));
+        }
+        Ok(combine(filters, Filter::And))
+    }
+
+    fn unary(&mut self) -> Result<Filter, FilterError> {
+        self.skip_whitespace();
+        let negated = if self.peek() == Some('-') {
+            self.pos += 1;
+            true
+        } else {
+            self.keyword("NOT")
+        };
+        if !negated {
+            return self.primary();
+        }
+
+        self.nest()?;
+        let filter = self.unary()?;
+        self.depth -= 1;
+        Ok(Filter::Not(Box::new(filter)))
+    }
+
+    fn primary(&mut self) -> Result<Filter, FilterError> {
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            let open = self.pos;
+            self.pos += 1;
+            self.nest()?;
+            let filter = self.or()?;
+            self.depth -= 1;
+            self.skip_whitespace();
+            if self.peek() != Some(')') {
+                return Err(FilterError::new(open, "unmatched '('"));
+            }
+            self.pos += 1;
+            return Ok(filter);
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Filter, FilterError> {
+        let start = self.pos;
+        let field = self.take_while(|c| c.is_ascii_alphabetic() || c == '_');
+        if field.is_empty() {
+            return Err(self.error("expected a condition such as title:heat"));
+        }
+        if self.peek() != Some(':') {
+            return Err(self.error(format!("expected ':' after {:?}", field)));
+        }
+        self.pos += 1;
+
+        self.conditions += 1;
+        if self.conditions > MAX_FILTER_CONDITIONS {
+            return Err(FilterError::new(
+                start,
+                format!(
+                    "filters are limited to {} conditions",
+                    MAX_FILTER_CONDITIONS
+                ),
+            ));
+        }
+
+        match field.to_lowercase().as_str() {
+            "title" => Ok(Filter::Title(self.text_match()?)),
+            "director" => Ok(Filter::Director(self.text_match()?)),
+            "year" => {
+                let comparison = self.comparison();
+                let at = self.pos;
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                match digits.parse() {
+                    Ok(year) if self.at_value_end() => Ok(Filter::Year(comparison, year)),
+                    _ => Err(FilterError::new(at, "expected a year such as 1995")),
+                }
+            }
+            _ => Err(FilterError::new(
+                start,
+                format!(
+                    "unknown field {:?}, expected title, director or year",
+                    field
+                ),
+            )),
+        }
+    }
+
+    fn text_match(&mut self) -> Result<TextMatch, FilterError> {
+        let equals = self.peek() == Some('=');
+        if equals {
+            self.pos += 1;
+        }
+        if matches!(self.peek(), Some('<' | '>')) {
+            return Err(self.error("text can only be matched with ':' or ':='"));
+        }
+
+        let at = self.pos;
+        let text = if self.peek() == Some('"') {
+            self.quoted()?
+        } else {
+            self.take_while(|c| !c.is_whitespace() && !matches!(c, '(' | ')' | '"'))
+        };
+        if text.is_empty() {
+            return Err(FilterError::new(at, "expected text to match"));
+        }
+
+        Ok(if equals {
+            TextMatch::Equals(text)
+        } else {
+            TextMatch::Contains(text)
+        })
+    }
+
+    fn quoted(&mut self) -> Result<String, FilterError> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(FilterError::new(open, "unterminated quote")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') if matches!(self.chars.get(self.pos + 1), Some('"' | '\\')) => {
+                    text.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn comparison(&mut self) -> Comparison {
+        for comparison in [
+            Comparison::Ge,
+            Comparison::Le,
+            Comparison::Gt,
+            Comparison::Lt,
+            Comparison::Eq,
+        ] {
+            let symbol = comparison.symbol().chars().collect::<Vec<_>>();
+            if self.chars[self.pos..].starts_with(&symbol) {
+                self.pos += symbol.len();
+                return comparison;
+            }
+        }
+        Comparison::Eq
+    }
+
+    fn nest(&mut self) -> Result<(), FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_FILTER_DEPTH {
+            return Err(self.error(format!(
+                "filters may nest at most {} levels deep",
+                MAX_FILTER_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    /// Consumes `keyword` if it comes next as a whole word.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        if !self.at_keyword(keyword) {
+            return false;
+        }
+        self.pos += keyword.len();
+        true
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        let end = self.pos + keyword.len();
+        end <= self.chars.len()
+            && self.chars[self.pos..end]
+                .iter()
+                .copied()
+                .eq(keyword.chars())
+            && self
+                .chars
+                .get(end)
+                .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '-'))
+    }
+
+    fn at_value_end(&self) -> bool {
+        self.peek().is_none_or(|c| c.is_whitespace() || c == ')')
+    }
+
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&keep) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterError {
+        FilterError::new(self.pos, message)
+    }
+}
+
+/// `filters` joined by `join`, or the only one.
+fn combine(mut filters: Vec<Filter>, join: fn(Vec<Filter>) -> Filter) -> Filter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        join(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn film(title: &str, director: &str, year: u16) -> Film {
+        Film {
+            title: title.to_string(),
+            director: director.to_string(),
+            year,
+            ..Default::default()
+        }
+    }
+
+    fn error_at(input: &str) -> usize {
+        Filter::parse(input).unwrap_err().position
+    }
+
+    #[test]
+    fn conditions_are_anded_unless_ored() {
+        let filter = Filter::parse(r#"director:"Nolan" year:>=2000 -title:batman"#).unwrap();
+        assert_eq!(
+            filter,
+            Filter::And(vec![
+                Filter::Director(TextMatch::Contains("Nolan".to_string())),
+                Filter::Year(Comparison::Ge, 2000),
+                Filter::Not(Box::new(Filter::Title(TextMatch::Contains(
+                    "batman".to_string()
+                )))),
+            ])
+        );
+
+        let filter =
+            Filter::parse(r#"(director:mann OR director:="Christopher Nolan") AND NOT year:<2000"#)
+                .unwrap();
+        assert_eq!(
+            filter,
+            Filter::And(vec![
+                Filter::Or(vec![
+                    Filter::Director(TextMatch::Contains("mann".to_string())),
+                    Filter::Director(TextMatch::Equals("Christopher Nolan".to_string())),
+                ]),
+                Filter::Not(Box::new(Filter::Year(Comparison::Lt, 2000))),
+            ])
+        );
+        assert_eq!(
+            Filter::parse(r#"title:"say \"hi\"""#).unwrap(),
+            Filter::Title(TextMatch::Contains(r#"say "hi""#.to_string()))
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error_at("rating:5"), 0);
+        assert_eq!(error_at("year:>=2000 title"), 17);
+        assert_eq!(error_at("year:soon"), 5);
+        assert_eq!(error_at("year:>=20x"), 7);
+        assert_eq!(error_at(r#"title:heat director:"Mann"#), 20);
+        assert_eq!(error_at("(title:heat"), 0);
+        assert_eq!(error_at("title:heat)"), 10);
+        assert_eq!(error_at("title:>heat"), 6);
+        assert_eq!(error_at("title:heat OR"), 13);
+        assert_eq!(error_at(""), 0);
+
+        let message = Filter::parse("rating:5").unwrap_err().to_string();
+        assert_eq!(
+            message,
+            r#"unknown field "rating", expected title, director or year at position 0"#
+        );
+    }
+
+    #[test]
+    fn oversized_filters_are_rejected() {
+        let long = "title:a ".repeat(MAX_FILTER_CONDITIONS + 1);
+        assert!(Filter::parse(&long).is_err());
+        let deep = "-".repeat(MAX_FILTER_DEPTH + 1) + "title:a";
+        assert!(Filter::parse(&deep).is_err());
+        let wide = format!("title:{}", "a".repeat(MAX_FILTER_CHARS));
+        assert!(Filter::parse(&wide).is_err());
+    }
+
+    #[test]
+    fn filters_match_ignoring_case() {
+        let filter = Filter::parse(r#"director:nolan year:>=2000 -title:BATMAN"#).unwrap();
+        assert!(filter.matches(&film("The Prestige", "Christopher Nolan", 2006)));
+        assert!(!filter.matches(&film("Batman Begins", "Christopher Nolan", 2005)));
+        assert!(!filter.matches(&film("Memento", "Christopher Nolan", 1998)));
+
+        let filter = Filter::parse(r#"director:="christopher nolan" OR year:1995"#).unwrap();
+        assert!(filter.matches(&film("Heat", "Michael Mann", 1995)));
+        assert!(!filter.matches(&film("Following", "Christopher Nolan Jr.", 1998)));
+    }
+}
@@ -1,4 +1,8 @@
+pub mod filter;
 pub mod models;
+pub mod problem;
+pub mod validation;
+pub mod ws;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
@@ -13,6 +13,9 @@ pub struct Film {
     pub poster: String,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Incremented on every change; the film's `ETag`.
+    #[serde(default)]
+    pub version: i32,
 }
 
 #[cfg_attr(feature = "backend", derive(sqlx::FromRow))]
@@ -23,3 +26,418 @@ pub struct CreateFilm {
     pub year: u16,
     pub poster: String,
 }
+
+/// A JSON Merge Patch (RFC 7396) document for `PATCH /api/v1/films/{film_id}`.
+///
+/// Absent fields are left untouched. An explicit `null` removes the field,
+/// which is only allowed for the optional `poster`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct FilmPatch {
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub title: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub director: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub year: Option<Option<u16>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub poster: Option<Option<String>>,
+}
+
+impl FilmPatch {
+    /// Whether the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.director.is_none()
+            && self.year.is_none()
+            && self.poster.is_none()
+    }
+
+    /// Applies the patch to `film`; removed fields fall back to their default.
+    pub fn apply_to(&self, film: &mut Film) {
+        if let Some(title) = &self.title {
+            film.title = title.clone().unwrap_or_default();
+        }
+        if let Some(director) = &self.director {
+            film.director = director.clone().unwrap_or_default();
+        }
+        if let Some(year) = self.year {
+            film.year = year.unwrap_or_default();
+        }
+        if let Some(poster) = &self.poster {
+            film.poster = poster.clone().unwrap_or_default();
+        }
+    }
+}
+
+/// Tells a field set to `null` (`Some(None)`) apart from an absent one (`None`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Field `GET /api/v1/films` results can be sorted by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FilmSort {
+    #[default]
+    CreatedAt,
+    Title,
+    Year,
+    Director,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Query parameters accepted by `GET /api/v1/films`.
+///
+/// `director` matches case-insensitively, `title` is a case-insensitive
+/// substring match and `year_from`/`year_to` are inclusive bounds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct FilmQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// Opaque `next` token of a previous page; excludes `offset`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<FilmSort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub director: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_from: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_to: Option<u16>,
+    /// Conditions in the [`crate::filter`] language, on top of the other
+    /// filters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+/// Whether `film` passes the `director`, `title` and `year_from`/`year_to`
+/// filters of a [`FilmQuery`], whether listing films or following them.
+pub fn film_matches(
+    film: &Film,
+    director: Option<&str>,
+    title: Option<&str>,
+    year_from: Option<u16>,
+    year_to: Option<u16>,
+) -> bool {
+    if let Some(director) = director {
+        if film.director.to_lowercase() != director.to_lowercase() {
+            return false;
+        }
+    }
+    if let Some(title) = title {
+        if !film.title.to_lowercase().contains(&title.to_lowercase()) {
+            return false;
+        }
+    }
+    if matches!(year_from, Some(from) if film.year < from) {
+        return false;
+    }
+    !matches!(year_to, Some(to) if film.year > to)
+}
+
+/// A page of films returned by `GET /api/v1/films`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct FilmPage {
+    pub items: Vec<Film>,
+    pub limit: u32,
+    pub offset: u32,
+    /// Offset of the following page, `None` on the last page or when paging by cursor.
+    pub next_offset: Option<u32>,
+    /// Cursor of the following page, `None` on the last page.
+    ///
+    /// Unlike offsets, cursors don't skip or repeat films inserted or
+    /// deleted while the list is being walked.
+    pub next: Option<String>,
+}
+
+/// Query parameters accepted by `GET /api/v1/films/search`.
+///
+/// Every word of `q` must start a word of the film's title or director,
+/// ignoring case and accents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct FilmSearchQuery {
+    pub q: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// A film found by a search, with its relevance.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilmSearchHit {
+    pub film: Film,
+    /// Higher for better matches; title matches count more than director
+    /// ones. Only comparable within one result list.
+    pub score: f32,
+}
+
+/// A page of search results, best first, returned by `GET /api/v1/films/search`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FilmSearchPage {
+    pub items: Vec<FilmSearchHit>,
+    pub limit: u32,
+    pub offset: u32,
+    /// Offset of the following page, `None` on the last page.
+    pub next_offset: Option<u32>,
+}
+
+/// Field `GET /api/v1/films/suggest` completes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestField {
+    Title,
+    Director,
+}
+
+/// Query parameters accepted by `GET /api/v1/films/suggest`.
+///
+/// Suggests the values of `field` starting with `prefix`, ignoring case and
+/// accents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilmSuggestQuery {
+    pub field: SuggestField,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A value already used by films, with how many use it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilmSuggestion {
+    pub value: String,
+    pub count: u32,
+}
+
+/// Suggestions returned by `GET /api/v1/films/suggest`, most used first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct FilmSuggestions {
+    pub items: Vec<FilmSuggestion>,
+}
+
+/// What happened to a film.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FilmChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Id of a [`FilmEvent`], written `<epoch>-<seq>`.
+///
+/// `epoch` is chosen by each API process when it starts and `seq` counts the
+/// events it published since, so ids from another process, or from before a
+/// restart, are never mistaken for one of its own.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(into = "String", try_from = "String")]
+pub struct EventId {
+    pub epoch: u64,
+    pub seq: u64,
+}
+
+impl std::fmt::Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}-{}", self.epoch, self.seq)
+    }
+}
+
+impl std::str::FromStr for EventId {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid event id {:?}", id);
+        let (epoch, seq) = id.split_once('-').ok_or_else(invalid)?;
+        Ok(EventId {
+            epoch: u64::from_str_radix(epoch, 16).map_err(|_| invalid())?,
+            seq: seq.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl From<EventId> for String {
+    fn from(id: EventId) -> Self {
+        id.to_string()
+    }
+}
+
+impl TryFrom<String> for EventId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, String> {
+        id.parse()
+    }
+}
+
+/// A film change streamed by `GET /api/v1/films/events`, whose SSE event
+/// name is `kind`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilmEvent {
+    /// The SSE event id, increasing with every change; send the last one
+    /// seen as `Last-Event-ID` to resume after it.
+    pub id: EventId,
+    pub kind: FilmChangeKind,
+    /// The film after the change, or as it was when deleted.
+    pub film: Film,
+    /// The film before an update, telling clients whether it left a filter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Film>,
+}
+
+/// What an API key may do.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// Only `GET`, `HEAD` and `OPTIONS` requests.
+    #[default]
+    Read,
+    /// Any request, except managing API keys.
+    Write,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Write => "write",
+        }
+    }
+}
+
+impl TryFrom<String> for ApiKeyScope {
+    type Error = String;
+
+    fn try_from(scope: String) -> Result<Self, String> {
+        match scope.as_str() {
+            "read" => Ok(ApiKeyScope::Read),
+            "write" => Ok(ApiKeyScope::Write),
+            _ => Err(format!("unknown API key scope {:?}", scope)),
+        }
+    }
+}
+
+/// An API key as listed by `GET /api/v1/api-keys`; the key itself is only
+/// shown once, when created.
+#[cfg_attr(feature = "backend", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub id: uuid::Uuid,
+    pub name: String,
+    /// The key's first characters, telling keys apart without revealing them.
+    pub prefix: String,
+    #[sqlx(try_from = "String")]
+    pub scope: ApiKeyScope,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Set once revoked, after which the key is refused.
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Body of `POST /api/v1/api-keys`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CreateApiKey {
+    /// Who or what the key is for.
+    pub name: String,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
+}
+
+/// A new API key, returned by `POST /api/v1/api-keys`. Only a hash of
+/// `key` is stored, so it can't be shown again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_distinguishes_absent_from_null() {
+        let patch: FilmPatch =
+            serde_json::from_str(r#"{"title": "Heat", "poster": null}"#).unwrap();
+
+        assert_eq!(patch.title, Some(Some("Heat".to_string())));
+        assert_eq!(patch.poster, Some(None));
+        assert_eq!(patch.director, None);
+        assert_eq!(patch.year, None);
+    }
+
+    #[test]
+    fn patch_only_touches_supplied_fields() {
+        let mut film = Film {
+            title: "Heat".to_string(),
+            director: "Michael Mann".to_string(),
+            year: 1995,
+            poster: "https://example.com/heat.jpg".to_string(),
+            ..Default::default()
+        };
+        let patch = FilmPatch {
+            year: Some(Some(1996)),
+            poster: Some(None),
+            ..Default::default()
+        };
+
+        patch.apply_to(&mut film);
+
+        assert_eq!(film.title, "Heat");
+        assert_eq!(film.director, "Michael Mann");
+        assert_eq!(film.year, 1996);
+        assert_eq!(film.poster, "");
+    }
+
+    #[test]
+    fn event_ids_round_trip_as_text() {
+        let id = EventId {
+            epoch: 0x5f3a9c,
+            seq: 42,
+        };
+        assert_eq!(id.to_string(), "5f3a9c-42");
+        assert_eq!("5f3a9c-42".parse(), Ok(id));
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""5f3a9c-42""#);
+
+        for invalid in ["42", "5f3a9c-", "-42", "xyz-42", "5f3a9c-4-2"] {
+            assert!(invalid.parse::<EventId>().is_err(), "{}", invalid);
+        }
+    }
+}
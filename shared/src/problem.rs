@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::validation::FieldError;
+
+/// Media type of RFC 7807 error bodies.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// An RFC 7807 problem document, returned by the API for every error.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProblemDetails {
+    /// URI reference identifying the problem type, e.g. `/problems/film-not-found`.
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short, human-readable summary of the problem type.
+    pub title: String,
+    /// HTTP status code generated by the server for this occurrence.
+    pub status: u16,
+    /// Explanation specific to this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Identifier of the request that caused the problem, also sent as `x-request-id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Every field that failed validation, for `/problems/validation-failed`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_field_is_renamed_and_empty_fields_are_skipped() {
+        let problem = ProblemDetails {
+            problem_type: "/problems/film-not-found".to_string(),
+            title: "Film not found".to_string(),
+            status: 404,
+            detail: None,
+            request_id: None,
+            errors: vec![],
+        };
+
+        let json = serde_json::to_value(&problem).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "/problems/film-not-found",
+                "title": "Film not found",
+                "status": 404,
+            })
+        );
+
+        let parsed: ProblemDetails = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, problem);
+    }
+}
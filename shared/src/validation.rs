@@ -0,0 +1,291 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{CreateApiKey, CreateFilm, Film, FilmPatch};
+
+/// Earliest accepted release year, the year of the first known motion picture.
+pub const MIN_YEAR: u16 = 1888;
+/// Latest accepted release year; also keeps years well inside Postgres' `smallint`.
+pub const MAX_YEAR: u16 = 2100;
+pub const MAX_TITLE_LEN: usize = 256;
+pub const MAX_DIRECTOR_LEN: usize = 128;
+pub const MAX_POSTER_LEN: usize = 2048;
+pub const MAX_API_KEY_NAME_LEN: usize = 128;
+
+/// A single rule a field failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine readable rule name, e.g. `required`, `too_long`, `range`, `url`.
+    pub code: String,
+    pub message: String,
+}
+
+/// Every rule a value failed, so clients can report all problems at once.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct ValidationErrors(Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Errors holding one failed rule.
+    pub fn single(field: &str, code: &str, message: impl Into<String>) -> Self {
+        let mut errors = Self::new();
+        errors.add(field, code, message);
+        errors
+    }
+
+    pub fn add(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.0
+    }
+
+    /// Whether any rule failed for `field`.
+    pub fn has(&self, field: &str) -> bool {
+        self.0.iter().any(|e| e.field == field)
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages = self
+            .0
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>();
+        f.write_str(&messages.join(", "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+impl Validate for CreateFilm {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_title(&mut errors, &self.title);
+        check_director(&mut errors, &self.director);
+        check_year(&mut errors, self.year);
+        check_poster(&mut errors, &self.poster);
+        errors.into_result()
+    }
+}
+
+impl Validate for Film {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_title(&mut errors, &self.title);
+        check_director(&mut errors, &self.director);
+        check_year(&mut errors, self.year);
+        check_poster(&mut errors, &self.poster);
+        errors.into_result()
+    }
+}
+
+impl Validate for FilmPatch {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match &self.title {
+            Some(Some(title)) => check_title(&mut errors, title),
+            Some(None) => errors.add("title", "required", "can't be removed"),
+            None => {}
+        }
+        match &self.director {
+            Some(Some(director)) => check_director(&mut errors, director),
+            Some(None) => errors.add("director", "required", "can't be removed"),
+            None => {}
+        }
+        match self.year {
+            Some(Some(year)) => check_year(&mut errors, year),
+            Some(None) => errors.add("year", "required", "can't be removed"),
+            None => {}
+        }
+        if let Some(Some(poster)) = &self.poster {
+            check_poster(&mut errors, poster);
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for CreateApiKey {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_text(&mut errors, "name", &self.name, MAX_API_KEY_NAME_LEN);
+        errors.into_result()
+    }
+}
+
+pub fn check_title(errors: &mut ValidationErrors, title: &str) {
+    check_text(errors, "title", title, MAX_TITLE_LEN);
+}
+
+pub fn check_director(errors: &mut ValidationErrors, director: &str) {
+    check_text(errors, "director", director, MAX_DIRECTOR_LEN);
+}
+
+pub fn check_year(errors: &mut ValidationErrors, year: u16) {
+    if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+        errors.add(
+            "year",
+            "range",
+            format!("must be between {} and {}", MIN_YEAR, MAX_YEAR),
+        );
+    }
+}
+
+/// Posters are optional, but when given must be an absolute http(s) URL.
+pub fn check_poster(errors: &mut ValidationErrors, poster: &str) {
+    if poster.is_empty() {
+        return;
+    }
+    if poster.chars().count() > MAX_POSTER_LEN {
+        errors.add(
+            "poster",
+            "too_long",
+            format!("must be at most {} characters", MAX_POSTER_LEN),
+        );
+        return;
+    }
+    match url::Url::parse(poster) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        _ => errors.add("poster", "url", "must be an absolute http or https URL"),
+    }
+}
+
+fn check_text(errors: &mut ValidationErrors, field: &str, value: &str, max_len: usize) {
+    if value.trim().is_empty() {
+        errors.add(field, "required", "must not be empty");
+    } else if value.chars().count() > max_len {
+        errors.add(
+            field,
+            "too_long",
+            format!("must be at most {} characters", max_len),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_film() -> CreateFilm {
+        CreateFilm {
+            title: "Star Wars: The Force Awakens".to_string(),
+            director: "J. J. Abrams".to_string(),
+            year: 2015,
+            poster: "https://example.com/posters/force-awakens.jpg".to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_film_passes() {
+        assert_eq!(valid_film().validate(), Ok(()));
+
+        let without_poster = CreateFilm {
+            poster: String::new(),
+            ..valid_film()
+        };
+        assert_eq!(without_poster.validate(), Ok(()));
+    }
+
+    #[test]
+    fn every_offending_field_is_reported() {
+        let film = CreateFilm {
+            title: "   ".to_string(),
+            director: String::new(),
+            year: 65535,
+            poster: "AWAKEN THE FORCE WITHIN".to_string(),
+        };
+
+        let errors = film.validate().unwrap_err();
+        assert_eq!(errors.errors().len(), 4);
+        for field in ["title", "director", "year", "poster"] {
+            assert!(errors.has(field), "missing error for {}", field);
+        }
+    }
+
+    #[test]
+    fn year_bounds_are_inclusive() {
+        for (year, ok) in [
+            (0, false),
+            (MIN_YEAR - 1, false),
+            (MIN_YEAR, true),
+            (MAX_YEAR, true),
+            (MAX_YEAR + 1, false),
+        ] {
+            let film = CreateFilm {
+                year,
+                ..valid_film()
+            };
+            assert_eq!(film.validate().is_ok(), ok, "year {}", year);
+        }
+    }
+
+    #[test]
+    fn poster_must_be_an_http_url() {
+        for poster in ["ftp://example.com/a.jpg", "/posters/a.jpg", "https://"] {
+            let film = CreateFilm {
+                poster: poster.to_string(),
+                ..valid_film()
+            };
+            let errors = film.validate().unwrap_err();
+            assert!(errors.has("poster"), "{} should be rejected", poster);
+        }
+    }
+
+    #[test]
+    fn patch_checks_supplied_fields_only() {
+        let patch = FilmPatch {
+            year: Some(Some(1995)),
+            poster: Some(None),
+            ..Default::default()
+        };
+        assert_eq!(patch.validate(), Ok(()));
+
+        let patch = FilmPatch {
+            title: Some(None),
+            year: Some(Some(0)),
+            ..Default::default()
+        };
+        let errors = patch.validate().unwrap_err();
+        assert!(errors.has("title"));
+        assert!(errors.has("year"));
+        assert!(!errors.has("director"));
+    }
+
+    #[test]
+    fn too_long_title_is_rejected() {
+        let film = CreateFilm {
+            title: "x".repeat(MAX_TITLE_LEN + 1),
+            ..valid_film()
+        };
+        let errors = film.validate().unwrap_err();
+        assert_eq!(errors.errors()[0].code, "too_long");
+    }
+}
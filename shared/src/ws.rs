@@ -0,0 +1,213 @@
+//! Messages exchanged over the `/api/v1/ws` WebSocket.
+//!
+//! Every message is a JSON text frame holding an [`Envelope`]: the protocol
+//! version next to the message's `type` and fields, e.g.
+//!
+//! ```json
+//! {"version": 1, "type": "subscribe", "subscription": "nolan", "topic": {"filter": {"director": "Christopher Nolan"}}}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{film_matches, Film, FilmEvent};
+
+/// Version of the protocol described here, bumped on incompatible changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A message with the protocol version it was written for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Envelope<T> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub message: T,
+}
+
+impl<T> Envelope<T> {
+    /// Wraps `message` for the current [`PROTOCOL_VERSION`].
+    pub fn new(message: T) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            message,
+        }
+    }
+}
+
+/// Sent by clients.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Follows changes to `topic` under a name of the client's choosing,
+    /// replacing any subscription already using it.
+    Subscribe {
+        subscription: String,
+        topic: Topic,
+    },
+    Unsubscribe {
+        subscription: String,
+    },
+    /// Tells the film's subscribers this client is editing it, until it
+    /// stops or disconnects.
+    StartEditing {
+        film_id: Uuid,
+        /// Shown to the other editors.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    StopEditing {
+        film_id: Uuid,
+    },
+    Ping,
+}
+
+/// What a subscription follows.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    /// These films, including who is editing them.
+    Films(Vec<Uuid>),
+    /// Every film matching the filter before or after a change.
+    Filter(FilmFilter),
+}
+
+/// Filters of `GET /api/v1/films`, with the same meaning: `director` matches
+/// case-insensitively, `title` is a case-insensitive substring match and
+/// `year_from`/`year_to` are inclusive bounds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct FilmFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub director: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_from: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_to: Option<u16>,
+}
+
+impl FilmFilter {
+    pub fn matches(&self, film: &Film) -> bool {
+        film_matches(
+            film,
+            self.director.as_deref(),
+            self.title.as_deref(),
+            self.year_from,
+            self.year_to,
+        )
+    }
+}
+
+/// Sent by the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// First message on every connection.
+    Welcome {
+        connection: Uuid,
+    },
+    Subscribed {
+        subscription: String,
+    },
+    Unsubscribed {
+        subscription: String,
+    },
+    /// A change seen by `subscription`.
+    Changed {
+        subscription: String,
+        /// Whether the film is part of the subscription after the change;
+        /// `false` once it was deleted or updated out of a filter.
+        matching: bool,
+        event: Box<FilmEvent>,
+    },
+    /// Everyone editing a film followed by id, sent on subscribing and
+    /// whenever it changes.
+    Presence {
+        film_id: Uuid,
+        editors: Vec<Editor>,
+    },
+    /// Changes were missed; reload whatever the subscriptions show.
+    Resync,
+    Pong,
+    /// A client message was refused; the connection stays open.
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// A connection editing a film.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Editor {
+    pub connection: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The envelope's version isn't [`PROTOCOL_VERSION`].
+    UnsupportedVersion,
+    /// Not JSON, or not a known message.
+    Malformed,
+    /// A subscription, name, id list or set of edited films beyond the
+    /// server's limits.
+    LimitExceeded,
+    UnknownSubscription,
+    /// No film has the id a client started editing.
+    UnknownFilm,
+    /// The server can't check the message right now; it may be sent again.
+    Unavailable,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelopes_carry_the_version_beside_the_message() {
+        let film_id = Uuid::new_v4();
+        let message = Envelope::new(ClientMessage::Subscribe {
+            subscription: "heat".to_string(),
+            topic: Topic::Films(vec![film_id]),
+        });
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "type": "subscribe",
+                "subscription": "heat",
+                "topic": { "films": [film_id] },
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<Envelope<_>>(json).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn filters_match_like_the_film_list() {
+        let film = Film {
+            title: "The Dark Knight".to_string(),
+            director: "Christopher Nolan".to_string(),
+            year: 2008,
+            ..Default::default()
+        };
+        let filter: FilmFilter = serde_json::from_str(
+            r#"{"director": "christopher nolan", "title": "KNIGHT", "year_from": 2008}"#,
+        )
+        .unwrap();
+
+        assert!(filter.matches(&film));
+        assert!(FilmFilter::default().matches(&film));
+        let later = FilmFilter {
+            year_from: Some(2009),
+            ..Default::default()
+        };
+        assert!(!later.matches(&film));
+    }
+}